@@ -1,4 +1,8 @@
 fn main() {
+    // pure-Rust kernels are used instead of freq_modulation.c
+    if std::env::var_os("CARGO_FEATURE_PORTABLE").is_some() {
+        return;
+    }
     let fname = "freq_modulation.c";
     let fpath1 = format!("cfiles/{}", fname);
     // let fpath2 = format!("cfiles/{}", "resampler.c");
@@ -1,5 +1,4 @@
 #include "./freq_modulation.h"
-#include "trigo.h"
 
 #ifdef ENABLE_DEBUG_UTILS
 #include <math.h>
@@ -195,11 +194,7 @@ void fm_modulate(f64 *restrict output_signal, const f64 *restrict input_signal,
         _mm256_cvtsd_f64(_mm256_permute_pd(sums, _MM_SHUFFLE(3, 1, 2, 3)));
     f64x4 integral = _mm256_fmadd_pd(coeff, sums, angle);
     // 変調
-    #ifdef __SVML
     f64x4 sigs = _mm256_cos_pd(integral);
-    #else
-    f64x4 sigs = _mm256_fcos_pd(integral);
-    #endif
     _mm256_store_pd(output_signal + i, sigs);
     angle = _mm256_add_pd(angle, phi);
   }
@@ -220,13 +215,8 @@ void fm_modulate(f64 *restrict output_signal, const f64 *restrict input_signal,
     f64x4 angle_tmp = _mm256_add_pd(angle2, phi);
     f64x4 modulated_angle1 = _mm256_fmadd_pd(coeff, s1_in, angle);
     f64x4 modulated_angle2 = _mm256_fmadd_pd(coeff, s2_in, angle2);
-    #ifdef __SVML
     f64x4 cos_value1 = _mm256_cos_pd(modulated_angle1);
     f64x4 cos_value2 = _mm256_cos_pd(modulated_angle2);
-    #else
-    f64x4 cos_value1 = _mm256_fcos_pd(modulated_angle1);
-    f64x4 cos_value2 = _mm256_fcos_pd(modulated_angle2);
-    #endif
 
     
     // f64x4 cos_value1 = _mm256_cos_pd(angle);
@@ -491,17 +481,10 @@ void convert_intermediate_freq(f64 output_signal[], const f64 input_signal[],
   f64x4 full_delta_angle = _mm256_set1_pd(info->delta_angle * 4);
 #pragma unroll
   for (usize i = 0, j = 0; i < buf_len; i += 8) {
-    #ifdef __SVML
 f64x4 cos_value1 = _mm256_cos_pd(angle);
     angle = _mm256_add_pd(angle, full_delta_angle);
     f64x4 cos_value2 = _mm256_cos_pd(angle);
     angle = _mm256_add_pd(angle, full_delta_angle);
-    #else
-f64x4 cos_value1 = _mm256_fcos_pd(angle);
-    angle = _mm256_add_pd(angle, full_delta_angle);
-    f64x4 cos_value2 = _mm256_fcos_pd(angle);
-    angle = _mm256_add_pd(angle, full_delta_angle);
-    #endif
     
     f64x4 signal1 = _mm256_load_pd(input_signal + i);     // 0 1 2 3
     f64x4 signal2 = _mm256_load_pd(input_signal + i + 4); // 0 1 2 3
@@ -807,7 +790,7 @@ void filtering_with_resample(f64 dst[], const f64 input[],
     // for s3
     f64x2 v3 = _mm_mul_pd(y1, d1);
     f64x2 w3 = _mm_fnmsub_pd(y2, d0, v3);
-    f64x2 x3 = _mm_sub_pd(s3, s0);
+    f64x2 x3 = _mm_sub_pd(s3, s1);
     f64x2 y3 = _mm_fmadd_pd(c0, x3, w3);
     // set next stage
     stage_lo = _mm_shuffle_pd(y0, y1, 0b11); // 0' 1'
@@ -875,7 +858,7 @@ void filtering(f64 dst[], const f64 input[], FilteringInfo *restrict info,
     // for s3
     f64x2 v3 = _mm_mul_pd(y1, d1);
     f64x2 w3 = _mm_fnmsub_pd(y2, d0, v3);
-    f64x2 x3 = _mm_sub_pd(s3, s0);
+    f64x2 x3 = _mm_sub_pd(s3, s1);
     f64x2 y3 = _mm_fmadd_pd(c0, x3, w3);
     // set next stage
     stage_lo = _mm_shuffle_pd(y0, y1, 0b11); // 0' 1'
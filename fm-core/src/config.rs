@@ -0,0 +1,840 @@
+/**
+ * シミュレーションのパラメータ
+ * `FmRadioSimConfig::builder` で作成し、`build` 時に組み合わせの整合性を検査する。
+*/
+use crate::modulation_modules::{
+    adc::AdcConfig,
+    audio_processor::AudioProcessorConfig,
+    channel::{ChannelConfig, Fading, ImpulseNoise, Interferer, MultipathTap, NoiseLevel},
+    composite::CompositeSignal,
+    front_end::FrontEndConfig,
+    get_8x_sample_rate,
+    if_stage::{Agc, Limiter},
+    mpx::MpxConfig,
+    oscillator::OscillatorConfig,
+    rds::{self, RdsConfig},
+    superhet::{Preselector, SuperhetConfig},
+};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FmRadioSimConfig {
+    audio_sample_rate: usize,
+    buffer_size: usize,
+    carrier_freq: f64,
+    tuned_freq: f64,
+    composite_sample_rate: usize,
+    rf_sample_rate: usize,
+    decimation: usize,
+    intermediate_freq: f64,
+    if_bandwidth: f64,
+    signal_max_freq: f64,
+    deviation: f64,
+    pilot_level: f64,
+    tx_emphasis_tau: f64,
+    rx_emphasis_tau: f64,
+    channel: ChannelConfig,
+    tx_oscillator: OscillatorConfig,
+    rx_oscillator: OscillatorConfig,
+    front_end: Option<FrontEndConfig>,
+    superhet: SuperhetConfig,
+    adc: Option<AdcConfig>,
+    agc: Option<Agc>,
+    limiter: Option<Limiter>,
+    rds: Option<RdsConfig>,
+    rds_decoder: bool,
+    audio_processor: Option<AudioProcessorConfig>,
+    mpx: MpxConfig,
+}
+impl FmRadioSimConfig {
+    // pub const COMPOSITE_SAMPLE_RATE: usize = 125_000;
+    pub const DEFAULT_COMPOSITE_SAMPLE_RATE: usize = 192_000;
+    /// RFのサンプルレートの下限。実際の値はコンポジットのサンプルレートの16の倍数倍に切り上げる
+    pub const DEFAULT_MIN_RF_SAMPLE_RATE: usize = 185_000_000;
+    /// カーネル (filtering_with_resample) は 1/4 への間引きのみ対応
+    pub const DEFAULT_DECIMATION: usize = 4;
+    pub const DEFAULT_INTERMEDIATE_FREQ: f64 = 10_700_000.; // JISC6421:1994
+    /// 中間周波数帯のBPFの帯域幅 (-3dB)
+    pub const DEFAULT_IF_BANDWIDTH: f64 = 1_860_000.;
+    pub const DEFAULT_SIGNAL_MAX_FREQ: f64 = CompositeSignal::MAX_FREQ * 2.; // x2 Composite freq max
+    /// 最大周波数偏移 (コンポジット信号の振幅 1 に対応)
+    pub const DEFAULT_DEVIATION: f64 = 75_000.;
+    /// パイロット信号のレベル (最大周波数偏移に対する比)
+    pub const DEFAULT_PILOT_LEVEL: f64 = 0.1;
+    /// エンファシスの時定数 [μs] (日本・欧州: 50μs、米国・韓国: 75μs)
+    pub const DEFAULT_EMPHASIS_TAU: f64 = 50.;
+    /// RFのサンプルレートはコンポジットのサンプルレートのこの倍数倍である必要がある
+    const RF_RATE_ALIGNMENT: usize = 16;
+
+    /// buffer_size: 内部で処理するブロックの長さ。`process` に渡す長さは任意
+    pub fn builder(
+        audio_sample_rate: usize,
+        buffer_size: usize,
+        carrier_freq: f64,
+    ) -> FmRadioSimConfigBuilder {
+        FmRadioSimConfigBuilder {
+            audio_sample_rate,
+            buffer_size,
+            carrier_freq,
+            tuned_freq: None,
+            composite_sample_rate: Self::DEFAULT_COMPOSITE_SAMPLE_RATE,
+            rf_sample_rate: None,
+            decimation: Self::DEFAULT_DECIMATION,
+            intermediate_freq: Self::DEFAULT_INTERMEDIATE_FREQ,
+            if_bandwidth: Self::DEFAULT_IF_BANDWIDTH,
+            signal_max_freq: Self::DEFAULT_SIGNAL_MAX_FREQ,
+            deviation: Self::DEFAULT_DEVIATION,
+            pilot_level: Self::DEFAULT_PILOT_LEVEL,
+            tx_emphasis_tau: Self::DEFAULT_EMPHASIS_TAU,
+            rx_emphasis_tau: Self::DEFAULT_EMPHASIS_TAU,
+            channel: ChannelConfig::default(),
+            tx_oscillator: OscillatorConfig::default(),
+            rx_oscillator: OscillatorConfig::default(),
+            front_end: None,
+            superhet: SuperhetConfig::default(),
+            adc: None,
+            agc: None,
+            limiter: None,
+            rds: None,
+            rds_decoder: false,
+            audio_processor: None,
+            mpx: MpxConfig::default(),
+        }
+    }
+    pub fn audio_sample_rate(&self) -> usize {
+        self.audio_sample_rate
+    }
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+    /// 送信側の搬送波周波数
+    pub fn carrier_freq(&self) -> f64 {
+        self.carrier_freq
+    }
+    /// 受信側の同調周波数 (局部発振周波数 = 同調周波数 - 中間周波数)
+    pub fn tuned_freq(&self) -> f64 {
+        self.tuned_freq
+    }
+    pub fn composite_sample_rate(&self) -> usize {
+        self.composite_sample_rate
+    }
+    pub fn rf_sample_rate(&self) -> usize {
+        self.rf_sample_rate
+    }
+    /// RF -> 中間周波数帯の間引き率
+    pub fn decimation(&self) -> usize {
+        self.decimation
+    }
+    pub fn intermediate_sample_rate(&self) -> usize {
+        self.rf_sample_rate / self.decimation
+    }
+    pub fn intermediate_freq(&self) -> f64 {
+        self.intermediate_freq
+    }
+    pub fn if_bandwidth(&self) -> f64 {
+        self.if_bandwidth
+    }
+    pub fn signal_max_freq(&self) -> f64 {
+        self.signal_max_freq
+    }
+    pub fn deviation(&self) -> f64 {
+        self.deviation
+    }
+    pub fn pilot_level(&self) -> f64 {
+        self.pilot_level
+    }
+    /// 副搬送波 (パイロットと RDS) の振幅の和。音声はその残りの範囲に収める
+    pub fn subcarrier_level(&self) -> f64 {
+        self.pilot_level + self.rds.as_ref().map_or(0., |rds| rds.injection)
+    }
+    /// 送信側のプリエンファシスの時定数 [μs] (0: なし)
+    pub fn tx_emphasis_tau(&self) -> f64 {
+        self.tx_emphasis_tau
+    }
+    /// 受信側のディエンファシスの時定数 [μs] (0: なし)
+    pub fn rx_emphasis_tau(&self) -> f64 {
+        self.rx_emphasis_tau
+    }
+    /// 送信機と受信機の間の伝搬路
+    pub fn channel(&self) -> &ChannelConfig {
+        &self.channel
+    }
+    /// 送信機の搬送波の発振器
+    pub fn tx_oscillator(&self) -> &OscillatorConfig {
+        &self.tx_oscillator
+    }
+    /// 受信機の局部発振器
+    pub fn rx_oscillator(&self) -> &OscillatorConfig {
+        &self.rx_oscillator
+    }
+    /// 受信機の RF 増幅器・ミキサーの非線形性 (None: 線形)
+    pub fn front_end(&self) -> Option<FrontEndConfig> {
+        self.front_end
+    }
+    /// 受信機の構成 (プリセレクタ・イメージ抑圧・2 回目の周波数変換)
+    pub fn superhet(&self) -> &SuperhetConfig {
+        &self.superhet
+    }
+    /// 中間周波数の A/D 変換器 (None: 量子化なし)
+    pub fn adc(&self) -> Option<AdcConfig> {
+        self.adc
+    }
+    /// 復調前の中間周波数段の AGC (None: なし)
+    pub fn agc(&self) -> Option<Agc> {
+        self.agc
+    }
+    /// 復調前の中間周波数段のリミッタ (None: なし)
+    pub fn limiter(&self) -> Option<Limiter> {
+        self.limiter
+    }
+    /// コンポジット信号に加える RDS (None: なし)
+    pub fn rds(&self) -> Option<&RdsConfig> {
+        self.rds.as_ref()
+    }
+    /// 受信側で RDS を復号する
+    pub fn rds_decoder(&self) -> bool {
+        self.rds_decoder
+    }
+    /// ステレオエンコーダの前の音声プロセッサ (None: なし)
+    pub fn audio_processor(&self) -> Option<&AudioProcessorConfig> {
+        self.audio_processor.as_ref()
+    }
+    /// コンポジット信号のクリッパ・MPX 電力の制限
+    pub fn mpx(&self) -> &MpxConfig {
+        &self.mpx
+    }
+    pub(crate) fn set_carrier_freq(&mut self, freq: f64) -> Result<(), ConfigError> {
+        check_carrier_freq(freq, self.rf_sample_rate)?;
+        check_interferer_freqs(freq, &self.channel.interferers, self.rf_sample_rate)?;
+        self.carrier_freq = freq;
+        Ok(())
+    }
+    pub(crate) fn set_tuned_freq(&mut self, freq: f64) -> Result<(), ConfigError> {
+        check_tuned_freq(freq, self.intermediate_freq, self.rf_sample_rate)?;
+        check_preselector(freq, self.superhet.preselector, self.rf_sample_rate)?;
+        self.tuned_freq = freq;
+        Ok(())
+    }
+    pub(crate) fn set_noise(&mut self, noise: Option<NoiseLevel>) -> Result<(), ConfigError> {
+        if let Some(noise) = &noise {
+            noise.validate()?;
+        }
+        self.channel.noise = noise;
+        Ok(())
+    }
+    pub(crate) fn set_multipath(&mut self, taps: &[MultipathTap]) -> Result<(), ConfigError> {
+        for tap in taps {
+            tap.validate()?;
+        }
+        self.channel.multipath = taps.to_vec();
+        Ok(())
+    }
+    pub(crate) fn set_fading(&mut self, fading: Option<Fading>) -> Result<(), ConfigError> {
+        if let Some(fading) = &fading {
+            fading.validate()?;
+        }
+        self.channel.fading = fading;
+        Ok(())
+    }
+    pub(crate) fn set_impulse_noise(
+        &mut self,
+        noise: Option<ImpulseNoise>,
+    ) -> Result<(), ConfigError> {
+        if let Some(noise) = &noise {
+            noise.validate()?;
+        }
+        self.channel.impulse_noise = noise;
+        Ok(())
+    }
+    pub(crate) fn set_interferers(
+        &mut self,
+        interferers: &[Interferer],
+    ) -> Result<(), ConfigError> {
+        for interferer in interferers {
+            interferer.validate()?;
+        }
+        check_interferer_freqs(self.carrier_freq, interferers, self.rf_sample_rate)?;
+        self.channel.interferers = interferers.to_vec();
+        Ok(())
+    }
+    pub(crate) fn set_tx_oscillator(
+        &mut self,
+        config: &OscillatorConfig,
+    ) -> Result<(), ConfigError> {
+        config.validate()?;
+        self.tx_oscillator = config.clone();
+        Ok(())
+    }
+    pub(crate) fn set_rx_oscillator(
+        &mut self,
+        config: &OscillatorConfig,
+    ) -> Result<(), ConfigError> {
+        config.validate()?;
+        self.rx_oscillator = config.clone();
+        Ok(())
+    }
+    pub(crate) fn set_front_end(
+        &mut self,
+        front_end: Option<FrontEndConfig>,
+    ) -> Result<(), ConfigError> {
+        if let Some(front_end) = &front_end {
+            front_end.validate()?;
+        }
+        self.front_end = front_end;
+        Ok(())
+    }
+    pub(crate) fn set_adc(&mut self, adc: Option<AdcConfig>) -> Result<(), ConfigError> {
+        if let Some(adc) = &adc {
+            adc.validate()?;
+        }
+        self.adc = adc;
+        Ok(())
+    }
+    pub(crate) fn set_agc(&mut self, agc: Option<Agc>) -> Result<(), ConfigError> {
+        if let Some(agc) = &agc {
+            agc.validate()?;
+        }
+        self.agc = agc;
+        Ok(())
+    }
+    pub(crate) fn set_limiter(&mut self, limiter: Option<Limiter>) -> Result<(), ConfigError> {
+        if let Some(limiter) = &limiter {
+            limiter.validate()?;
+        }
+        self.limiter = limiter;
+        Ok(())
+    }
+    pub(crate) fn set_rds(&mut self, rds: Option<&RdsConfig>) -> Result<(), ConfigError> {
+        if let Some(rds) = rds {
+            check_rds(rds, self.pilot_level, self.composite_sample_rate)?;
+        }
+        self.rds = rds.cloned();
+        Ok(())
+    }
+    pub(crate) fn set_rds_decoder(&mut self, enabled: bool) -> Result<(), ConfigError> {
+        if enabled {
+            check_nyquist("rds", rds::MAX_FREQ, self.composite_sample_rate)?;
+        }
+        self.rds_decoder = enabled;
+        Ok(())
+    }
+    pub(crate) fn set_mpx(&mut self, mpx: &MpxConfig) -> Result<(), ConfigError> {
+        mpx.validate()?;
+        self.mpx = *mpx;
+        Ok(())
+    }
+    pub(crate) fn set_channel_seed(&mut self, seed: u64) {
+        self.channel.seed = seed;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FmRadioSimConfigBuilder {
+    audio_sample_rate: usize,
+    buffer_size: usize,
+    carrier_freq: f64,
+    tuned_freq: Option<f64>,
+    composite_sample_rate: usize,
+    rf_sample_rate: Option<usize>,
+    decimation: usize,
+    intermediate_freq: f64,
+    if_bandwidth: f64,
+    signal_max_freq: f64,
+    deviation: f64,
+    pilot_level: f64,
+    tx_emphasis_tau: f64,
+    rx_emphasis_tau: f64,
+    channel: ChannelConfig,
+    tx_oscillator: OscillatorConfig,
+    rx_oscillator: OscillatorConfig,
+    front_end: Option<FrontEndConfig>,
+    superhet: SuperhetConfig,
+    adc: Option<AdcConfig>,
+    agc: Option<Agc>,
+    limiter: Option<Limiter>,
+    rds: Option<RdsConfig>,
+    rds_decoder: bool,
+    audio_processor: Option<AudioProcessorConfig>,
+    mpx: MpxConfig,
+}
+impl FmRadioSimConfigBuilder {
+    /// 指定しない場合は搬送波周波数に同調する
+    pub fn tuned_freq(mut self, freq: f64) -> Self {
+        self.tuned_freq = Some(freq);
+        self
+    }
+    pub fn composite_sample_rate(mut self, fs: usize) -> Self {
+        self.composite_sample_rate = fs;
+        self
+    }
+    /// 指定しない場合は `DEFAULT_MIN_RF_SAMPLE_RATE` 以上で条件を満たす最小の値
+    pub fn rf_sample_rate(mut self, fs: usize) -> Self {
+        self.rf_sample_rate = Some(fs);
+        self
+    }
+    pub fn decimation(mut self, ratio: usize) -> Self {
+        self.decimation = ratio;
+        self
+    }
+    pub fn intermediate_freq(mut self, freq: f64) -> Self {
+        self.intermediate_freq = freq;
+        self
+    }
+    pub fn if_bandwidth(mut self, bandwidth: f64) -> Self {
+        self.if_bandwidth = bandwidth;
+        self
+    }
+    /// 復調時のLPFのカットオフ周波数
+    pub fn signal_max_freq(mut self, freq: f64) -> Self {
+        self.signal_max_freq = freq;
+        self
+    }
+    pub fn deviation(mut self, deviation: f64) -> Self {
+        self.deviation = deviation;
+        self
+    }
+    pub fn pilot_level(mut self, level: f64) -> Self {
+        self.pilot_level = level;
+        self
+    }
+    /// 送信側と受信側の両方の時定数 [μs]。0 の場合はエンファシスを掛けない
+    pub fn emphasis_tau(mut self, tau: f64) -> Self {
+        self.tx_emphasis_tau = tau;
+        self.rx_emphasis_tau = tau;
+        self
+    }
+    /// 送信側だけの時定数 [μs]。受信側と異なる値にすると周波数特性が傾く
+    pub fn tx_emphasis_tau(mut self, tau: f64) -> Self {
+        self.tx_emphasis_tau = tau;
+        self
+    }
+    /// 受信側だけの時定数 [μs]
+    pub fn rx_emphasis_tau(mut self, tau: f64) -> Self {
+        self.rx_emphasis_tau = tau;
+        self
+    }
+    pub fn channel(mut self, channel: ChannelConfig) -> Self {
+        self.channel = channel;
+        self
+    }
+    pub fn tx_oscillator(mut self, oscillator: OscillatorConfig) -> Self {
+        self.tx_oscillator = oscillator;
+        self
+    }
+    pub fn rx_oscillator(mut self, oscillator: OscillatorConfig) -> Self {
+        self.rx_oscillator = oscillator;
+        self
+    }
+    pub fn front_end(mut self, front_end: FrontEndConfig) -> Self {
+        self.front_end = Some(front_end);
+        self
+    }
+    pub fn superhet(mut self, superhet: SuperhetConfig) -> Self {
+        self.superhet = superhet;
+        self
+    }
+    pub fn adc(mut self, adc: AdcConfig) -> Self {
+        self.adc = Some(adc);
+        self
+    }
+    pub fn agc(mut self, agc: Agc) -> Self {
+        self.agc = Some(agc);
+        self
+    }
+    pub fn limiter(mut self, limiter: Limiter) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+    pub fn rds(mut self, rds: RdsConfig) -> Self {
+        self.rds = Some(rds);
+        self
+    }
+    pub fn rds_decoder(mut self, enabled: bool) -> Self {
+        self.rds_decoder = enabled;
+        self
+    }
+    /// 先読みのリミッタの分だけ遅延が増えるので、処理中には変更できない
+    pub fn audio_processor(mut self, processor: AudioProcessorConfig) -> Self {
+        self.audio_processor = Some(processor);
+        self
+    }
+    pub fn mpx(mut self, mpx: MpxConfig) -> Self {
+        self.mpx = mpx;
+        self
+    }
+    pub fn build(self) -> Result<FmRadioSimConfig, ConfigError> {
+        use ConfigError::*;
+        for (name, value) in [
+            ("audio_sample_rate", self.audio_sample_rate as f64),
+            ("buffer_size", self.buffer_size as f64),
+            ("carrier_freq", self.carrier_freq),
+            ("composite_sample_rate", self.composite_sample_rate as f64),
+            ("intermediate_freq", self.intermediate_freq),
+            ("if_bandwidth", self.if_bandwidth),
+            ("signal_max_freq", self.signal_max_freq),
+            ("deviation", self.deviation),
+        ] {
+            check_positive(name, value)?;
+        }
+        for (name, value) in [
+            ("tx_emphasis_tau", self.tx_emphasis_tau),
+            ("rx_emphasis_tau", self.rx_emphasis_tau),
+        ] {
+            if !value.is_finite() {
+                return Err(NotFinite { name, value });
+            }
+            if value < 0. {
+                return Err(Negative { name, value });
+            }
+        }
+        if !(0. ..1.).contains(&self.pilot_level) {
+            return Err(PilotLevelOutOfRange(self.pilot_level));
+        }
+        if self.decimation != FmRadioSimConfig::DEFAULT_DECIMATION {
+            return Err(UnsupportedDecimation(self.decimation));
+        }
+        let composite_sample_rate = self.composite_sample_rate;
+        check_nyquist(
+            "composite",
+            CompositeSignal::MAX_FREQ,
+            composite_sample_rate,
+        )?;
+        let rf_sample_rate = self.rf_sample_rate.unwrap_or_else(|| {
+            get_8x_sample_rate(
+                FmRadioSimConfig::DEFAULT_MIN_RF_SAMPLE_RATE,
+                composite_sample_rate,
+            )
+        });
+        if rf_sample_rate % (composite_sample_rate * FmRadioSimConfig::RF_RATE_ALIGNMENT) != 0 {
+            return Err(RfRateNotMultiple {
+                rf_sample_rate,
+                composite_sample_rate,
+                alignment: FmRadioSimConfig::RF_RATE_ALIGNMENT,
+            });
+        }
+        check_carrier_freq(self.carrier_freq, rf_sample_rate)?;
+        let tuned_freq = self.tuned_freq.unwrap_or(self.carrier_freq);
+        check_tuned_freq(tuned_freq, self.intermediate_freq, rf_sample_rate)?;
+        let intermediate_sample_rate = rf_sample_rate / self.decimation;
+        check_nyquist(
+            "if_bandwidth",
+            self.intermediate_freq + self.if_bandwidth / 2.,
+            intermediate_sample_rate,
+        )?;
+        if self.if_bandwidth >= self.intermediate_freq * 2. {
+            return Err(IfBandwidthTooWide {
+                if_bandwidth: self.if_bandwidth,
+                intermediate_freq: self.intermediate_freq,
+            });
+        }
+        check_nyquist(
+            "signal_max_freq",
+            self.signal_max_freq,
+            intermediate_sample_rate,
+        )?;
+        self.channel.validate()?;
+        self.tx_oscillator.validate()?;
+        self.rx_oscillator.validate()?;
+        if let Some(front_end) = &self.front_end {
+            front_end.validate()?;
+        }
+        self.superhet.validate()?;
+        if let Some(adc) = &self.adc {
+            adc.validate()?;
+        }
+        if let Some(agc) = &self.agc {
+            agc.validate()?;
+        }
+        if let Some(limiter) = &self.limiter {
+            limiter.validate()?;
+        }
+        if let Some(rds) = &self.rds {
+            check_rds(rds, self.pilot_level, composite_sample_rate)?;
+        }
+        if self.rds_decoder {
+            check_nyquist("rds", rds::MAX_FREQ, composite_sample_rate)?;
+        }
+        if let Some(processor) = &self.audio_processor {
+            processor.validate()?;
+        }
+        self.mpx.validate()?;
+        check_preselector(tuned_freq, self.superhet.preselector, rf_sample_rate)?;
+        if let Some(second_if) = self.superhet.second_if {
+            // 2 回目の局部発振 (1 回目の中間周波数 - 2 回目の中間周波数) が正である必要がある
+            if second_if.freq >= self.intermediate_freq {
+                return Err(SecondIfAboveFirstIf {
+                    second_if: second_if.freq,
+                    intermediate_freq: self.intermediate_freq,
+                });
+            }
+            if second_if.bandwidth >= second_if.freq * 2. {
+                return Err(IfBandwidthTooWide {
+                    if_bandwidth: second_if.bandwidth,
+                    intermediate_freq: second_if.freq,
+                });
+            }
+        }
+        check_interferer_freqs(self.carrier_freq, &self.channel.interferers, rf_sample_rate)?;
+        Ok(FmRadioSimConfig {
+            audio_sample_rate: self.audio_sample_rate,
+            buffer_size: self.buffer_size,
+            carrier_freq: self.carrier_freq,
+            tuned_freq,
+            composite_sample_rate,
+            rf_sample_rate,
+            decimation: self.decimation,
+            intermediate_freq: self.intermediate_freq,
+            if_bandwidth: self.if_bandwidth,
+            signal_max_freq: self.signal_max_freq,
+            deviation: self.deviation,
+            pilot_level: self.pilot_level,
+            tx_emphasis_tau: self.tx_emphasis_tau,
+            rx_emphasis_tau: self.rx_emphasis_tau,
+            channel: self.channel,
+            tx_oscillator: self.tx_oscillator,
+            rx_oscillator: self.rx_oscillator,
+            front_end: self.front_end,
+            superhet: self.superhet,
+            adc: self.adc,
+            agc: self.agc,
+            limiter: self.limiter,
+            rds: self.rds,
+            rds_decoder: self.rds_decoder,
+            audio_processor: self.audio_processor,
+            mpx: self.mpx,
+        })
+    }
+}
+fn check_positive(name: &'static str, value: f64) -> Result<(), ConfigError> {
+    if value > 0. && value.is_finite() {
+        Ok(())
+    } else {
+        Err(ConfigError::NotPositive { name, value })
+    }
+}
+fn check_carrier_freq(freq: f64, rf_sample_rate: usize) -> Result<(), ConfigError> {
+    check_positive("carrier_freq", freq)?;
+    check_nyquist("carrier_freq", freq, rf_sample_rate)
+}
+/// 妨害局の搬送波 (希望局の搬送波 + offset) も RF で表せる範囲にある必要がある
+fn check_interferer_freqs(
+    carrier_freq: f64,
+    interferers: &[Interferer],
+    rf_sample_rate: usize,
+) -> Result<(), ConfigError> {
+    for interferer in interferers {
+        let freq = carrier_freq + interferer.offset;
+        check_positive("interferer_freq", freq)?;
+        check_nyquist("interferer_freq", freq, rf_sample_rate)?;
+    }
+    Ok(())
+}
+fn check_tuned_freq(
+    freq: f64,
+    intermediate_freq: f64,
+    rf_sample_rate: usize,
+) -> Result<(), ConfigError> {
+    check_positive("tuned_freq", freq)?;
+    check_nyquist("tuned_freq", freq, rf_sample_rate)?;
+    // 局部発振周波数 (fc - fi) が正である必要がある
+    if intermediate_freq >= freq {
+        return Err(ConfigError::IntermediateAboveCarrier {
+            intermediate_freq,
+            carrier_freq: freq,
+        });
+    }
+    Ok(())
+}
+/// プリセレクタの通過帯域 (同調周波数 ± 帯域幅 / 2) も RF で表せる範囲にある必要がある
+fn check_preselector(
+    tuned_freq: f64,
+    preselector: Option<Preselector>,
+    rf_sample_rate: usize,
+) -> Result<(), ConfigError> {
+    if let Some(preselector) = preselector {
+        check_positive(
+            "preselector_low_edge",
+            tuned_freq - preselector.bandwidth / 2.,
+        )?;
+        check_nyquist(
+            "preselector_high_edge",
+            tuned_freq + preselector.bandwidth / 2.,
+            rf_sample_rate,
+        )?;
+    }
+    Ok(())
+}
+/// RDS の副搬送波はコンポジットのサンプルレートで表せ、パイロットと合わせて振幅 1 未満である必要がある
+fn check_rds(
+    rds: &RdsConfig,
+    pilot_level: f64,
+    composite_sample_rate: usize,
+) -> Result<(), ConfigError> {
+    rds.validate()?;
+    check_nyquist("rds", rds::MAX_FREQ, composite_sample_rate)?;
+    if pilot_level + rds.injection >= 1. {
+        return Err(ConfigError::SubcarrierLevelOutOfRange {
+            pilot_level,
+            rds_injection: rds.injection,
+        });
+    }
+    Ok(())
+}
+fn check_nyquist(name: &'static str, freq: f64, sample_rate: usize) -> Result<(), ConfigError> {
+    if freq * 2. >= sample_rate as f64 {
+        Err(ConfigError::AboveNyquist {
+            name,
+            freq,
+            sample_rate,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    NotPositive {
+        name: &'static str,
+        value: f64,
+    },
+    NotFinite {
+        name: &'static str,
+        value: f64,
+    },
+    Negative {
+        name: &'static str,
+        value: f64,
+    },
+    NotAscending {
+        name: &'static str,
+        value: f64,
+    },
+    PilotLevelOutOfRange(f64),
+    UnsupportedDecimation(usize),
+    RfRateNotMultiple {
+        rf_sample_rate: usize,
+        composite_sample_rate: usize,
+        alignment: usize,
+    },
+    AboveNyquist {
+        name: &'static str,
+        freq: f64,
+        sample_rate: usize,
+    },
+    IntermediateAboveCarrier {
+        intermediate_freq: f64,
+        carrier_freq: f64,
+    },
+    IfBandwidthTooWide {
+        if_bandwidth: f64,
+        intermediate_freq: f64,
+    },
+    SecondIfAboveFirstIf {
+        second_if: f64,
+        intermediate_freq: f64,
+    },
+    AdcBitsOutOfRange(u32),
+    RdsTextInvalid {
+        name: &'static str,
+        text: String,
+        max_len: usize,
+    },
+    RdsFieldOutOfRange {
+        name: &'static str,
+        value: i64,
+    },
+    SubcarrierLevelOutOfRange {
+        pilot_level: f64,
+        rds_injection: f64,
+    },
+    CompressorRatioOutOfRange(f64),
+}
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotPositive { name, value } => {
+                write!(f, "{name} must be a positive finite value (got {value})")
+            }
+            Self::NotFinite { name, value } => {
+                write!(f, "{name} must be a finite value (got {value})")
+            }
+            Self::Negative { name, value } => {
+                write!(f, "{name} must not be negative (got {value})")
+            }
+            Self::NotAscending { name, value } => {
+                write!(f, "{name} must be in ascending order (got {value})")
+            }
+            Self::PilotLevelOutOfRange(level) => {
+                write!(f, "pilot level must be in [0, 1) (got {level})")
+            }
+            Self::UnsupportedDecimation(ratio) => write!(
+                f,
+                "decimation ratio {ratio} is not supported (only {})",
+                FmRadioSimConfig::DEFAULT_DECIMATION
+            ),
+            Self::RfRateNotMultiple {
+                rf_sample_rate,
+                composite_sample_rate,
+                alignment,
+            } => write!(
+                f,
+                "rf sample rate {rf_sample_rate}Hz is not a multiple of {alignment} x composite sample rate ({composite_sample_rate}Hz)"
+            ),
+            Self::AboveNyquist {
+                name,
+                freq,
+                sample_rate,
+            } => write!(
+                f,
+                "{name} ({freq}Hz) is above the nyquist frequency of {sample_rate}Hz"
+            ),
+            Self::IntermediateAboveCarrier {
+                intermediate_freq,
+                carrier_freq,
+            } => write!(
+                f,
+                "intermediate freq ({intermediate_freq}Hz) must be below the carrier freq ({carrier_freq}Hz)"
+            ),
+            Self::IfBandwidthTooWide {
+                if_bandwidth,
+                intermediate_freq,
+            } => write!(
+                f,
+                "if bandwidth ({if_bandwidth}Hz) is too wide for the intermediate freq ({intermediate_freq}Hz)"
+            ),
+            Self::SecondIfAboveFirstIf {
+                second_if,
+                intermediate_freq,
+            } => write!(
+                f,
+                "second if ({second_if}Hz) must be below the first intermediate freq ({intermediate_freq}Hz)"
+            ),
+            Self::AdcBitsOutOfRange(bits) => {
+                write!(f, "adc resolution must be in [1, 32] bits (got {bits})")
+            }
+            Self::RdsTextInvalid {
+                name,
+                text,
+                max_len,
+            } => write!(
+                f,
+                "{name} must be at most {max_len} printable ascii characters (got {text:?})"
+            ),
+            Self::RdsFieldOutOfRange { name, value } => {
+                write!(f, "{name} ({value}) does not fit in the rds group")
+            }
+            Self::SubcarrierLevelOutOfRange {
+                pilot_level,
+                rds_injection,
+            } => write!(
+                f,
+                "pilot level ({pilot_level}) + rds injection ({rds_injection}) must be below 1"
+            ),
+            Self::CompressorRatioOutOfRange(ratio) => {
+                write!(f, "compressor ratio must be at least 1 (got {ratio})")
+            }
+        }
+    }
+}
+impl std::error::Error for ConfigError {}
@@ -0,0 +1,58 @@
+/**
+ * FmRadioSim のエラー型
+*/
+use crate::config::ConfigError;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum FmSimError {
+    Config(ConfigError),
+    /// soxr の作成・処理の失敗
+    Resampler(libsoxr::Error),
+    /// 入出力のスライスの長さが揃っていない
+    LengthMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// ステージの処理中に panic し、Mutex が poison された
+    PoisonedStage(&'static str),
+    /// `init_thread` を呼ばずに `process` を呼んだ
+    ThreadsNotInitialized,
+    /// ワーカースレッドが panic して終了していた
+    WorkerPanicked,
+}
+impl fmt::Display for FmSimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(e) => write!(f, "invalid config: {e}"),
+            Self::Resampler(e) => write!(f, "resampler error: {e}"),
+            Self::LengthMismatch { expected, actual } => write!(
+                f,
+                "buffer length mismatch (expected {expected}, got {actual})"
+            ),
+            Self::PoisonedStage(stage) => write!(f, "stage `{stage}` is poisoned"),
+            Self::ThreadsNotInitialized => write!(f, "threads are not initialized"),
+            Self::WorkerPanicked => write!(f, "a worker thread panicked"),
+        }
+    }
+}
+impl std::error::Error for FmSimError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Config(e) => Some(e),
+            Self::Resampler(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+impl From<ConfigError> for FmSimError {
+    fn from(e: ConfigError) -> Self {
+        Self::Config(e)
+    }
+}
+impl From<libsoxr::Error> for FmSimError {
+    fn from(e: libsoxr::Error) -> Self {
+        Self::Resampler(e)
+    }
+}
+pub type Result<T> = std::result::Result<T, FmSimError>;
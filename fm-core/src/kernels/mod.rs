@@ -0,0 +1,216 @@
+/**
+ * 信号処理カーネルの実行時ディスパッチ
+ * 起動時に CPU の機能を検出し、ステージ毎に Scalar / AVX2+FMA の
+ * いずれかの実装を選択する。AVX2+FMA は C カーネル (freq_modulation.c) が
+ * リンクされていればそれを、`portable` feature 有効時は純Rust実装を使う。
+*/
+mod portable;
+use crate::modulation_modules::modulator::{
+    BandPassFilter, CnvFiInfos, DemodulationInfo, Modulator,
+};
+use crate::resampler::ResamplerInfo;
+use std::{fmt, sync::OnceLock};
+
+// repr(C) な状態構造体の末尾に持たせるため
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Backend {
+    #[default]
+    Scalar,
+    Avx2Fma,
+}
+impl Backend {
+    /// 環境変数でこれより上のバックエンドを使わないよう制限できる (CI 等での比較用)
+    pub const ENV_OVERRIDE: &'static str = "FM_SIM_BACKEND";
+    pub fn detect() -> Self {
+        static DETECTED: OnceLock<Backend> = OnceLock::new();
+        *DETECTED.get_or_init(|| {
+            let supported = Self::supported();
+            match std::env::var(Self::ENV_OVERRIDE)
+                .ok()
+                .and_then(|v| Self::from_name(&v))
+            {
+                Some(requested) => requested.min(supported),
+                None => supported,
+            }
+        })
+    }
+    /// この CPU で実行可能な最上位のバックエンド
+    pub fn supported() -> Self {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+                return Self::Avx2Fma;
+            }
+        }
+        Self::Scalar
+    }
+    pub fn is_supported(self) -> bool {
+        self <= Self::supported()
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "scalar" => Some(Self::Scalar),
+            "avx2" | "avx2+fma" | "avx2fma" => Some(Self::Avx2Fma),
+            _ => None,
+        }
+    }
+    /// AVX2+FMA 版が C カーネルかどうか
+    pub const fn is_c_kernel(self) -> bool {
+        matches!(self, Self::Avx2Fma) && cfg!(not(feature = "portable"))
+    }
+}
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Scalar => write!(f, "scalar"),
+            Self::Avx2Fma if self.is_c_kernel() => write!(f, "avx2+fma (c)"),
+            Self::Avx2Fma => write!(f, "avx2+fma"),
+        }
+    }
+}
+
+/// 各ステージが使用しているバックエンド
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KernelBackends {
+    pub modulator: Backend,
+    pub freq_converter: Backend,
+    pub bandpass_filter: Backend,
+    pub demodulator: Backend,
+    pub resampler: Backend,
+}
+impl fmt::Display for KernelBackends {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "modulate: {}, cvt-freq: {}, bandpass-filter: {}, demodulate: {}, resample: {}",
+            self.modulator,
+            self.freq_converter,
+            self.bandpass_filter,
+            self.demodulator,
+            self.resampler
+        )
+    }
+}
+
+#[cfg(not(feature = "portable"))]
+mod ffi {
+    use super::*;
+    #[link(name = "freq_modulation")]
+    extern "C" {
+        // extern "vectorcall" {
+        pub fn fm_modulate(
+            output_signal: *mut f64,
+            input_signal: *const f64,
+            buf_len: u64,
+            info: *mut Modulator,
+        );
+        pub fn convert_intermediate_freq(
+            output_signal: *mut f64,
+            input_signal: *const f64,
+            // sample_period: f64,
+            // fc: f64,
+            // fi: f64,
+            info: *mut CnvFiInfos,
+            buf_len: usize,
+        );
+        pub fn fm_demodulate(
+            output_signal: *mut f64,
+            input_signal: *const f64,
+            sample_period: f64,
+            carrier_freq: f64,
+            info: *mut DemodulationInfo,
+            buf_len: u64,
+        );
+        pub fn upsample(dst: *mut f64, input: *const f64, info: *mut ResamplerInfo);
+        pub fn downsample(dst: *mut f64, input: *const f64, info: *mut ResamplerInfo);
+        pub fn filtering(
+            output_signal: *mut f64,
+            input_signal: *const f64,
+            filter_coeff: *mut BandPassFilter,
+            buf_len: u64,
+        );
+        pub fn filtering_with_resample(
+            output_signal: *mut f64,
+            input_signal: *const f64,
+            filter_coeff: *mut BandPassFilter,
+            buf_len: u64,
+        );
+    }
+}
+
+macro_rules! dispatch {
+    ($(fn $name:ident($($arg:ident: $ty:ty),* $(,)?);)*) => {
+        $(
+            pub unsafe fn $name(backend: Backend, $($arg: $ty),*) {
+                match backend {
+                    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+                    Backend::Avx2Fma => avx2_fma::$name($($arg),*),
+                    _ => portable::$name($($arg),*),
+                }
+            }
+        )*
+        #[cfg(all(feature = "portable", any(target_arch = "x86", target_arch = "x86_64")))]
+        mod avx2_fma {
+            use super::*;
+            $(
+                #[target_feature(enable = "avx2,fma")]
+                pub unsafe fn $name($($arg: $ty),*) {
+                    portable::$name($($arg),*)
+                }
+            )*
+        }
+        #[cfg(all(not(feature = "portable"), any(target_arch = "x86", target_arch = "x86_64")))]
+        use ffi as avx2_fma;
+    };
+}
+
+dispatch! {
+    fn fm_modulate(
+        output_signal: *mut f64,
+        input_signal: *const f64,
+        buf_len: u64,
+        info: *mut Modulator,
+    );
+    fn convert_intermediate_freq(
+        output_signal: *mut f64,
+        input_signal: *const f64,
+        info: *mut CnvFiInfos,
+        buf_len: usize,
+    );
+    fn fm_demodulate(
+        output_signal: *mut f64,
+        input_signal: *const f64,
+        sample_period: f64,
+        carrier_freq: f64,
+        info: *mut DemodulationInfo,
+        buf_len: u64,
+    );
+    fn upsample(dst: *mut f64, input: *const f64, info: *mut ResamplerInfo);
+    fn downsample(dst: *mut f64, input: *const f64, info: *mut ResamplerInfo);
+    fn filtering(
+        output_signal: *mut f64,
+        input_signal: *const f64,
+        filter_coeff: *mut BandPassFilter,
+        buf_len: u64,
+    );
+    fn filtering_with_resample(
+        output_signal: *mut f64,
+        input_signal: *const f64,
+        filter_coeff: *mut BandPassFilter,
+        buf_len: u64,
+    );
+}
+
+/// MXCSR にフラグを立てる (C 側は /arch:AVX2 でビルドされるため常にこちらを使う)
+pub unsafe fn set_csr(flag: u32) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        let mut csr = 0u32;
+        std::arch::asm!("stmxcsr [{}]", in(reg) &raw mut csr, options(nostack));
+        csr |= flag;
+        std::arch::asm!("ldmxcsr [{}]", in(reg) &raw const csr, options(nostack, readonly));
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = flag;
+}
@@ -0,0 +1,248 @@
+/**
+ * freq_modulation.c の純Rust実装
+ * Scalar バックエンドとして直接呼ばれるほか、kernels/mod.rs で
+ * target_feature 付きの関数からインライン展開され AVX2+FMA 版になる。
+ * 各関数は C 側と同じシグネチャ・同じ状態レイアウトを持ち、
+ * SIMD のレーン構成もそのままスカラーで再現している。
+*/
+use crate::modulation_modules::modulator::{
+    BandPassFilter, CnvFiInfos, DemodulationInfo, Modulator,
+};
+use crate::resampler::ResamplerInfo;
+use std::f64::consts::TAU;
+
+#[inline(always)]
+pub unsafe fn fm_modulate(
+    output_signal: *mut f64,
+    input_signal: *const f64,
+    buf_len: u64,
+    info: *mut Modulator,
+) {
+    let info = &mut *info;
+    let output = std::slice::from_raw_parts_mut(output_signal, buf_len as usize);
+    let input = std::slice::from_raw_parts(input_signal, buf_len as usize);
+    let phi = TAU * info.carrier_freq * info.sample_period * 4.;
+    let coeff = info.modulation_index * info.sample_period;
+    let mut angle = info.t;
+    for (dst, src) in output.chunks_exact_mut(4).zip(input.chunks_exact(4)) {
+        for k in 0..4 {
+            dst[k] = (coeff * src[k] + angle[k]).cos();
+            angle[k] += phi;
+        }
+    }
+    info.t = angle.map(|a| a % TAU);
+}
+
+#[inline(always)]
+pub unsafe fn convert_intermediate_freq(
+    output_signal: *mut f64,
+    input_signal: *const f64,
+    info: *mut CnvFiInfos,
+    buf_len: usize,
+) {
+    let info = &mut *info;
+    let output = std::slice::from_raw_parts_mut(output_signal, buf_len);
+    let input = std::slice::from_raw_parts(input_signal, buf_len);
+    let full_delta_angle = info.delta_angle * 4.;
+    let mut angle = info.angle;
+    for (dst, src) in output.chunks_exact_mut(4).zip(input.chunks_exact(4)) {
+        for k in 0..4 {
+            dst[k] = src[k] * angle[k].cos();
+            angle[k] += full_delta_angle;
+        }
+    }
+    info.angle = angle.map(|a| a % TAU);
+}
+
+#[inline(always)]
+pub unsafe fn fm_demodulate(
+    output_signal: *mut f64,
+    input_signal: *const f64,
+    sample_period: f64,
+    carrier_freq: f64,
+    info: *mut DemodulationInfo,
+    buf_len: u64,
+) {
+    let info = &mut *info;
+    let output = std::slice::from_raw_parts_mut(output_signal, buf_len as usize);
+    let input = std::slice::from_raw_parts(input_signal, buf_len as usize);
+    let delta_angle = TAU * carrier_freq * sample_period * 4.;
+    let differential_coeff = 1. / (TAU * carrier_freq * sample_period);
+    let d_coeff = 1. / sample_period;
+    let (c0, c1, c2, d0, d1) = (
+        info.filter_coeff.c0,
+        info.filter_coeff.c1,
+        info.filter_coeff.c2,
+        info.filter_coeff.c3,
+        info.filter_coeff.c4,
+    );
+    let lanes = |v: &[f64]| -> [f64; 4] { [v[0], v[1], v[2], v[3]] };
+    let mut angle = info.angle;
+    let mut prev_sin = info.prev_sin;
+    // 1段目LPF出力 (1ブロック遅れで2段目に入る): [I0 Q0 I2 Q2] / [I1 Q1 I3 Q3]
+    let mut prev_sig_lo = lanes(&info.prev_sig[0..4]);
+    let mut prev_sig_hi = lanes(&info.prev_sig[4..8]);
+    // 2段目LPF出力 (微分用)
+    let mut prev_internal_lo = lanes(&info.prev_internal[0..4]);
+    let mut prev_internal_hi = lanes(&info.prev_internal[4..8]);
+    // LPF INFOS (lane: [I, Q, I', Q'])
+    let mut prev_sig = lanes(&info.filter_info[0..4]);
+    let mut prev_prev_sig = lanes(&info.filter_info[4..8]);
+    let mut prev_out = lanes(&info.filter_info[8..12]);
+    let mut prev_prev_out = lanes(&info.filter_info[12..16]);
+    for (dst, src) in output.chunks_exact_mut(4).zip(input.chunks_exact(4)) {
+        // Removing Carrier
+        let sin_val = angle.map(f64::sin);
+        let last_sin = [prev_sin[3], sin_val[0], sin_val[1], sin_val[2]];
+        let mut sig1 = [0.; 4];
+        let mut sig2 = [0.; 4];
+        for k in 0..4 {
+            let cos_val = (sin_val[k] - last_sin[k]) * differential_coeff;
+            sig1[k] = -(src[k] * sin_val[k]);
+            sig2[k] = src[k] * cos_val;
+        }
+        angle = angle.map(|a| (a + delta_angle) % TAU);
+        prev_sin = sin_val;
+        // Signal Interleaving
+        let s = [
+            [sig1[0], sig2[0], prev_sig_lo[0], prev_sig_lo[1]],
+            [sig1[1], sig2[1], prev_sig_hi[0], prev_sig_hi[1]],
+            [sig1[2], sig2[2], prev_sig_lo[2], prev_sig_lo[3]],
+            [sig1[3], sig2[3], prev_sig_hi[2], prev_sig_hi[3]],
+        ];
+        // LPF Process
+        let mut o = [[0.; 4]; 4];
+        for n in 0..4 {
+            for k in 0..4 {
+                o[n][k] = c0 * s[n][k] + c1 * prev_sig[k] + c2 * prev_prev_sig[k]
+                    - d0 * prev_out[k]
+                    - d1 * prev_prev_out[k];
+            }
+            prev_prev_sig = prev_sig;
+            prev_sig = s[n];
+            prev_prev_out = prev_out;
+            prev_out = o[n];
+        }
+        prev_sig_lo = [o[0][0], o[0][1], o[2][0], o[2][1]];
+        prev_sig_hi = [o[1][0], o[1][1], o[3][0], o[3][1]];
+        // DeInterleaving
+        let s_lo = [o[0][2], o[0][3], o[2][2], o[2][3]];
+        let s_hi = [o[1][2], o[1][3], o[3][2], o[3][3]];
+        // differential & たすき掛け
+        let iq = [
+            (s_lo[0], s_lo[1]),
+            (s_hi[0], s_hi[1]),
+            (s_lo[2], s_lo[3]),
+            (s_hi[2], s_hi[3]),
+        ];
+        let mut last = (prev_internal_hi[2], prev_internal_hi[3]);
+        for (d, (re, im)) in dst.iter_mut().zip(iq) {
+            let d_re = (re - last.0) * d_coeff;
+            let d_im = (im - last.1) * d_coeff;
+            *d = d_im * re - d_re * im;
+            last = (re, im);
+        }
+        prev_internal_lo = s_lo;
+        prev_internal_hi = s_hi;
+    }
+    info.angle = angle.map(|a| a % TAU);
+    info.prev_sin = prev_sin;
+    info.prev_sig[0..4].copy_from_slice(&prev_sig_lo);
+    info.prev_sig[4..8].copy_from_slice(&prev_sig_hi);
+    info.prev_internal[0..4].copy_from_slice(&prev_internal_lo);
+    info.prev_internal[4..8].copy_from_slice(&prev_internal_hi);
+    info.filter_info[0..4].copy_from_slice(&prev_sig);
+    info.filter_info[4..8].copy_from_slice(&prev_prev_sig);
+    info.filter_info[8..12].copy_from_slice(&prev_out);
+    info.filter_info[12..16].copy_from_slice(&prev_prev_out);
+}
+
+#[inline(always)]
+pub unsafe fn upsample(dst: *mut f64, input: *const f64, info: *mut ResamplerInfo) {
+    let info = &mut *info;
+    let multiplier = info.multiplier;
+    let input = std::slice::from_raw_parts(input, info.input_len);
+    let dst = std::slice::from_raw_parts_mut(dst, info.input_len * multiplier);
+    let coeff = multiplier as f64;
+    let m = 1. / coeff;
+    let mut prev = info.prev;
+    for (current_val, d) in input.iter().zip(dst.chunks_exact_mut(multiplier)) {
+        let s = prev + current_val * coeff; // 変調時のパラメータの整合性を確保
+        for (n, v) in d.iter_mut().enumerate() {
+            let coeff1 = n as f64 * m;
+            *v = prev * (1. - coeff1) + s * coeff1;
+        }
+        prev = s;
+    }
+    info.prev = prev;
+}
+
+#[inline(always)]
+pub unsafe fn downsample(dst: *mut f64, input: *const f64, info: *mut ResamplerInfo) {
+    let info = &*info;
+    let input = std::slice::from_raw_parts(input, info.input_len);
+    for (j, v) in input.iter().step_by(info.multiplier).enumerate() {
+        *dst.add(j) = *v;
+    }
+}
+
+/// 2レーンのBPF処理 (lane0: 前ブロックの1段目出力を入力とする2段目, lane1: 1段目)
+#[inline(always)]
+fn bpf_block(info: &mut BandPassFilter, input: &[f64]) -> [[f64; 2]; 4] {
+    let c0 = info.filter_coeff.c0;
+    let d0 = info.filter_coeff.c3;
+    let d1 = info.filter_coeff.c4;
+    let s0 = [info.stage[0], input[0]];
+    let s1 = [info.stage[1], input[1]];
+    let s2 = [info.stage[2], input[2]];
+    let s3 = [info.stage[3], input[3]];
+    let mut y = [[0.; 2]; 4];
+    for k in 0..2 {
+        y[0][k] = c0 * (s0[k] - info.prev_prev_sig[k])
+            - info.prev_out[k] * d0
+            - info.prev_prev_out[k] * d1;
+        y[1][k] = c0 * (s1[k] - info.prev_sig[k]) - y[0][k] * d0 - info.prev_out[k] * d1;
+        y[2][k] = c0 * (s2[k] - s0[k]) - y[1][k] * d0 - y[0][k] * d1;
+        y[3][k] = c0 * (s3[k] - s1[k]) - y[2][k] * d0 - y[1][k] * d1;
+    }
+    // set next stage
+    info.stage = [y[0][1], y[1][1], y[2][1], y[3][1]];
+    info.prev_out = y[3];
+    info.prev_prev_out = y[2];
+    info.prev_sig = s3;
+    info.prev_prev_sig = s2;
+    y
+}
+
+#[inline(always)]
+pub unsafe fn filtering(
+    output_signal: *mut f64,
+    input_signal: *const f64,
+    filter_coeff: *mut BandPassFilter,
+    buf_len: u64,
+) {
+    let info = &mut *filter_coeff;
+    let output = std::slice::from_raw_parts_mut(output_signal, buf_len as usize);
+    let input = std::slice::from_raw_parts(input_signal, buf_len as usize);
+    for (dst, src) in output.chunks_exact_mut(4).zip(input.chunks_exact(4)) {
+        let y = bpf_block(info, src);
+        for k in 0..4 {
+            dst[k] = y[k][0];
+        }
+    }
+}
+
+#[inline(always)]
+pub unsafe fn filtering_with_resample(
+    output_signal: *mut f64,
+    input_signal: *const f64,
+    filter_coeff: *mut BandPassFilter,
+    buf_len: u64,
+) {
+    let info = &mut *filter_coeff;
+    let input = std::slice::from_raw_parts(input_signal, buf_len as usize);
+    for (i, src) in input.chunks_exact(4).enumerate() {
+        let y = bpf_block(info, src);
+        *output_signal.add(i) = 2. * y[3][0];
+    }
+}
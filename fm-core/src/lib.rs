@@ -1,4 +1,7 @@
 // #![feature(abi_vectorcall)]
+mod config;
+mod error;
+mod kernels;
 mod modulation_modules;
 use libsoxr::{
     datatype::Datatype,
@@ -8,61 +11,41 @@ use libsoxr::{
 use modulation_modules::*;
 mod resampler;
 use resampler::*;
+#[cfg(test)]
+mod tests;
 mod utils;
 
+pub use config::{ConfigError, FmRadioSimConfig, FmRadioSimConfigBuilder};
+pub use error::{FmSimError, Result};
+use kernels::{downsample, set_csr, upsample};
+pub use kernels::{Backend, KernelBackends};
+pub use modulation_modules::adc::AdcConfig;
+pub use modulation_modules::audio_processor::{AudioProcessorConfig, CompressorBands};
+pub use modulation_modules::channel::{
+    ChannelConfig, Fading, ImpulseNoise, Interferer, InterfererSource, MultipathTap, NoiseLevel,
+    PulseAmplitude, PulseShape, PulseTiming,
+};
+pub use modulation_modules::front_end::{FrontEndConfig, Nonlinearity};
+pub use modulation_modules::if_stage::{Agc, Limiter};
+pub use modulation_modules::mpx::{MpxConfig, MpxMeter};
+pub use modulation_modules::oscillator::{Drift, OscillatorConfig};
+pub use modulation_modules::rds::{ClockTime, RdsConfig, RdsStatus};
+pub use modulation_modules::superhet::{Preselector, SecondIf, SuperhetConfig};
 use std::{
+    collections::VecDeque,
     ops::{Deref, DerefMut},
-    sync::{Arc, Barrier, Mutex},
-    thread,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Barrier, Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
     time::Instant,
 };
 pub use utils::Shareable;
 use utils::{generate_pipline_buffer, ExecFlag, PipeLineBuffer};
 const ENABLE_MODULE_TIME: bool = false;
 const ENABLE_END_BARRIER: bool = false;
-#[link(name = "freq_modulation")]
-extern "C" {
-    // extern "vectorcall" {
-    fn fm_modulate(
-        output_signal: *mut f64,
-        input_signal: *const f64,
-        buf_len: u64,
-        info: *mut modulator::Modulator,
-    );
-    fn convert_intermediate_freq(
-        output_signal: *mut f64,
-        input_signal: *const f64,
-        // sample_period: f64,
-        // fc: f64,
-        // fi: f64,
-        info: *mut modulator::CnvFiInfos,
-        buf_len: usize,
-    );
-    fn fm_demodulate(
-        output_signal: *mut f64,
-        input_signal: *const f64,
-        sample_period: f64,
-        carrier_freq: f64,
-        info: *mut modulator::DemodulationInfo,
-        buf_len: u64,
-    );
-    fn upsample(dst: *mut f64, input: *const f64, info: *mut ResamplerInfo);
-    fn downsample(dst: *mut f64, input: *const f64, info: *mut ResamplerInfo);
-    fn filtering(
-        output_signal: *mut f64,
-        input_signal: *const f64,
-        filter_coeff: *mut modulator::BandPassFilter,
-        buf_len: u64,
-    );
-    fn filtering_with_resample(
-        output_signal: *mut f64,
-        input_signal: *const f64,
-        filter_coeff: *mut modulator::BandPassFilter,
-        buf_len: u64,
-    );
-    fn set_csr(flag: u32);
-}
-
 pub struct FmRadioSim {
     // basic parameters
     audio_sample_rate: usize,
@@ -73,6 +56,15 @@ pub struct FmRadioSim {
     modulator: Shareable<modulator::Modulator>,
     demodulator: Shareable<modulator::DeModulator>,
     freq_converter: Shareable<modulator::CvtIntermediateFreq>,
+    channel: Shareable<channel::Channel>,
+    interferers: Shareable<channel::interferer::Interferers>,
+    tx_oscillator: Shareable<oscillator::Oscillator>,
+    rx_oscillator: Shareable<oscillator::Oscillator>,
+    front_end: Shareable<front_end::FrontEnd>,
+    preselector: Shareable<superhet::PreselectorFilter>,
+    second_conversion: Shareable<superhet::SecondConversion>,
+    if_stage: Shareable<if_stage::IfStage>,
+    adc: Shareable<adc::Adc>,
 
     bandpass_filter1: Shareable<modulator::BandPassFilter>,
     bandpass_filter2: Shareable<modulator::BandPassFilter>,
@@ -82,8 +74,11 @@ pub struct FmRadioSim {
     downsampler: [Soxr; 2],
     upsampler_for_radio_waves: ResamplerInfo,
     downsampler_for_radio_waves: ResamplerInfo,
+    resampler_backend: Backend,
     // internal buffer
     // interleave/de-interleave
+    input_fifo: [VecDeque<f32>; 2],
+    output_fifo: [VecDeque<f32>; 2],
     tmp_buffer: [Vec<f64>; 2],            // audio sample rate
     audio_in_buffer: [Vec<f64>; 2],       // 125kHz
     composite_signal: Vec<f64>,           // 125kHz
@@ -101,92 +96,172 @@ pub struct FmRadioSim {
     read_state: bool,
     barrier: Arc<Barrier>,
     is_init: bool,
+    workers: Vec<JoinHandle<()>>,
+    // バリアを通過した回数と、ワーカーを停止させる回 (u64::MAX: 停止しない)
+    block_count: u64,
+    stop_at_block: Arc<AtomicU64>,
+    // panic したステージの名前 (ワーカーはバリアを揃えるため処理を止めずに記録だけする)。
+    // process で報告したら消す
+    stage_error: Arc<Mutex<Option<&'static str>>>,
+    // soxr・フィルタによる遅延 [audio samples] (作成時に測定)
+    dsp_latency: usize,
+    config: FmRadioSimConfig,
 }
 unsafe impl Send for FmRadioSim {}
 
 impl FmRadioSim {
-    // define constants
-    // pub const COMPOSITE_SAMPLE_RATE: usize = 125_000;
-    pub const COMPOSITE_SAMPLE_RATE: usize = 192_000;
-    // pub const FM_MODULATION_SAMPLE_RATE: usize = 192_000_000;
-    pub const FM_MODULATION_SAMPLE_RATE: usize = 185_000_000;
-    pub const INTERMEDIATE_FREQ: f64 = 10_700_000f64; // JISC6421:1994
-                                                      // pub const INTERMEDIATE_FREQ: f64 = 79_500_000f64 - 440f64;
-    pub const SIGNAL_MAX_FREQ: f64 = 53_000. * 2.; // x2 Composite freq max
-    pub const RATIO_FS_INTER_FS: usize = 4;
-    // fn set_fs(&mut self) {
-
-    // }
-    //
-    pub fn from(audio_fs: usize, buffer_size: usize, carrier_freq: f64) -> Self {
+    const PIPELINE_DEPTH: usize = 5;
+    /// 既定のパラメータで作成する
+    pub fn from(audio_fs: usize, buffer_size: usize, carrier_freq: f64) -> Result<Self> {
+        let config = FmRadioSimConfig::builder(audio_fs, buffer_size, carrier_freq).build()?;
+        Self::with_config(config)
+    }
+    pub fn with_config(config: FmRadioSimConfig) -> Result<Self> {
+        let audio_fs = config.audio_sample_rate();
+        let buffer_size = config.buffer_size();
+        let composite_fs = config.composite_sample_rate();
         // calc basic params
-        let fm_sample_rate =
-            get_8x_sample_rate(Self::FM_MODULATION_SAMPLE_RATE, Self::COMPOSITE_SAMPLE_RATE);
-        let intermediate_fs = fm_sample_rate / Self::RATIO_FS_INTER_FS;
+        let fm_sample_rate = config.rf_sample_rate();
+        let intermediate_fs = config.intermediate_sample_rate();
         // generate soxr
         let upsampler = [
-            generate_resampler(audio_fs as f64, Self::COMPOSITE_SAMPLE_RATE as f64).unwrap(),
-            generate_resampler(audio_fs as f64, Self::COMPOSITE_SAMPLE_RATE as f64).unwrap(),
+            generate_resampler(audio_fs as f64, composite_fs as f64)?,
+            generate_resampler(audio_fs as f64, composite_fs as f64)?,
         ];
         let downsampler = [
-            generate_resampler(Self::COMPOSITE_SAMPLE_RATE as f64, audio_fs as f64).unwrap(),
-            generate_resampler(Self::COMPOSITE_SAMPLE_RATE as f64, audio_fs as f64).unwrap(),
+            generate_resampler(composite_fs as f64, audio_fs as f64)?,
+            generate_resampler(composite_fs as f64, audio_fs as f64)?,
         ];
         // calculate buffer size
-        let composite_buffer_size =
-            get_buffer_size(audio_fs, Self::COMPOSITE_SAMPLE_RATE, buffer_size);
-        let modulated_buffer_size = get_buffer_size(
-            Self::COMPOSITE_SAMPLE_RATE,
-            fm_sample_rate,
-            composite_buffer_size,
-        );
-        let intermediate_buffer_size = modulated_buffer_size / Self::RATIO_FS_INTER_FS;
+        let composite_buffer_size = get_buffer_size(audio_fs, composite_fs, buffer_size);
+        let modulated_buffer_size =
+            get_buffer_size(composite_fs, fm_sample_rate, composite_buffer_size);
+        let intermediate_buffer_size = modulated_buffer_size / config.decimation();
+        let dsp_latency = measure_dsp_latency(&config, composite_buffer_size)?;
         // MHz order resampler init
-        let upsampler_for_radio_waves = ResamplerInfo::new_upsample_info(
-            Self::COMPOSITE_SAMPLE_RATE,
-            fm_sample_rate,
-            composite_buffer_size,
-        );
+        let upsampler_for_radio_waves =
+            ResamplerInfo::new_upsample_info(composite_fs, fm_sample_rate, composite_buffer_size);
         let downsampler_for_radio_waves = ResamplerInfo::new_downsample_info(
             intermediate_fs,
-            Self::COMPOSITE_SAMPLE_RATE,
+            composite_fs,
             intermediate_buffer_size,
         );
-        Self {
+        let demodulator = modulator::DeModulator::from(
+            config
+                .superhet()
+                .demodulation_freq(config.intermediate_freq()),
+            intermediate_fs as f64,
+            // 880.
+            config.signal_max_freq(),
+        );
+        Ok(Self {
             audio_sample_rate: audio_fs,
             buffer_size,
             //
-            composite: composite::CompositeSignal::new(Self::COMPOSITE_SAMPLE_RATE as f64),
-            restore: composite::RestoreSignal::new(Self::COMPOSITE_SAMPLE_RATE as f64),
+            composite: {
+                let mut composite = composite::CompositeSignal::new(
+                    composite_fs as f64,
+                    config.pilot_level(),
+                    config.tx_emphasis_tau(),
+                );
+                composite.set_rds(config.rds());
+                composite.set_audio_processor(config.audio_processor());
+                composite.set_mpx(config.mpx(), config.deviation());
+                composite
+            },
+            restore: {
+                let mut restore = composite::RestoreSignal::new(
+                    composite_fs as f64,
+                    config.subcarrier_level(),
+                    config.rx_emphasis_tau(),
+                    demodulator.output_gain(config.deviation()),
+                );
+                restore.set_rds_decoder(config.rds_decoder());
+                restore
+            },
             modulator: sharable!(modulator::Modulator::from(
-                carrier_freq,
+                config.carrier_freq(),
+                fm_sample_rate as f64,
+                config.deviation(),
+            )),
+            freq_converter: sharable!({
+                let mut converter = modulator::CvtIntermediateFreq::new(
+                    fm_sample_rate as f64,
+                    config.tuned_freq(),
+                    config.intermediate_freq(),
+                );
+                converter.set_image_rejection(config.superhet().image_rejection);
+                converter
+            }),
+            channel: sharable!(channel::Channel::new(
                 fm_sample_rate as f64,
+                config.carrier_freq(),
+                config.if_bandwidth(),
+                config.channel(),
+            )),
+            interferers: sharable!(channel::interferer::Interferers::new(
+                &config,
+                composite_buffer_size,
             )),
-            freq_converter: sharable!(modulator::CvtIntermediateFreq::new(
+            tx_oscillator: sharable!(oscillator::Oscillator::new(
                 fm_sample_rate as f64,
-                carrier_freq,
-                Self::INTERMEDIATE_FREQ,
+                config.carrier_freq(),
+                config.tx_oscillator(),
+                channel::Rng::new(config.channel().seed ^ oscillator::TX_STREAM),
             )),
-            bandpass_filter1: sharable!(modulator::BandPassFilter::new(
+            rx_oscillator: sharable!(oscillator::Oscillator::new(
                 fm_sample_rate as f64,
-                Self::INTERMEDIATE_FREQ,
+                config.tuned_freq() - config.intermediate_freq(),
+                config.rx_oscillator(),
+                channel::Rng::new(config.channel().seed ^ oscillator::RX_STREAM),
             )),
-            bandpass_filter2: sharable!(modulator::BandPassFilter::new(
+            front_end: sharable!(front_end::FrontEnd::new(config.front_end())),
+            preselector: sharable!(superhet::PreselectorFilter::new(
                 fm_sample_rate as f64,
-                Self::INTERMEDIATE_FREQ,
+                config.tuned_freq(),
+                config.superhet().preselector,
             )),
-            demodulator: sharable!(modulator::DeModulator::from(
-                Self::INTERMEDIATE_FREQ,
+            adc: sharable!(adc::Adc::new(
                 intermediate_fs as f64,
-                // 880.
-                Self::SIGNAL_MAX_FREQ,
+                config.adc(),
+                channel::Rng::new(config.channel().seed ^ adc::ADC_STREAM),
+            )),
+            second_conversion: sharable!(superhet::SecondConversion::new(
+                intermediate_fs as f64,
+                config.intermediate_freq(),
+                config.superhet().second_if,
+            )),
+            if_stage: sharable!(if_stage::IfStage::new(
+                intermediate_fs as f64,
+                config
+                    .superhet()
+                    .demodulation_freq(config.intermediate_freq()),
+                config.agc(),
+                config.limiter(),
+            )),
+            bandpass_filter1: sharable!(modulator::BandPassFilter::with_bandwidth(
+                fm_sample_rate as f64,
+                config.intermediate_freq(),
+                config.if_bandwidth(),
+            )),
+            bandpass_filter2: sharable!(modulator::BandPassFilter::with_bandwidth(
+                fm_sample_rate as f64,
+                config.intermediate_freq(),
+                config.if_bandwidth(),
             )),
+            demodulator: sharable!(demodulator),
             // resampler
             upsampler,
             downsampler,
             upsampler_for_radio_waves,
             downsampler_for_radio_waves,
+            resampler_backend: Backend::detect(),
             // buffer
+            input_fifo: [
+                VecDeque::with_capacity(buffer_size * 2),
+                VecDeque::with_capacity(buffer_size * 2),
+            ],
+            output_fifo: primed_output_fifo(buffer_size),
             tmp_buffer: [vec![0.; buffer_size], vec![0.; buffer_size]],
             audio_in_buffer: [
                 vec![0.; composite_buffer_size],
@@ -211,7 +286,207 @@ impl FmRadioSim {
             barrier: Arc::new(Barrier::new(5)),
             // barrier: Arc::new(Barrier::new(3)),
             is_init: false,
-        }
+            workers: Vec::new(),
+            block_count: 0,
+            stop_at_block: Arc::new(AtomicU64::new(u64::MAX)),
+            stage_error: Arc::new(Mutex::new(None)),
+            dsp_latency,
+            config,
+        })
+    }
+    pub fn config(&self) -> &FmRadioSimConfig {
+        &self.config
+    }
+    /// 送信側の搬送波周波数を変更する。ワーカーの動作中でも呼べる
+    /// 位相は連続で、変調ステージが次に処理するブロックから反映される
+    pub fn set_carrier_freq(&mut self, freq: f64) -> Result<()> {
+        self.config.set_carrier_freq(freq)?;
+        self.modulator
+            .lock()
+            .map_err(poisoned("modulate"))?
+            .set_carrier_freq(freq);
+        self.tx_oscillator
+            .lock()
+            .map_err(poisoned("modulate"))?
+            .set_nominal_freq(freq);
+        self.channel
+            .lock()
+            .map_err(poisoned("channel"))?
+            .set_carrier_freq(freq);
+        self.interferers
+            .lock()
+            .map_err(poisoned("interferers"))?
+            .set_carrier_freq(freq);
+        Ok(())
+    }
+    /// 受信側の同調周波数 (局部発振) を変更する。中間周波数以降のステージは変わらない
+    pub fn set_tuned_freq(&mut self, freq: f64) -> Result<()> {
+        self.config.set_tuned_freq(freq)?;
+        self.freq_converter
+            .lock()
+            .map_err(poisoned("cvt-freq"))?
+            .set_tuned_freq(freq);
+        self.preselector
+            .lock()
+            .map_err(poisoned("preselector"))?
+            .set_tuned_freq(freq);
+        self.rx_oscillator
+            .lock()
+            .map_err(poisoned("cvt-freq"))?
+            .set_nominal_freq(freq - self.config.intermediate_freq());
+        Ok(())
+    }
+    /// 送信機の搬送波の発振器 (周波数偏差・ドリフト・位相雑音) を変更する
+    pub fn set_tx_oscillator(&mut self, oscillator: &OscillatorConfig) -> Result<()> {
+        self.config.set_tx_oscillator(oscillator)?;
+        self.tx_oscillator
+            .lock()
+            .map_err(poisoned("modulate"))?
+            .set_config(oscillator);
+        Ok(())
+    }
+    /// 受信機の局部発振器を変更する。周波数偏差は局部発振の周波数に対する値
+    pub fn set_rx_oscillator(&mut self, oscillator: &OscillatorConfig) -> Result<()> {
+        self.config.set_rx_oscillator(oscillator)?;
+        self.rx_oscillator
+            .lock()
+            .map_err(poisoned("cvt-freq"))?
+            .set_config(oscillator);
+        Ok(())
+    }
+    /// 受信機のフロントエンドの非線形性を変更する (None: 線形)
+    pub fn set_front_end(&mut self, front_end: Option<FrontEndConfig>) -> Result<()> {
+        self.config.set_front_end(front_end)?;
+        self.front_end
+            .lock()
+            .map_err(poisoned("front-end"))?
+            .set_config(front_end);
+        Ok(())
+    }
+    /// 中間周波数の A/D 変換器を変更する (None: 量子化なし)
+    pub fn set_adc(&mut self, adc: Option<AdcConfig>) -> Result<()> {
+        self.config.set_adc(adc)?;
+        self.adc.lock().map_err(poisoned("adc"))?.set_config(adc);
+        Ok(())
+    }
+    /// 復調前の AGC を変更する (None: なし)
+    pub fn set_agc(&mut self, agc: Option<Agc>) -> Result<()> {
+        self.config.set_agc(agc)?;
+        self.if_stage
+            .lock()
+            .map_err(poisoned("if-stage"))?
+            .set_agc(agc);
+        Ok(())
+    }
+    /// 復調前のリミッタを変更する (None: なし)
+    pub fn set_limiter(&mut self, limiter: Option<Limiter>) -> Result<()> {
+        self.config.set_limiter(limiter)?;
+        self.if_stage
+            .lock()
+            .map_err(poisoned("if-stage"))?
+            .set_limiter(limiter);
+        Ok(())
+    }
+    /// コンポジット信号のクリッパ・MPX 電力の制限を変更する。測定値は引き継ぐ
+    pub fn set_mpx(&mut self, mpx: &MpxConfig) -> Result<()> {
+        self.config.set_mpx(mpx)?;
+        self.composite.set_mpx(mpx, self.config.deviation());
+        Ok(())
+    }
+    /// 直近 `power_window` 秒の最大周波数偏移・MPX 電力 (ITU-R BS.412)
+    pub fn mpx_meter(&self) -> MpxMeter {
+        self.composite.mpx_meter()
+    }
+    /// コンポジット信号に加える RDS を変更する (None: なし)
+    /// 局の情報 (PS・RadioText 等) だけを変えた場合は、データの送信は途切れずに続く
+    pub fn set_rds(&mut self, rds: Option<&RdsConfig>) -> Result<()> {
+        self.config.set_rds(rds)?;
+        self.composite.set_rds(rds);
+        self.restore
+            .set_subcarrier_level(self.config.subcarrier_level());
+        Ok(())
+    }
+    /// 受信側の RDS のデコーダを有効・無効にする。有効にした時点から同期・統計をやり直す
+    pub fn set_rds_decoder(&mut self, enabled: bool) -> Result<()> {
+        self.config.set_rds_decoder(enabled)?;
+        self.restore.set_rds_decoder(enabled);
+        Ok(())
+    }
+    /// 受信した RDS の内容とブロックの誤りの統計 (デコーダが無効の場合は None)
+    /// パイプラインの遅延の分だけ、送信側より遅れて反映される
+    pub fn rds_status(&self) -> Option<&RdsStatus> {
+        self.restore.rds_status()
+    }
+    /// 受信機に加える熱雑音のレベルを変更する (None: 雑音なし)
+    pub fn set_noise(&mut self, noise: Option<NoiseLevel>) -> Result<()> {
+        self.config.set_noise(noise)?;
+        self.channel
+            .lock()
+            .map_err(poisoned("channel"))?
+            .set_noise(noise);
+        Ok(())
+    }
+    /// 遅延波のタップを変更する (空: 直接波のみ)。`MultipathTap::urban` 等のプリセットも使える
+    pub fn set_multipath(&mut self, taps: &[MultipathTap]) -> Result<()> {
+        self.config.set_multipath(taps)?;
+        self.channel
+            .lock()
+            .map_err(poisoned("channel"))?
+            .set_multipath(taps);
+        Ok(())
+    }
+    /// 移動受信のフェージングを変更する (None: フェージングなし)
+    /// 速度を変えても利得は連続するので、加速・減速も再現できる
+    pub fn set_fading(&mut self, fading: Option<Fading>) -> Result<()> {
+        self.config.set_fading(fading)?;
+        self.channel
+            .lock()
+            .map_err(poisoned("channel"))?
+            .set_fading(fading);
+        Ok(())
+    }
+    /// パルス性雑音を変更する (None: なし)
+    pub fn set_impulse_noise(&mut self, noise: Option<ImpulseNoise>) -> Result<()> {
+        self.config.set_impulse_noise(noise)?;
+        self.channel
+            .lock()
+            .map_err(poisoned("channel"))?
+            .set_impulse_noise(noise);
+        Ok(())
+    }
+    /// 妨害局を変更する (空: 妨害なし)。各局の送信機は初期状態から始まる
+    pub fn set_interferers(&mut self, interferers: &[Interferer]) -> Result<()> {
+        self.config.set_interferers(interferers)?;
+        self.interferers
+            .lock()
+            .map_err(poisoned("interferers"))?
+            .set_interferers(interferers);
+        Ok(())
+    }
+    /// 伝搬路 (と発振器の位相雑音) の乱数の種を変更し、系列を最初からやり直す
+    pub fn set_channel_seed(&mut self, seed: u64) -> Result<()> {
+        self.config.set_channel_seed(seed);
+        self.channel
+            .lock()
+            .map_err(poisoned("channel"))?
+            .set_seed(seed);
+        self.interferers
+            .lock()
+            .map_err(poisoned("interferers"))?
+            .set_seed(seed);
+        self.tx_oscillator
+            .lock()
+            .map_err(poisoned("modulate"))?
+            .reseed(channel::Rng::new(seed ^ oscillator::TX_STREAM));
+        self.rx_oscillator
+            .lock()
+            .map_err(poisoned("cvt-freq"))?
+            .reseed(channel::Rng::new(seed ^ oscillator::RX_STREAM));
+        self.adc
+            .lock()
+            .map_err(poisoned("adc"))?
+            .reseed(channel::Rng::new(seed ^ adc::ADC_STREAM));
+        Ok(())
     }
     pub fn get_intermediate(&self) -> &[f64] {
         if self.read_state {
@@ -239,6 +514,15 @@ impl FmRadioSim {
             std::slice::from_raw_parts(tmp.as_ptr(), tmp.len())
         }
     }
+    pub fn kernel_backends(&self) -> KernelBackends {
+        KernelBackends {
+            modulator: self.modulator.lock().unwrap().backend(),
+            freq_converter: self.freq_converter.lock().unwrap().backend(),
+            bandpass_filter: self.bandpass_filter2.lock().unwrap().backend(),
+            demodulator: self.demodulator.lock().unwrap().backend(),
+            resampler: self.resampler_backend,
+        }
+    }
     pub fn get_composite(&self) -> &[f64] {
         &self.composite_signal
     }
@@ -254,6 +538,12 @@ impl FmRadioSim {
             set_csr(crate::utils::float::FLUSH_TO_ZERO);
         }
         self.is_init = true;
+        // ワーカーは常に [0] から書き込みを始める
+        self.read_state = false;
+        self.block_count = 0;
+        self.stop_at_block.store(u64::MAX, Ordering::Release);
+        // 前回の実行で記録されたまま報告していない失敗は持ち越さない
+        self.stage_error = Arc::new(Mutex::new(None));
         println!("initialize Threads.");
         // Modules
         let listener0 = Arc::clone(&self.barrier);
@@ -264,9 +554,14 @@ impl FmRadioSim {
         // Modulation Process
         {
             let modulator = Arc::clone(&self.modulator);
+            let tx_oscillator = Arc::clone(&self.tx_oscillator);
+            let channel = Arc::clone(&self.channel);
+            let interferers = Arc::clone(&self.interferers);
+            let stop_at_block = Arc::clone(&self.stop_at_block);
+            let stage_error = Arc::clone(&self.stage_error);
             let up_sample_signal = Arc::clone(&self.up_sampled_signal);
             let modulate_signal = Arc::clone(&self.modulate_signal);
-            let _ = thread::spawn(move || {
+            self.workers.push(thread::spawn(move || {
                 unsafe {
                     set_csr(crate::utils::float::FLUSH_TO_ZERO);
                 }
@@ -274,17 +569,37 @@ impl FmRadioSim {
                 let up_sample_signal = Arc::clone(&up_sample_signal);
                 let modulate_signal = Arc::clone(&modulate_signal);
 
+                let mut block = 0u64;
                 loop {
                     listener0.wait();
+                    block += 1;
+                    if block == stop_at_block.load(Ordering::Acquire) {
+                        break;
+                    }
                     let start = Instant::now();
-                    unsafe {
-                        modulator.lock().unwrap_unchecked().process(
-                            &up_sample_signal[(!state) as usize]
-                                .lock()
-                                .unwrap_unchecked(),
-                            &mut modulate_signal[state as usize].lock().unwrap_unchecked(),
+                    run_stage("modulate", &stage_error, || {
+                        modulate(
+                            &mut *modulator.lock().ok()?,
+                            &mut *tx_oscillator.lock().ok()?,
+                            &up_sample_signal[(!state) as usize].lock().ok()?,
+                            &mut modulate_signal[state as usize].lock().ok()?,
                         );
-                    }
+                        Some(())
+                    });
+                    run_stage("channel", &stage_error, || {
+                        channel
+                            .lock()
+                            .ok()?
+                            .process(&mut modulate_signal[state as usize].lock().ok()?);
+                        Some(())
+                    });
+                    run_stage("interferers", &stage_error, || {
+                        interferers
+                            .lock()
+                            .ok()?
+                            .process(&mut modulate_signal[state as usize].lock().ok()?);
+                        Some(())
+                    });
                     // println!("hoge");
                     let end = start.elapsed();
                     if ENABLE_END_BARRIER {
@@ -296,14 +611,19 @@ impl FmRadioSim {
                         println!("Modulate: {:?}", end);
                     }
                 }
-            });
+            }));
         }
         // Convert-fi Process
         {
             let intermediate_signal = Arc::clone(&self.intermediate_signal1);
             let modulate_signal = Arc::clone(&self.modulate_signal);
             let freq_converter = Arc::clone(&self.freq_converter);
-            let _ = thread::spawn(move || {
+            let rx_oscillator = Arc::clone(&self.rx_oscillator);
+            let preselector = Arc::clone(&self.preselector);
+            let front_end = Arc::clone(&self.front_end);
+            let stop_at_block = Arc::clone(&self.stop_at_block);
+            let stage_error = Arc::clone(&self.stage_error);
+            self.workers.push(thread::spawn(move || {
                 unsafe {
                     set_csr(crate::utils::float::FLUSH_TO_ZERO);
                 }
@@ -311,17 +631,37 @@ impl FmRadioSim {
                 let intermediate_signal = Arc::clone(&intermediate_signal);
                 let modulate_signal = Arc::clone(&modulate_signal);
 
+                let mut block = 0u64;
                 loop {
                     listener1.wait();
+                    block += 1;
+                    if block == stop_at_block.load(Ordering::Acquire) {
+                        break;
+                    }
                     let start = Instant::now();
-                    unsafe {
-                        freq_converter.lock().unwrap_unchecked().process(
-                            &modulate_signal[(!state) as usize].lock().unwrap_unchecked(),
-                            &mut intermediate_signal[state as usize]
-                                .lock()
-                                .unwrap_unchecked(),
+                    run_stage("preselector", &stage_error, || {
+                        preselector
+                            .lock()
+                            .ok()?
+                            .process(&mut modulate_signal[(!state) as usize].lock().ok()?);
+                        Some(())
+                    });
+                    run_stage("front-end", &stage_error, || {
+                        front_end
+                            .lock()
+                            .ok()?
+                            .process(&mut modulate_signal[(!state) as usize].lock().ok()?);
+                        Some(())
+                    });
+                    run_stage("cvt-freq", &stage_error, || {
+                        convert_freq(
+                            &mut *freq_converter.lock().ok()?,
+                            &mut *rx_oscillator.lock().ok()?,
+                            &modulate_signal[(!state) as usize].lock().ok()?,
+                            &mut intermediate_signal[state as usize].lock().ok()?,
                         );
-                    }
+                        Some(())
+                    });
                     let end = start.elapsed();
                     if ENABLE_END_BARRIER {
                         listener1.wait();
@@ -332,7 +672,7 @@ impl FmRadioSim {
                     }
                     // println!("fuga");
                 }
-            });
+            }));
         }
         // BPF
         // {
@@ -376,10 +716,13 @@ impl FmRadioSim {
         // }
         {
             let bandpass_filter = Arc::clone(&self.bandpass_filter2);
+            let adc = Arc::clone(&self.adc);
+            let stop_at_block = Arc::clone(&self.stop_at_block);
+            let stage_error = Arc::clone(&self.stage_error);
             // let intermediate_signal_in = Arc::clone(&self.intermediate_signal2);
             let intermediate_signal_in = Arc::clone(&self.intermediate_signal1);
             let intermediate_signal_out = Arc::clone(&self.intermediate_signal3);
-            let _ = thread::spawn(move || {
+            self.workers.push(thread::spawn(move || {
                 unsafe {
                     set_csr(crate::utils::float::FLUSH_TO_ZERO);
                 }
@@ -387,19 +730,27 @@ impl FmRadioSim {
                 let intermediate_signal = Arc::clone(&intermediate_signal_in);
                 let intermediate_signal_out = Arc::clone(&intermediate_signal_out);
 
+                let mut block = 0u64;
                 loop {
                     listener3.wait();
+                    block += 1;
+                    if block == stop_at_block.load(Ordering::Acquire) {
+                        break;
+                    }
                     let start = Instant::now();
-                    unsafe {
-                        bandpass_filter.lock().unwrap_unchecked().process(
-                            &intermediate_signal[(!state) as usize]
-                                .lock()
-                                .unwrap_unchecked(),
-                            &mut intermediate_signal_out[state as usize]
-                                .lock()
-                                .unwrap_unchecked(),
+                    run_stage("bandpass-filter", &stage_error, || {
+                        bandpass_filter.lock().ok()?.process(
+                            &intermediate_signal[(!state) as usize].lock().ok()?,
+                            &mut intermediate_signal_out[state as usize].lock().ok()?,
                         );
-                    }
+                        Some(())
+                    });
+                    run_stage("adc", &stage_error, || {
+                        adc.lock()
+                            .ok()?
+                            .process(&mut intermediate_signal_out[state as usize].lock().ok()?);
+                        Some(())
+                    });
                     let end = start.elapsed();
                     if ENABLE_END_BARRIER {
                         listener3.wait();
@@ -409,13 +760,17 @@ impl FmRadioSim {
                         println!("BPF2 : {:?}", end);
                     }
                 }
-            });
+            }));
         }
         {
             let demodulation = Arc::clone(&self.demodulator);
+            let second_conversion = Arc::clone(&self.second_conversion);
+            let if_stage = Arc::clone(&self.if_stage);
+            let stop_at_block = Arc::clone(&self.stop_at_block);
+            let stage_error = Arc::clone(&self.stage_error);
             let intermediate_signal = Arc::clone(&self.intermediate_signal3);
             let demodulate_signal = Arc::clone(&self.demodulate_signal);
-            let _ = thread::spawn(move || {
+            self.workers.push(thread::spawn(move || {
                 unsafe {
                     set_csr(crate::utils::float::FLUSH_TO_ZERO);
                 }
@@ -423,17 +778,35 @@ impl FmRadioSim {
                 let intermediate_signal = Arc::clone(&intermediate_signal);
                 let demodulate_signal = Arc::clone(&demodulate_signal);
 
+                let mut block = 0u64;
                 loop {
                     listener4.wait();
+                    block += 1;
+                    if block == stop_at_block.load(Ordering::Acquire) {
+                        break;
+                    }
                     let start = Instant::now();
-                    unsafe {
-                        demodulation.lock().unwrap_unchecked().process(
-                            &intermediate_signal[(!state) as usize]
-                                .lock()
-                                .unwrap_unchecked(),
-                            &mut demodulate_signal[state as usize].lock().unwrap_unchecked(),
+                    run_stage("second-conversion", &stage_error, || {
+                        second_conversion
+                            .lock()
+                            .ok()?
+                            .process(&mut intermediate_signal[(!state) as usize].lock().ok()?);
+                        Some(())
+                    });
+                    run_stage("if-stage", &stage_error, || {
+                        if_stage
+                            .lock()
+                            .ok()?
+                            .process(&mut intermediate_signal[(!state) as usize].lock().ok()?);
+                        Some(())
+                    });
+                    run_stage("demodulate", &stage_error, || {
+                        demodulation.lock().ok()?.process(
+                            &intermediate_signal[(!state) as usize].lock().ok()?,
+                            &mut demodulate_signal[state as usize].lock().ok()?,
                         );
-                    }
+                        Some(())
+                    });
                     let end = start.elapsed();
                     if ENABLE_END_BARRIER {
                         listener4.wait();
@@ -443,30 +816,174 @@ impl FmRadioSim {
                         println!("De-Modulate: {:?}", end);
                     }
                 }
-            });
+            }));
+        }
+    }
+    /// ワーカースレッドを停止して join する。再度 `init_thread` を呼べば再開できる。
+    /// panic して終了したワーカーがあれば、全て join した後にエラーを返す
+    pub fn shutdown(&mut self) -> Result<()> {
+        if !self.is_init {
+            return Ok(());
+        }
+        // 次にバリアを通過した回で終了させる。
+        // フラグだと前の回の判定前に立ってしまったワーカーが先に抜け、バリアが揃わなくなる
+        self.stop_at_block
+            .store(self.block_count + 1, Ordering::Release);
+        self.barrier.wait();
+        // 途中で止めずに全て join する
+        let results: Vec<_> = self.workers.drain(..).map(JoinHandle::join).collect();
+        self.is_init = false;
+        match results.iter().all(thread::Result::is_ok) {
+            true => Ok(()),
+            false => Err(FmSimError::WorkerPanicked),
+        }
+    }
+    /// スレッドを作り直す (停止中であれば起動のみ)
+    pub fn reinit_thread(&mut self) -> Result<()> {
+        self.shutdown()?;
+        self.init_thread();
+        Ok(())
+    }
+    pub fn is_running(&self) -> bool {
+        self.is_init
+    }
+    /// 全ステージの状態・バッファを作成直後の状態に戻す。
+    /// ワーカーの動作中であれば一度停止させてから戻し、再度起動する
+    /// (処理途中のブロックが戻した後の状態に書き込まないようにするため)
+    pub fn reset(&mut self) -> Result<()> {
+        let was_running = self.is_init;
+        // ワーカーが panic していても join は済んでいるので、戻してから報告する
+        let stopped = self.shutdown();
+        let result = self.reset_state();
+        if was_running {
+            self.init_thread();
+        }
+        stopped.and(result)
+    }
+    fn reset_state(&mut self) -> Result<()> {
+        self.composite.reset();
+        self.restore.reset();
+        self.modulator.lock().map_err(poisoned("modulate"))?.reset();
+        self.channel.lock().map_err(poisoned("channel"))?.reset();
+        self.interferers
+            .lock()
+            .map_err(poisoned("interferers"))?
+            .reset();
+        let seed = self.config.channel().seed;
+        self.tx_oscillator
+            .lock()
+            .map_err(poisoned("modulate"))?
+            .reset(channel::Rng::new(seed ^ oscillator::TX_STREAM));
+        self.rx_oscillator
+            .lock()
+            .map_err(poisoned("cvt-freq"))?
+            .reset(channel::Rng::new(seed ^ oscillator::RX_STREAM));
+        self.adc
+            .lock()
+            .map_err(poisoned("adc"))?
+            .reseed(channel::Rng::new(seed ^ adc::ADC_STREAM));
+        self.freq_converter
+            .lock()
+            .map_err(poisoned("cvt-freq"))?
+            .reset();
+        self.preselector
+            .lock()
+            .map_err(poisoned("preselector"))?
+            .reset();
+        self.second_conversion
+            .lock()
+            .map_err(poisoned("second-conversion"))?
+            .reset();
+        self.if_stage.lock().map_err(poisoned("if-stage"))?.reset();
+        self.bandpass_filter1
+            .lock()
+            .map_err(poisoned("bandpass-filter1"))?
+            .reset();
+        self.bandpass_filter2
+            .lock()
+            .map_err(poisoned("bandpass-filter"))?
+            .reset();
+        self.demodulator
+            .lock()
+            .map_err(poisoned("demodulate"))?
+            .reset();
+        for soxr in self.upsampler.iter().chain(self.downsampler.iter()) {
+            soxr.clear()?;
+        }
+        self.upsampler_for_radio_waves.reset();
+        self.downsampler_for_radio_waves.reset();
+        for buffer in [
+            &self.up_sampled_signal,
+            &self.modulate_signal,
+            &self.intermediate_signal1,
+            &self.intermediate_signal2,
+            &self.intermediate_signal3,
+            &self.demodulate_signal,
+        ] {
+            for buffer in buffer.iter() {
+                buffer.lock().map_err(poisoned("pipeline"))?.fill(0.);
+            }
         }
+        for buffer in self
+            .tmp_buffer
+            .iter_mut()
+            .chain(self.audio_in_buffer.iter_mut())
+            .chain([
+                &mut self.composite_signal,
+                &mut self.post_down_sample,
+                &mut self.restored_signal_l,
+                &mut self.restored_signal_r,
+            ])
+        {
+            buffer.fill(0.);
+        }
+        self.input_fifo.iter_mut().for_each(VecDeque::clear);
+        self.output_fifo = primed_output_fifo(self.buffer_size);
+        self.read_state = false;
+        Ok(())
     }
+    /// 任意の長さのブロックを処理する。内部では `buffer_size` 毎にまとめて処理するため、
+    /// `buffering_latency` サンプル分の遅延が加わる
     pub fn process(
         &mut self,
         input_l: &[f32],
         input_r: &[f32],
         dst_l: &mut [f32],
         dst_r: &mut [f32],
-    ) {
+    ) -> Result<()> {
+        if !self.is_init {
+            return Err(FmSimError::ThreadsNotInitialized);
+        }
+        self.rebuffer(input_l, input_r, dst_l, dst_r, Self::process_block)
+    }
+    fn process_block(&mut self) -> Result<()> {
         self.barrier.wait();
-        // cvar_3.notify_one();
-        // de-interleave
-        for (i, lr) in input_l.iter().zip(input_r).enumerate() {
-            unsafe {
-                *self.tmp_buffer[0].get_unchecked_mut(i) = *lr.0 as f64;
-                *self.tmp_buffer[1].get_unchecked_mut(i) = *lr.1 as f64;
-            }
+        self.block_count += 1;
+        // 途中で失敗してもワーカーとの状態 (read_state, バリア) は揃えておく
+        let result = self.process_main_thread();
+        self.read_state ^= true;
+        if ENABLE_END_BARRIER {
+            self.barrier.wait();
         }
+        result?;
+        // poison されたままのステージは次のブロックでも失敗して記録し直される
+        let stage_error = self
+            .stage_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        match stage_error {
+            Some(stage) => Err(FmSimError::PoisonedStage(stage)),
+            None => Ok(()),
+        }
+    }
+    fn process_main_thread(&mut self) -> Result<()> {
+        // cvar_3.notify_one();
         // up sample
-        let _ = self.upsampler[0]
-            .process::<f64, f64>(Some(&self.tmp_buffer[0]), &mut self.audio_in_buffer[0]);
-        let _ = self.upsampler[1]
-            .process::<f64, f64>(Some(&self.tmp_buffer[1]), &mut self.audio_in_buffer[1]);
+        self.upsampler[0]
+            .process::<f64, f64>(Some(&self.tmp_buffer[0]), &mut self.audio_in_buffer[0])?;
+        self.upsampler[1]
+            .process::<f64, f64>(Some(&self.tmp_buffer[1]), &mut self.audio_in_buffer[1])?;
         // composite
         self.composite.process(
             &self.audio_in_buffer[0],
@@ -477,9 +994,10 @@ impl FmRadioSim {
         //
         unsafe {
             upsample(
+                self.resampler_backend,
                 self.up_sampled_signal[(self.read_state) as usize]
                     .lock()
-                    .unwrap()
+                    .map_err(poisoned("modulate"))?
                     .deref_mut()
                     .as_mut_slice()
                     .as_mut_ptr(),
@@ -500,10 +1018,11 @@ impl FmRadioSim {
         //
         unsafe {
             downsample(
+                self.resampler_backend,
                 self.post_down_sample.as_mut_ptr(),
                 self.demodulate_signal[(!self.read_state) as usize]
                     .lock()
-                    .unwrap()
+                    .map_err(poisoned("demodulate"))?
                     .as_ptr(),
                 &raw mut self.downsampler_for_radio_waves,
             );
@@ -515,22 +1034,76 @@ impl FmRadioSim {
             &mut self.restored_signal_r,
         );
         // down sample
-        let _ = self.downsampler[0]
-            .process::<f64, f64>(Some(&self.restored_signal_l), &mut self.tmp_buffer[0]);
-        let _ = self.downsampler[1]
-            .process::<f64, f64>(Some(&self.restored_signal_r), &mut self.tmp_buffer[1]);
-        // interleave
-        for (i, lr) in dst_l.iter_mut().zip(dst_r.iter_mut()).enumerate() {
-            unsafe {
-                *lr.0 = *self.tmp_buffer[0].get_unchecked(i) as f32;
-                *lr.1 = *self.tmp_buffer[1].get_unchecked(i) as f32;
+        self.downsampler[0]
+            .process::<f64, f64>(Some(&self.restored_signal_l), &mut self.tmp_buffer[0])?;
+        self.downsampler[1]
+            .process::<f64, f64>(Some(&self.restored_signal_r), &mut self.tmp_buffer[1])?;
+        Ok(())
+    }
+    /// 入力を FIFO に溜め、`buffer_size` 毎に `f` で処理して出力の FIFO から取り出す
+    fn rebuffer(
+        &mut self,
+        input_l: &[f32],
+        input_r: &[f32],
+        dst_l: &mut [f32],
+        dst_r: &mut [f32],
+        mut f: impl FnMut(&mut Self) -> Result<()>,
+    ) -> Result<()> {
+        let len = input_l.len();
+        for actual in [input_r.len(), dst_l.len(), dst_r.len()] {
+            if actual != len {
+                return Err(FmSimError::LengthMismatch {
+                    expected: len,
+                    actual,
+                });
             }
         }
-        // println!("check point3");
-        self.read_state ^= true;
-        if ENABLE_END_BARRIER {
-            self.barrier.wait();
+        self.input_fifo[0].extend(input_l);
+        self.input_fifo[1].extend(input_r);
+        while self.input_fifo[0].len() >= self.buffer_size {
+            // de-interleave
+            for (fifo, tmp) in self.input_fifo.iter_mut().zip(self.tmp_buffer.iter_mut()) {
+                for (t, v) in tmp.iter_mut().zip(fifo.drain(..self.buffer_size)) {
+                    *t = v as f64;
+                }
+            }
+            if let Err(e) = f(self) {
+                // 取り出したブロックの出力が無いまま次の呼び出しで FIFO が足りなくならないよう、
+                // 残りの入力を捨てて初期状態 (遅延分の無音) に戻す
+                self.input_fifo.iter_mut().for_each(VecDeque::clear);
+                self.output_fifo = primed_output_fifo(self.buffer_size);
+                return Err(e);
+            }
+            // interleave
+            for (fifo, tmp) in self.output_fifo.iter_mut().zip(self.tmp_buffer.iter()) {
+                fifo.extend(tmp.iter().map(|v| *v as f32));
+            }
+        }
+        for (fifo, dst) in self.output_fifo.iter_mut().zip([dst_l, dst_r]) {
+            for (d, v) in dst.iter_mut().zip(fifo.drain(..len)) {
+                *d = v;
+            }
         }
+        Ok(())
+    }
+    /// 再バッファリングによる遅延 (出力の FIFO の初期値)
+    pub fn buffering_latency(&self) -> usize {
+        self.buffer_size - 1
+    }
+    /// スレッドのパイプラインによる遅延。メインスレッドで作ったブロックが
+    /// 4 つのワーカーを 1 ブロックずつ遅れて通り、その次の回でメインスレッドに戻る
+    /// (遅れるのはコンポジット信号のブロックなので、その長さから換算する)
+    pub fn pipeline_latency(&self) -> usize {
+        let composite_buffer_size = self.audio_in_buffer[0].len();
+        ((Self::PIPELINE_DEPTH * composite_buffer_size) as f64
+            * self.config.audio_sample_rate() as f64
+            / self.config.composite_sample_rate() as f64
+            + 0.5) as usize
+    }
+    /// `process` の入力から出力までの遅延 [audio samples]
+    /// (`process_serial` では `pipeline_latency` を除いた値)
+    pub fn latency_samples(&self) -> usize {
+        self.buffering_latency() + self.pipeline_latency() + self.dsp_latency
     }
     pub fn process_serial(
         &mut self,
@@ -538,25 +1111,21 @@ impl FmRadioSim {
         input_r: &[f32],
         dst_l: &mut [f32],
         dst_r: &mut [f32],
-    ) {
+    ) -> Result<()> {
+        self.rebuffer(input_l, input_r, dst_l, dst_r, Self::process_block_serial)
+    }
+    fn process_block_serial(&mut self) -> Result<()> {
         // self.barrier.wait();
         // cvar_3.notify_one();
-        // de-interleave
-        for (i, lr) in input_l.iter().zip(input_r).enumerate() {
-            unsafe {
-                *self.tmp_buffer[0].get_unchecked_mut(i) = *lr.0 as f64;
-                *self.tmp_buffer[1].get_unchecked_mut(i) = *lr.1 as f64;
-            }
-        }
         let timer_start = Instant::now();
         // up sample
-        let _ = self.upsampler[0].process::<f64, f64>(
+        self.upsampler[0].process::<f64, f64>(
             Some(&self.tmp_buffer[0]),
             &mut self.audio_in_buffer[0],
             // &mut self.composite_signal
-        );
-        let _ = self.upsampler[1]
-            .process::<f64, f64>(Some(&self.tmp_buffer[1]), &mut self.audio_in_buffer[1]);
+        )?;
+        self.upsampler[1]
+            .process::<f64, f64>(Some(&self.tmp_buffer[1]), &mut self.audio_in_buffer[1])?;
         let lap0 = timer_start.elapsed();
         // composite
         self.composite.process(
@@ -567,9 +1136,10 @@ impl FmRadioSim {
         let lap1 = timer_start.elapsed();
         unsafe {
             upsample(
+                self.resampler_backend,
                 self.up_sampled_signal[0]
                     .lock()
-                    .unwrap()
+                    .map_err(poisoned("modulate"))?
                     .deref_mut()
                     .as_mut_slice()
                     .as_mut_ptr(),
@@ -581,39 +1151,123 @@ impl FmRadioSim {
         // println!("check point1");
         //
         let lap2 = timer_start.elapsed();
-        self.modulator.lock().unwrap().process(
-            &self.up_sampled_signal[0].lock().unwrap(),
-            &mut self.modulate_signal[0].lock().unwrap(),
+        modulate(
+            &mut *self.modulator.lock().map_err(poisoned("modulate"))?,
+            &mut *self.tx_oscillator.lock().map_err(poisoned("modulate"))?,
+            &self.up_sampled_signal[0]
+                .lock()
+                .map_err(poisoned("modulate"))?,
+            &mut self.modulate_signal[0]
+                .lock()
+                .map_err(poisoned("modulate"))?,
         );
+        self.channel.lock().map_err(poisoned("channel"))?.process(
+            &mut self.modulate_signal[0]
+                .lock()
+                .map_err(poisoned("channel"))?,
+        );
+        self.interferers
+            .lock()
+            .map_err(poisoned("interferers"))?
+            .process(
+                &mut self.modulate_signal[0]
+                    .lock()
+                    .map_err(poisoned("interferers"))?,
+            );
         let lap3 = timer_start.elapsed();
+        self.preselector
+            .lock()
+            .map_err(poisoned("preselector"))?
+            .process(
+                &mut self.modulate_signal[0]
+                    .lock()
+                    .map_err(poisoned("preselector"))?,
+            );
+        self.front_end
+            .lock()
+            .map_err(poisoned("front-end"))?
+            .process(
+                &mut self.modulate_signal[0]
+                    .lock()
+                    .map_err(poisoned("front-end"))?,
+            );
         // super heterodyne
-        self.freq_converter.lock().unwrap().process(
-            &self.modulate_signal[0].lock().unwrap(),
-            &mut self.intermediate_signal1[0].lock().unwrap(),
+        convert_freq(
+            &mut *self.freq_converter.lock().map_err(poisoned("cvt-freq"))?,
+            &mut *self.rx_oscillator.lock().map_err(poisoned("cvt-freq"))?,
+            &self.modulate_signal[0]
+                .lock()
+                .map_err(poisoned("cvt-freq"))?,
+            &mut self.intermediate_signal1[0]
+                .lock()
+                .map_err(poisoned("cvt-freq"))?,
         );
         let lap4 = timer_start.elapsed();
-        self.bandpass_filter1.lock().unwrap().process_no_resample(
-            &self.intermediate_signal1[0].lock().unwrap(),
-            &mut self.intermediate_signal2[0].lock().unwrap(),
-        );
+        self.bandpass_filter1
+            .lock()
+            .map_err(poisoned("bandpass-filter1"))?
+            .process_no_resample(
+                &self.intermediate_signal1[0]
+                    .lock()
+                    .map_err(poisoned("bandpass-filter1"))?,
+                &mut self.intermediate_signal2[0]
+                    .lock()
+                    .map_err(poisoned("bandpass-filter1"))?,
+            );
         let lap5 = timer_start.elapsed();
-        self.bandpass_filter2.lock().unwrap().process(
-            &self.intermediate_signal2[0].lock().unwrap(),
-            &mut self.intermediate_signal3[0].lock().unwrap(),
+        self.bandpass_filter2
+            .lock()
+            .map_err(poisoned("bandpass-filter"))?
+            .process(
+                &self.intermediate_signal2[0]
+                    .lock()
+                    .map_err(poisoned("bandpass-filter"))?,
+                &mut self.intermediate_signal3[0]
+                    .lock()
+                    .map_err(poisoned("bandpass-filter"))?,
+            );
+        self.adc.lock().map_err(poisoned("adc"))?.process(
+            &mut self.intermediate_signal3[0]
+                .lock()
+                .map_err(poisoned("adc"))?,
+        );
+        self.second_conversion
+            .lock()
+            .map_err(poisoned("second-conversion"))?
+            .process(
+                &mut self.intermediate_signal3[0]
+                    .lock()
+                    .map_err(poisoned("second-conversion"))?,
+            );
+        self.if_stage.lock().map_err(poisoned("if-stage"))?.process(
+            &mut self.intermediate_signal3[0]
+                .lock()
+                .map_err(poisoned("if-stage"))?,
         );
         // de-modulate
         let lap6 = timer_start.elapsed();
-        self.demodulator.lock().unwrap().process(
-            &self.intermediate_signal3[0].lock().unwrap(),
-            &mut self.demodulate_signal[0].lock().unwrap(),
-        );
+        self.demodulator
+            .lock()
+            .map_err(poisoned("demodulate"))?
+            .process(
+                &self.intermediate_signal3[0]
+                    .lock()
+                    .map_err(poisoned("demodulate"))?,
+                &mut self.demodulate_signal[0]
+                    .lock()
+                    .map_err(poisoned("demodulate"))?,
+            );
         // println!("check point2");
         //
         let lap7 = timer_start.elapsed();
         unsafe {
             downsample(
+                self.resampler_backend,
                 self.post_down_sample.as_mut_ptr(),
-                self.demodulate_signal[0].lock().unwrap().as_ptr(),
+                self.demodulate_signal[0]
+                    .lock()
+                    .map_err(poisoned("demodulate"))?
+                    .as_ptr(),
                 &raw mut self.downsampler_for_radio_waves,
             );
         }
@@ -625,18 +1279,11 @@ impl FmRadioSim {
         );
         let lap9 = timer_start.elapsed();
         // down sample
-        let _ = self.downsampler[0]
-            .process::<f64, f64>(Some(&self.restored_signal_l), &mut self.tmp_buffer[0]);
-        let _ = self.downsampler[1]
-            .process::<f64, f64>(Some(&self.restored_signal_r), &mut self.tmp_buffer[1]);
+        self.downsampler[0]
+            .process::<f64, f64>(Some(&self.restored_signal_l), &mut self.tmp_buffer[0])?;
+        self.downsampler[1]
+            .process::<f64, f64>(Some(&self.restored_signal_r), &mut self.tmp_buffer[1])?;
         let lap10 = timer_start.elapsed();
-        // interleave
-        for (i, lr) in dst_l.iter_mut().zip(dst_r.iter_mut()).enumerate() {
-            unsafe {
-                *lr.0 = *self.tmp_buffer[0].get_unchecked(i) as f32;
-                *lr.1 = *self.tmp_buffer[1].get_unchecked(i) as f32;
-            }
-        }
         println!(
             "===============================
 up-sampling    : {:?}
@@ -662,7 +1309,107 @@ down-sampling  : {:?}",
             lap9 - lap8,
             lap10 - lap9,
         );
+        Ok(())
+    }
+}
+impl Drop for FmRadioSim {
+    fn drop(&mut self) {
+        // ワーカーの panic は呼び出し元に返せないので捨てる
+        let _ = self.shutdown();
+    }
+}
+/// 最初の 1 ブロックが溜まるまでの間に出力する無音 (buffer_size - 1 サンプル)
+fn primed_output_fifo(buffer_size: usize) -> [VecDeque<f32>; 2] {
+    [
+        VecDeque::from(vec![0.; buffer_size - 1]),
+        VecDeque::from(vec![0.; buffer_size - 1]),
+    ]
+}
+#[inline]
+fn poisoned<T>(stage: &'static str) -> impl FnOnce(T) -> FmSimError {
+    move |_| FmSimError::PoisonedStage(stage)
+}
+/// ワーカーの1ブロック分の処理。panic・poison はステージ名を記録して次のブロックへ進む。
+/// release プロファイルは panic = "abort" なので、そこでは panic を捕まえられずプロセスが終了する
+fn run_stage(
+    name: &'static str,
+    error: &Mutex<Option<&'static str>>,
+    f: impl FnOnce() -> Option<()>,
+) {
+    if !matches!(panic::catch_unwind(AssertUnwindSafe(f)), Ok(Some(()))) {
+        error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_insert(name);
+    }
+}
+/// 変調。送信機の発振器の周波数のずれ・位相雑音もここで加える
+fn modulate(
+    modulator: &mut modulator::Modulator,
+    oscillator: &mut oscillator::Oscillator,
+    input: &[f64],
+    dst: &mut [f64],
+) {
+    if let Some(freq) = oscillator.next_block_freq(dst.len()) {
+        modulator.set_carrier_freq(freq);
+    }
+    modulator.process(input, dst);
+    oscillator.process_phase_noise(dst, modulator.carrier_freq);
+}
+/// 中間周波数への変換。局部発振器の位相雑音は中間周波数の信号の位相に現れる
+fn convert_freq(
+    converter: &mut modulator::CvtIntermediateFreq,
+    oscillator: &mut oscillator::Oscillator,
+    input: &[f64],
+    dst: &mut [f64],
+) {
+    if let Some(freq) = oscillator.next_block_freq(dst.len()) {
+        converter.set_local_freq(freq);
+    }
+    converter.process(input, dst);
+    oscillator.process_phase_noise(dst, converter.intermediate_freq());
+}
+/// soxr とコンポジット信号のフィルタによる遅延 [audio samples]
+/// RF の部分を除いた audio -> composite -> audio にインパルスを通し、ピークの位置を遅延とする
+/// (RF のステージの遅延はオーディオのサンプルに対して 1 サンプル未満なので無視する)
+fn measure_dsp_latency(
+    config: &FmRadioSimConfig,
+    composite_buffer_size: usize,
+) -> libsoxr::Result<usize> {
+    const PROBE_LEN: usize = 4096;
+    let audio_fs = config.audio_sample_rate() as f64;
+    let composite_fs = config.composite_sample_rate() as f64;
+    let buffer_size = config.buffer_size();
+    let upsampler = generate_resampler(audio_fs, composite_fs)?;
+    let downsampler = generate_resampler(composite_fs, audio_fs)?;
+    // パイロットはピークの検出の邪魔になるので入れない。
+    // エンファシスは送受信で打ち消し合うものとして入れない (片側だけでもピークの位置がずれる)
+    let mut composite = composite::CompositeSignal::new(composite_fs, 0., 0.);
+    // 音声プロセッサの先読みの遅延も含める
+    composite.set_audio_processor(config.audio_processor());
+    let mut restore = composite::RestoreSignal::new(composite_fs, 0., 0., 1.);
+    let mut input = vec![0.; buffer_size];
+    input[0] = 1.;
+    let mut audio_in = vec![0.; composite_buffer_size];
+    let mut composite_signal = vec![0.; composite_buffer_size];
+    let mut restored_l = vec![0.; composite_buffer_size];
+    let mut restored_r = vec![0.; composite_buffer_size];
+    let mut output = vec![0.; buffer_size];
+    let (mut peak, mut latency) = (0f64, 0);
+    for block in 0..PROBE_LEN.div_ceil(buffer_size) {
+        upsampler.process::<f64, f64>(Some(&input), &mut audio_in)?;
+        composite.process(&audio_in, &audio_in, &mut composite_signal);
+        restore.process(&composite_signal, &mut restored_l, &mut restored_r);
+        downsampler.process::<f64, f64>(Some(&restored_l), &mut output)?;
+        for (i, v) in output.iter().enumerate() {
+            if v.abs() > peak {
+                peak = v.abs();
+                latency = block * buffer_size + i;
+            }
+        }
+        input[0] = 0.;
     }
+    Ok(latency)
 }
 fn generate_resampler(f1: f64, f2: f64) -> libsoxr::Result<Soxr> {
     Soxr::create(
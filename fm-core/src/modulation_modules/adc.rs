@@ -0,0 +1,112 @@
+/**
+ * SDR 型の受信機の A/D 変換器
+ * 中間周波数のサンプルレートに間引いた信号を N ビットに量子化する。
+ * フルスケールを超えた入力はクリップし、サンプリング時刻の揺らぎ (アパーチャジッタ) は
+ * 信号の傾きに比例する誤差として加える。
+*/
+use super::channel::Rng;
+use crate::config::ConfigError;
+
+/// A/D 変換器の雑音の乱数系列 (伝搬路の雑音とは別の系列にする)
+pub(crate) const ADC_STREAM: u64 = 0x6164_635f_7178;
+
+/// A/D 変換器の設定
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdcConfig {
+    /// 分解能 [bit] (1..=32)
+    pub bits: u32,
+    /// フルスケールの正弦波の電力 [dB] (希望局の搬送波電力を 0dB とする)
+    pub full_scale: f64,
+    /// ±1LSB の三角分布のディザを加える
+    pub dither: bool,
+    /// アパーチャジッタ (rms) [ps]
+    pub jitter: f64,
+}
+impl AdcConfig {
+    pub fn new(bits: u32, full_scale: f64) -> Self {
+        Self {
+            bits,
+            full_scale,
+            dither: false,
+            jitter: 0.,
+        }
+    }
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        if !(1..=32).contains(&self.bits) {
+            return Err(ConfigError::AdcBitsOutOfRange(self.bits));
+        }
+        if !self.full_scale.is_finite() {
+            return Err(ConfigError::NotFinite {
+                name: "adc_full_scale",
+                value: self.full_scale,
+            });
+        }
+        if !(self.jitter >= 0. && self.jitter.is_finite()) {
+            return Err(ConfigError::NotPositive {
+                name: "adc_jitter",
+                value: self.jitter,
+            });
+        }
+        Ok(())
+    }
+}
+
+pub struct Adc {
+    sample_rate: f64,
+    config: Option<AdcConfig>,
+    // 1LSB の大きさと、出力できる符号の範囲
+    step: f64,
+    codes: (f64, f64),
+    rng: Rng,
+    // ジッタの誤差の計算に使う、直前の入力
+    last: f64,
+}
+impl Adc {
+    pub fn new(sample_rate: f64, config: Option<AdcConfig>, rng: Rng) -> Self {
+        let mut adc = Self {
+            sample_rate,
+            config: None,
+            step: 0.,
+            codes: (0., 0.),
+            rng,
+            last: 0.,
+        };
+        adc.set_config(config);
+        adc
+    }
+    pub fn set_config(&mut self, config: Option<AdcConfig>) {
+        self.config = config;
+        let Some(config) = config else {
+            return;
+        };
+        // 中央に 0 がある量子化 (mid-tread)。符号は -2^(N-1) ..= 2^(N-1) - 1
+        let full_scale = 10f64.powf(config.full_scale / 20.);
+        let half = 2f64.powi(config.bits as i32 - 1);
+        self.step = full_scale / half;
+        self.codes = (-half, half - 1.);
+    }
+    /// 乱数を差し替え、状態を初期値に戻す
+    pub fn reseed(&mut self, rng: Rng) {
+        self.rng = rng;
+        self.last = 0.;
+    }
+    pub fn process(&mut self, signal: &mut [f64]) {
+        let Some(config) = self.config else {
+            return;
+        };
+        // 時刻が δ ずれると x(t + δ) ≈ x(t) + δ x'(t)。x' は後退差分で近似する
+        let jitter = config.jitter * 1e-12 * self.sample_rate;
+        for v in signal.iter_mut() {
+            let mut x = *v;
+            if jitter > 0. {
+                x += jitter * self.rng.gaussian() * (*v - self.last);
+            }
+            self.last = *v;
+            if config.dither {
+                x += (self.rng.uniform() - self.rng.uniform()) * self.step;
+            }
+            let code = (x / self.step).round().clamp(self.codes.0, self.codes.1);
+            *v = code * self.step;
+        }
+    }
+}
@@ -0,0 +1,274 @@
+/**
+ * 放送局の音声プロセッサ
+ * ステレオエンコーダの前で、広帯域の AGC・マルチバンドのコンプレッサで音量を揃え、
+ * プリエンファシスを掛けた後の音声のピークを先読みのリミッタで抑える。
+ * リミッタの出力は |L|,|R| <= 1 になるので、コンポジット信号が最大周波数偏移を超えない。
+*/
+use super::filter::{FilterInfo, Lpf};
+use crate::config::ConfigError;
+use std::collections::VecDeque;
+
+/// コンプレッサの帯域の分け方
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompressorBands {
+    /// 200Hz, 3kHz で分ける
+    Three,
+    /// 100Hz, 400Hz, 1.6kHz, 6.4kHz で分ける
+    Five,
+}
+impl CompressorBands {
+    fn crossovers(&self) -> &'static [f64] {
+        match self {
+            Self::Three => &[200., 3000.],
+            Self::Five => &[100., 400., 1600., 6400.],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AudioProcessorConfig {
+    /// 広帯域の AGC で揃える音声のレベル (RMS) [dBFS]。None の場合は AGC を掛けない
+    pub agc_target: Option<f64>,
+    /// AGC の利得が目標に追従する時定数 [ms]
+    pub agc_time: f64,
+    /// AGC の利得の上限 [dB] (下限は -agc_max_gain)
+    pub agc_max_gain: f64,
+    pub bands: CompressorBands,
+    /// 各帯域のコンプレッサが働き始めるレベル (ピーク) [dBFS]
+    pub threshold: f64,
+    /// 圧縮比 (1 以上)
+    pub ratio: f64,
+    /// コンプレッサの時定数 [ms]
+    pub attack: f64,
+    pub release: f64,
+    /// コンプレッサの後に掛ける利得 [dB]。大きいほど音が大きくなり、リミッタが強く働く
+    pub drive: f64,
+    /// リミッタの利得が戻る時定数 [ms]
+    pub limiter_release: f64,
+}
+impl AudioProcessorConfig {
+    pub fn new(bands: CompressorBands) -> Self {
+        Self {
+            agc_target: Some(-20.),
+            agc_time: 3000.,
+            agc_max_gain: 15.,
+            bands,
+            threshold: -24.,
+            ratio: 4.,
+            attack: 5.,
+            release: 150.,
+            drive: 12.,
+            limiter_release: 50.,
+        }
+    }
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        for (name, value) in [
+            ("processor_agc_time", self.agc_time),
+            ("processor_attack", self.attack),
+            ("processor_release", self.release),
+            ("processor_limiter_release", self.limiter_release),
+        ] {
+            if !(value > 0. && value.is_finite()) {
+                return Err(ConfigError::NotPositive { name, value });
+            }
+        }
+        for (name, value) in [
+            ("processor_agc_target", self.agc_target.unwrap_or(0.)),
+            ("processor_agc_max_gain", self.agc_max_gain),
+            ("processor_threshold", self.threshold),
+            ("processor_drive", self.drive),
+        ] {
+            if !value.is_finite() {
+                return Err(ConfigError::NotFinite { name, value });
+            }
+        }
+        if !(self.ratio >= 1. && self.ratio.is_finite()) {
+            return Err(ConfigError::CompressorRatioOutOfRange(self.ratio));
+        }
+        Ok(())
+    }
+}
+
+/// 時定数 ms [ms] の 1 次の平滑化の係数
+fn smoothing(ms: f64, sample_rate: f64) -> f64 {
+    (-1. / (ms * 1e-3 * sample_rate)).exp()
+}
+fn db_to_gain(db: f64) -> f64 {
+    10f64.powf(db / 20.)
+}
+
+pub struct AudioProcessor {
+    config: AudioProcessorConfig,
+    sample_rate: f64,
+    // AGC の短時間の電力、利得 [dB] と平滑化の係数 (検出, 利得)
+    agc_power: f64,
+    agc_gain: f64,
+    agc_coeffs: (f64, f64),
+    // 帯域を分ける LPF (Linkwitz-Riley: 2 段重ねる) と、その状態 [帯域][L/R][段]
+    crossovers: Vec<Lpf>,
+    crossover_info: Vec<[[FilterInfo; 2]; 2]>,
+    // 各帯域の包絡線と平滑化の係数 (attack, release)
+    envelopes: Vec<f64>,
+    compressor_coeffs: (f64, f64),
+    // 先読みのリミッタ
+    lookahead: usize,
+    delay: VecDeque<(f64, f64)>,
+    // 先読みの範囲の必要な利得の最小値 (単調増加の列)
+    window: VecDeque<(u64, f64)>,
+    index: u64,
+    hold: f64,
+    // 保持した利得の移動平均
+    averaging: VecDeque<f64>,
+    average_sum: f64,
+    limiter_coeff: f64,
+}
+impl AudioProcessor {
+    /// リミッタの先読みの長さ [ms]。この分だけ音声が遅れる
+    pub const LOOKAHEAD: f64 = 1.5;
+    /// AGC のレベルの検出の時定数 [ms]
+    const AGC_DETECTOR: f64 = 50.;
+    /// これより小さい入力 (無音) では AGC の利得を保つ [dBFS]
+    const AGC_GATE: f64 = -50.;
+    pub fn new(sample_rate: f64, config: &AudioProcessorConfig) -> Self {
+        let lookahead = ((Self::LOOKAHEAD * 1e-3 * sample_rate).round() as usize).max(1);
+        let mut processor = Self {
+            config: *config,
+            sample_rate,
+            agc_power: 0.,
+            agc_gain: 0.,
+            agc_coeffs: (0., 0.),
+            crossovers: Vec::new(),
+            crossover_info: Vec::new(),
+            envelopes: Vec::new(),
+            compressor_coeffs: (0., 0.),
+            lookahead,
+            delay: VecDeque::with_capacity(lookahead),
+            window: VecDeque::with_capacity(lookahead),
+            index: 0,
+            hold: 1.,
+            averaging: VecDeque::with_capacity(lookahead),
+            average_sum: 0.,
+            limiter_coeff: 0.,
+        };
+        processor.set_config(config);
+        processor.reset();
+        processor
+    }
+    /// パラメータを変更する。帯域の分け方が変わった場合はコンプレッサの状態を初期化する
+    pub fn set_config(&mut self, config: &AudioProcessorConfig) {
+        let fs = self.sample_rate;
+        if self.crossovers.is_empty() || config.bands != self.config.bands {
+            let crossovers = config.bands.crossovers();
+            self.crossovers = crossovers
+                .iter()
+                .map(|f| Lpf::new(fs, *f, Lpf::Q))
+                .collect();
+            self.crossover_info = vec![Default::default(); crossovers.len()];
+            self.envelopes = vec![0.; crossovers.len() + 1];
+        }
+        self.config = *config;
+        self.agc_coeffs = (
+            smoothing(Self::AGC_DETECTOR, fs),
+            smoothing(config.agc_time, fs),
+        );
+        self.compressor_coeffs = (smoothing(config.attack, fs), smoothing(config.release, fs));
+        self.limiter_coeff = smoothing(config.limiter_release, fs);
+    }
+    pub fn reset(&mut self) {
+        self.agc_power = 0.;
+        self.agc_gain = 0.;
+        self.crossover_info.fill(Default::default());
+        self.envelopes.fill(0.);
+        self.delay.clear();
+        self.delay.resize(self.lookahead - 1, (0., 0.));
+        self.window.clear();
+        self.index = 0;
+        self.hold = 1.;
+        self.averaging.clear();
+        self.averaging.resize(self.lookahead, 1.);
+        self.average_sum = self.lookahead as f64;
+    }
+    /// プリエンファシスの前に掛ける AGC とマルチバンドのコンプレッサ
+    pub fn compress(&mut self, l: f64, r: f64) -> (f64, f64) {
+        let (l, r) = self.agc(l, r);
+        let (attack, release) = self.compressor_coeffs;
+        let slope = 1. - 1. / self.config.ratio;
+        let (mut rest_l, mut rest_r) = (l, r);
+        let (mut out_l, mut out_r) = (0., 0.);
+        for band in 0..self.envelopes.len() {
+            // 低い帯域から順に切り出し、残りを次の帯域に回す (全帯域の和は入力に戻る)
+            let (band_l, band_r) = match self.crossovers.get(band) {
+                Some(lpf) => {
+                    let [info_l, info_r] = &mut self.crossover_info[band];
+                    let low_l = lpf.process_without_buffer(
+                        lpf.process_without_buffer(rest_l, &mut info_l[0]),
+                        &mut info_l[1],
+                    );
+                    let low_r = lpf.process_without_buffer(
+                        lpf.process_without_buffer(rest_r, &mut info_r[0]),
+                        &mut info_r[1],
+                    );
+                    rest_l -= low_l;
+                    rest_r -= low_r;
+                    (low_l, low_r)
+                }
+                None => (rest_l, rest_r),
+            };
+            let peak = band_l.abs().max(band_r.abs());
+            let envelope = &mut self.envelopes[band];
+            let coeff = if peak > *envelope { attack } else { release };
+            *envelope = coeff * *envelope + (1. - coeff) * peak;
+            let over = 20. * envelope.max(1e-10).log10() - self.config.threshold;
+            let gain = db_to_gain(-over.max(0.) * slope);
+            out_l += band_l * gain;
+            out_r += band_r * gain;
+        }
+        let drive = db_to_gain(self.config.drive);
+        (out_l * drive, out_r * drive)
+    }
+    fn agc(&mut self, l: f64, r: f64) -> (f64, f64) {
+        let Some(target) = self.config.agc_target else {
+            return (l, r);
+        };
+        let (detector, time) = self.agc_coeffs;
+        self.agc_power = detector * self.agc_power + (1. - detector) * (l * l + r * r) / 2.;
+        let level = 10. * self.agc_power.max(1e-20).log10();
+        if level > Self::AGC_GATE {
+            let max_gain = self.config.agc_max_gain;
+            let required = (target - level).clamp(-max_gain, max_gain);
+            self.agc_gain = time * self.agc_gain + (1. - time) * required;
+        }
+        let gain = db_to_gain(self.agc_gain);
+        (l * gain, r * gain)
+    }
+    /// プリエンファシスの後に掛ける先読みのリミッタ (出力は |L|,|R| <= 1、LOOKAHEAD - 1 サンプル遅れる)
+    pub fn limit(&mut self, l: f64, r: f64) -> (f64, f64) {
+        let required = 1f64.min(1. / l.abs().max(r.abs()));
+        // 直近の lookahead サンプルの最小値を保持し、ゆっくり戻す
+        while self.window.back().is_some_and(|(_, v)| *v >= required) {
+            self.window.pop_back();
+        }
+        self.window.push_back((self.index, required));
+        while self
+            .window
+            .front()
+            .is_some_and(|(i, _)| i + self.lookahead as u64 <= self.index)
+        {
+            self.window.pop_front();
+        }
+        self.index += 1;
+        let released = self.hold + (1. - self.hold) * (1. - self.limiter_coeff);
+        self.hold = released.min(self.window[0].1);
+        // 保持した値の lookahead サンプルの平均は、その範囲の全ての必要な利得以下になる
+        self.average_sum += self.hold - self.averaging.pop_front().unwrap_or(1.);
+        self.averaging.push_back(self.hold);
+        if self.index.is_multiple_of(self.lookahead as u64) {
+            // 丸め誤差が溜まらないように計算し直す
+            self.average_sum = self.averaging.iter().sum();
+        }
+        let gain = (self.average_sum / self.lookahead as f64).min(1.);
+        self.delay.push_back((l, r));
+        let (l, r) = self.delay.pop_front().unwrap_or_default();
+        (l * gain, r * gain)
+    }
+}
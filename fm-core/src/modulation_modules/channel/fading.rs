@@ -0,0 +1,148 @@
+/**
+ * 移動受信のフェージング (Rayleigh / Rician)
+ * 到来角の異なる多数の波の和 (Clarke モデル) を正弦波の和で作り、
+ * その複素利得を RF の実信号に掛ける。
+*/
+use super::Rng;
+use crate::config::ConfigError;
+use crate::modulation_modules::quadrature::Quadrature;
+use std::f64::consts::TAU;
+
+/// 光速 [m/s]
+const SPEED_OF_LIGHT: f64 = 299_792_458.;
+/// 散乱波を作る正弦波の数
+const SINUSOIDS: usize = 32;
+/// 利得を計算し直す間隔 [サンプル] (間は線形補間する)
+const UPDATE_INTERVAL: usize = 256;
+
+/// フェージングの設定
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fading {
+    /// 受信機 (車) の速度 [km/h]。最大ドップラー周波数は搬送波周波数から決まる
+    pub speed: f64,
+    /// 直接波と散乱波の電力比 (線形)。0 で Rayleigh フェージング
+    pub k_factor: f64,
+}
+impl Fading {
+    pub fn rayleigh(speed: f64) -> Self {
+        Self {
+            speed,
+            k_factor: 0.,
+        }
+    }
+    pub fn rician(speed: f64, k_factor: f64) -> Self {
+        Self { speed, k_factor }
+    }
+    /// 搬送波周波数 carrier_freq [Hz] での最大ドップラー周波数 [Hz]
+    pub fn doppler_freq(&self, carrier_freq: f64) -> f64 {
+        self.speed / 3.6 / SPEED_OF_LIGHT * carrier_freq
+    }
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        for (name, value) in [("fading_speed", self.speed), ("k_factor", self.k_factor)] {
+            if !(value >= 0. && value.is_finite()) {
+                return Err(ConfigError::NotPositive { name, value });
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct Fader {
+    sample_rate: f64,
+    carrier_freq: f64,
+    fading: Option<Fading>,
+    // 各波の到来角の cos と、現在の位相 (最後が直接波)
+    cos_angles: [f64; SINUSOIDS + 1],
+    phases: [f64; SINUSOIDS + 1],
+    // 現在の複素利得と、1 サンプルあたりの変化量
+    gain: (f64, f64),
+    step: (f64, f64),
+    remain: usize,
+    quadrature: Quadrature,
+}
+impl Fader {
+    pub fn new(sample_rate: f64, carrier_freq: f64, fading: Option<Fading>, rng: Rng) -> Self {
+        let mut fader = Self {
+            sample_rate,
+            carrier_freq,
+            fading,
+            cos_angles: [0.; SINUSOIDS + 1],
+            phases: [0.; SINUSOIDS + 1],
+            gain: (1., 0.),
+            step: (0., 0.),
+            remain: 0,
+            quadrature: Quadrature::new(sample_rate, carrier_freq),
+        };
+        fader.reseed(rng);
+        fader
+    }
+    /// 設定を変更する。位相は引き継ぐので、処理中に速度を変えても利得は連続する
+    pub fn set_fading(&mut self, fading: Option<Fading>) {
+        self.fading = fading;
+        self.gain = self.current_gain();
+        self.remain = 0;
+    }
+    pub fn set_carrier_freq(&mut self, freq: f64) {
+        self.carrier_freq = freq;
+        self.quadrature.set_freq(self.sample_rate, freq);
+    }
+    /// 到来角・初期位相を引き直し、時刻 0 からやり直す
+    pub fn reseed(&mut self, mut rng: Rng) {
+        for (cos_angle, phase) in self.cos_angles.iter_mut().zip(self.phases.iter_mut()) {
+            *cos_angle = (TAU * rng.uniform()).cos();
+            *phase = TAU * rng.uniform();
+        }
+        self.gain = self.current_gain();
+        self.step = (0., 0.);
+        self.remain = 0;
+        self.quadrature.reset();
+    }
+    fn current_gain(&self) -> (f64, f64) {
+        let Some(fading) = self.fading else {
+            return (1., 0.);
+        };
+        let scatter = (1. / ((fading.k_factor + 1.) * SINUSOIDS as f64)).sqrt();
+        let los = (fading.k_factor / (fading.k_factor + 1.)).sqrt();
+        let (mut re, mut im) = (0., 0.);
+        for (i, phase) in self.phases.iter().enumerate() {
+            let amp = if i == SINUSOIDS { los } else { scatter };
+            let (sin, cos) = phase.sin_cos();
+            re += amp * cos;
+            im += amp * sin;
+        }
+        (re, im)
+    }
+    // 次の更新点まで位相を進め、そこまでの利得を線形補間する
+    fn advance(&mut self) {
+        let doppler = self
+            .fading
+            .map_or(0., |fading| fading.doppler_freq(self.carrier_freq));
+        let delta = TAU * doppler * UPDATE_INTERVAL as f64 / self.sample_rate;
+        for (phase, cos_angle) in self.phases.iter_mut().zip(self.cos_angles) {
+            *phase = (*phase + delta * cos_angle) % TAU;
+        }
+        let next = self.current_gain();
+        self.step = (
+            (next.0 - self.gain.0) / UPDATE_INTERVAL as f64,
+            (next.1 - self.gain.1) / UPDATE_INTERVAL as f64,
+        );
+        self.remain = UPDATE_INTERVAL;
+    }
+    pub fn process(&mut self, signal: &mut [f64]) {
+        if self.fading.is_none() {
+            return;
+        }
+        // 複素利得 h を掛けた Re{h (x + j x̂)} = h.re x - h.im x̂
+        for v in signal.iter_mut() {
+            if self.remain == 0 {
+                self.advance();
+            }
+            let x = *v;
+            let quadrature = self.quadrature.process(x);
+            *v = self.gain.0 * x - self.gain.1 * quadrature;
+            self.gain.0 += self.step.0;
+            self.gain.1 += self.step.1;
+            self.remain -= 1;
+        }
+    }
+}
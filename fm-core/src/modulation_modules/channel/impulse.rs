@@ -0,0 +1,189 @@
+/**
+ * パルス性雑音 (点火プラグ・スイッチング・雷)
+ * パルスの発生時刻・振幅を乱数で決め、波形を整形するフィルタを通して RF の信号に足す。
+*/
+use super::Rng;
+use crate::config::ConfigError;
+use std::f64::consts::TAU;
+
+/// パルスの発生間隔
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PulseTiming {
+    /// 一定の間隔 (rate [Hz])。エンジンの点火等
+    Periodic { rate: f64 },
+    /// ポアソン過程 (平均 rate [Hz])。スイッチング・雷等
+    Random { rate: f64 },
+}
+/// パルスの振幅 (搬送波の振幅 1 に対するピーク値) の分布
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PulseAmplitude {
+    Fixed(f64),
+    /// Rayleigh 分布 (scale: 最頻値)
+    Rayleigh {
+        scale: f64,
+    },
+    /// 対数正規分布 (median: 中央値、sigma: 標準偏差 [dB])
+    LogNormal {
+        median: f64,
+        sigma: f64,
+    },
+}
+/// パルスの波形
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PulseShape {
+    /// 1 サンプルのインパルス (全帯域に広がる)
+    Impulse,
+    /// 指数関数的に減衰する (decay: 時定数 [μs])
+    Exponential { decay: f64 },
+    /// 周波数 freq [Hz] で減衰振動する (decay: 包絡線の時定数 [μs])
+    DampedSine { freq: f64, decay: f64 },
+}
+
+/// パルス性雑音の設定
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImpulseNoise {
+    pub timing: PulseTiming,
+    pub amplitude: PulseAmplitude,
+    pub shape: PulseShape,
+}
+impl ImpulseNoise {
+    /// 自動車の点火雑音 (エンジン回転に合わせた周期、減衰の速いパルス)
+    pub fn ignition(rate: f64, amplitude: f64) -> Self {
+        Self {
+            timing: PulseTiming::Periodic { rate },
+            amplitude: PulseAmplitude::LogNormal {
+                median: amplitude,
+                sigma: 3.,
+            },
+            shape: PulseShape::Exponential { decay: 0.05 },
+        }
+    }
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        let rate = match self.timing {
+            PulseTiming::Periodic { rate } | PulseTiming::Random { rate } => rate,
+        };
+        let mut positive = vec![("impulse_rate", rate)];
+        match self.shape {
+            PulseShape::Impulse => {}
+            PulseShape::Exponential { decay } => positive.push(("impulse_decay", decay)),
+            PulseShape::DampedSine { freq, decay } => {
+                positive.push(("impulse_freq", freq));
+                positive.push(("impulse_decay", decay));
+            }
+        }
+        for (name, value) in positive {
+            if !(value > 0. && value.is_finite()) {
+                return Err(ConfigError::NotPositive { name, value });
+            }
+        }
+        let (name, value) = match self.amplitude {
+            PulseAmplitude::Fixed(value) => ("impulse_amplitude", value),
+            PulseAmplitude::Rayleigh { scale } => ("impulse_amplitude", scale),
+            PulseAmplitude::LogNormal { median, sigma } => {
+                if !(sigma >= 0. && sigma.is_finite()) {
+                    return Err(ConfigError::NotPositive {
+                        name: "impulse_sigma",
+                        value: sigma,
+                    });
+                }
+                ("impulse_amplitude", median)
+            }
+        };
+        if !(value >= 0. && value.is_finite()) {
+            return Err(ConfigError::NotPositive { name, value });
+        }
+        Ok(())
+    }
+}
+
+pub struct ImpulseGenerator {
+    sample_rate: f64,
+    noise: Option<ImpulseNoise>,
+    rng: Rng,
+    // 次のパルスまでのサンプル数
+    countdown: f64,
+    // 整形フィルタ y[n] = x[n] * b0 + a1 y[n-1] + a2 y[n-2]
+    coeffs: (f64, f64, f64),
+    state: [f64; 2],
+}
+impl ImpulseGenerator {
+    pub fn new(sample_rate: f64, noise: Option<ImpulseNoise>, rng: Rng) -> Self {
+        let mut generator = Self {
+            sample_rate,
+            noise: None,
+            rng,
+            countdown: 0.,
+            coeffs: (1., 0., 0.),
+            state: [0.; 2],
+        };
+        generator.set_noise(noise);
+        generator
+    }
+    /// 設定を変更する。発生中のパルスの減衰はそのまま続く
+    pub fn set_noise(&mut self, noise: Option<ImpulseNoise>) {
+        let changed_timing = self.noise.map(|n| n.timing) != noise.map(|n| n.timing);
+        self.noise = noise;
+        self.coeffs = match noise.map(|n| n.shape) {
+            None | Some(PulseShape::Impulse) => (1., 0., 0.),
+            Some(PulseShape::Exponential { decay }) => {
+                (1., (-1. / (decay * 1e-6 * self.sample_rate)).exp(), 0.)
+            }
+            // 2 極の共振器。インパルス応答 r^n sin((n+1)ω) / sin ω のピークが 1 になるよう b0 = sin ω
+            Some(PulseShape::DampedSine { freq, decay }) => {
+                let r = (-1. / (decay * 1e-6 * self.sample_rate)).exp();
+                let (sin, cos) = (TAU * freq / self.sample_rate).sin_cos();
+                (sin, 2. * r * cos, -r * r)
+            }
+        };
+        if changed_timing {
+            self.countdown = self.next_interval();
+        }
+    }
+    /// 乱数を差し替え、最初のパルスからやり直す
+    pub fn reseed(&mut self, rng: Rng) {
+        self.rng = rng;
+        self.state = [0.; 2];
+        self.countdown = self.next_interval();
+    }
+    fn next_interval(&mut self) -> f64 {
+        match self.noise.map(|n| n.timing) {
+            None => 0.,
+            Some(PulseTiming::Periodic { rate }) => self.sample_rate / rate,
+            Some(PulseTiming::Random { rate }) => {
+                -self.rng.uniform().ln() * self.sample_rate / rate
+            }
+        }
+    }
+    fn pulse_amplitude(&mut self, amplitude: PulseAmplitude) -> f64 {
+        let peak = match amplitude {
+            PulseAmplitude::Fixed(value) => value,
+            PulseAmplitude::Rayleigh { scale } => scale * (-2. * self.rng.uniform().ln()).sqrt(),
+            PulseAmplitude::LogNormal { median, sigma } => {
+                median * 10f64.powf(sigma * self.rng.gaussian() / 20.)
+            }
+        };
+        // 極性はランダム
+        if self.rng.next_u64() & 1 == 0 {
+            peak
+        } else {
+            -peak
+        }
+    }
+    pub fn process(&mut self, signal: &mut [f64]) {
+        let Some(noise) = self.noise else {
+            return;
+        };
+        let (b0, a1, a2) = self.coeffs;
+        for v in signal.iter_mut() {
+            let mut x = 0.;
+            self.countdown -= 1.;
+            while self.countdown < 0. {
+                x += self.pulse_amplitude(noise.amplitude);
+                self.countdown += self.next_interval();
+            }
+            let y = b0 * x + a1 * self.state[0] + a2 * self.state[1];
+            self.state = [y, self.state[0]];
+            *v += y;
+        }
+    }
+}
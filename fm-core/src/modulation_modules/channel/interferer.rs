@@ -0,0 +1,264 @@
+/**
+ * 妨害局 (隣接チャンネル・同一チャンネル)
+ * 希望局と同じ送信機 (コンポジット信号の生成・アップサンプル・変調) を妨害局ごとに持ち、
+ * RF のサンプルレートで受信機の入力に足し合わせる。
+*/
+use super::Rng;
+use crate::config::{ConfigError, FmRadioSimConfig};
+use crate::kernels::{upsample, Backend};
+use crate::modulation_modules::{composite::CompositeSignal, modulator::Modulator};
+use crate::resampler::ResamplerInfo;
+use std::f64::consts::TAU;
+use std::sync::Arc;
+
+/// 妨害局の音源の乱数系列 (伝搬路の雑音とは別の系列にする)
+const INTERFERER_STREAM: u64 = 0x696e_7465_7266_6572;
+
+/// 妨害局が送信する音声
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterfererSource {
+    /// 左右同じ正弦波
+    Tone { freq: f64, amplitude: f64 },
+    /// 左右独立の白色雑音 (標準偏差 amplitude)
+    Noise { amplitude: f64 },
+    /// 音声データ (WAV ファイル等)。末尾まで再生したら先頭に戻る
+    Samples {
+        sample_rate: f64,
+        left: Arc<[f32]>,
+        right: Arc<[f32]>,
+    },
+}
+
+/// 妨害局の設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interferer {
+    pub source: InterfererSource,
+    /// 希望局の搬送波からの周波数差 [Hz] (0: 同一チャンネル)
+    pub offset: f64,
+    /// 希望局に対する電力比 [dB]
+    pub level: f64,
+    /// 入力の振幅 1 に対する周波数偏移 [Hz]
+    pub deviation: f64,
+}
+impl Interferer {
+    pub fn new(source: InterfererSource, offset: f64, level: f64) -> Self {
+        Self {
+            source,
+            offset,
+            level,
+            deviation: FmRadioSimConfig::DEFAULT_DEVIATION,
+        }
+    }
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        let positive = match &self.source {
+            InterfererSource::Tone { freq, .. } => Some(("interferer_tone_freq", *freq)),
+            InterfererSource::Samples {
+                sample_rate,
+                left,
+                right,
+            } => {
+                if left.is_empty() || left.len() != right.len() {
+                    return Err(ConfigError::NotPositive {
+                        name: "interferer_samples",
+                        value: left.len().min(right.len()) as f64,
+                    });
+                }
+                Some(("interferer_sample_rate", *sample_rate))
+            }
+            InterfererSource::Noise { .. } => None,
+        };
+        for (name, value) in positive
+            .into_iter()
+            .chain([("interferer_deviation", self.deviation)])
+        {
+            if !(value > 0. && value.is_finite()) {
+                return Err(ConfigError::NotPositive { name, value });
+            }
+        }
+        let amplitude = match self.source {
+            InterfererSource::Tone { amplitude, .. } | InterfererSource::Noise { amplitude } => {
+                amplitude
+            }
+            InterfererSource::Samples { .. } => 0.,
+        };
+        for (name, value) in [
+            ("interferer_amplitude", amplitude),
+            ("interferer_offset", self.offset),
+            ("interferer_level", self.level),
+        ] {
+            if !value.is_finite() {
+                return Err(ConfigError::NotFinite { name, value });
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Station {
+    source: InterfererSource,
+    // 音源の再生位置 (Tone: 位相 [rad]、Samples: 入力のサンプル位置)
+    position: f64,
+    rng: Rng,
+    gain: f64,
+    offset: f64,
+    composite: CompositeSignal,
+    modulator: Modulator,
+    upsampler: ResamplerInfo,
+    audio: [Vec<f64>; 2],
+    composite_signal: Vec<f64>,
+    up_sampled_signal: Vec<f64>,
+    modulate_signal: Vec<f64>,
+}
+impl Station {
+    fn generate_audio(&mut self, sample_rate: f64) {
+        let [l, r] = &mut self.audio;
+        match &self.source {
+            InterfererSource::Tone { freq, amplitude } => {
+                let delta = TAU * freq / sample_rate;
+                for (l, r) in l.iter_mut().zip(r.iter_mut()) {
+                    *l = amplitude * self.position.sin();
+                    *r = *l;
+                    self.position = (self.position + delta) % TAU;
+                }
+            }
+            InterfererSource::Noise { amplitude } => {
+                for (l, r) in l.iter_mut().zip(r.iter_mut()) {
+                    *l = amplitude * self.rng.gaussian();
+                    *r = amplitude * self.rng.gaussian();
+                }
+            }
+            // コンポジット信号の生成時に 15kHz の LPF を通るので、線形補間で足りる
+            InterfererSource::Samples {
+                sample_rate: source_rate,
+                left,
+                right,
+            } => {
+                let step = source_rate / sample_rate;
+                let len = left.len();
+                for (l, r) in l.iter_mut().zip(r.iter_mut()) {
+                    let i = self.position as usize;
+                    let frac = self.position - i as f64;
+                    let j = (i + 1) % len;
+                    *l = left[i] as f64 + (left[j] - left[i]) as f64 * frac;
+                    *r = right[i] as f64 + (right[j] - right[i]) as f64 * frac;
+                    self.position = (self.position + step) % len as f64;
+                }
+            }
+        }
+    }
+}
+
+pub struct Interferers {
+    composite_sample_rate: f64,
+    rf_sample_rate: f64,
+    composite_buffer_size: usize,
+    carrier_freq: f64,
+    pilot_level: f64,
+    emphasis_tau: f64,
+    seed: u64,
+    interferers: Vec<Interferer>,
+    stations: Vec<Station>,
+    backend: Backend,
+}
+impl Interferers {
+    pub fn new(config: &FmRadioSimConfig, composite_buffer_size: usize) -> Self {
+        let mut interferers = Self {
+            composite_sample_rate: config.composite_sample_rate() as f64,
+            rf_sample_rate: config.rf_sample_rate() as f64,
+            composite_buffer_size,
+            carrier_freq: config.carrier_freq(),
+            pilot_level: config.pilot_level(),
+            emphasis_tau: config.tx_emphasis_tau(),
+            seed: config.channel().seed,
+            interferers: Vec::new(),
+            stations: Vec::new(),
+            backend: Backend::detect(),
+        };
+        interferers.set_interferers(&config.channel().interferers);
+        interferers
+    }
+    /// 妨害局を作り直す (各局の送信機は初期状態から始まる)
+    pub fn set_interferers(&mut self, interferers: &[Interferer]) {
+        self.interferers = interferers.to_vec();
+        self.reset();
+    }
+    /// 希望局の搬送波に合わせて、各局の搬送波を位相連続で動かす
+    pub fn set_carrier_freq(&mut self, freq: f64) {
+        self.carrier_freq = freq;
+        for station in &mut self.stations {
+            station.modulator.set_carrier_freq(freq + station.offset);
+        }
+    }
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.reset();
+    }
+    pub fn reset(&mut self) {
+        let mut rng = Rng::new(self.seed ^ INTERFERER_STREAM);
+        let rf_len = self.composite_buffer_size
+            * (self.rf_sample_rate / self.composite_sample_rate) as usize;
+        self.stations = self
+            .interferers
+            .iter()
+            .map(|interferer| {
+                let mut modulator = Modulator::from(
+                    self.carrier_freq + interferer.offset,
+                    self.rf_sample_rate,
+                    interferer.deviation,
+                );
+                modulator.set_backend(self.backend);
+                Station {
+                    source: interferer.source.clone(),
+                    position: 0.,
+                    rng: rng.fork(),
+                    // 変調器の出力は希望局と同じ振幅 1 なので、電力比がそのまま振幅比になる
+                    gain: 10f64.powf(interferer.level / 20.),
+                    offset: interferer.offset,
+                    composite: CompositeSignal::new(
+                        self.composite_sample_rate,
+                        self.pilot_level,
+                        self.emphasis_tau,
+                    ),
+                    modulator,
+                    upsampler: ResamplerInfo::new_upsample_info(
+                        self.composite_sample_rate as usize,
+                        self.rf_sample_rate as usize,
+                        self.composite_buffer_size,
+                    ),
+                    audio: [
+                        vec![0.; self.composite_buffer_size],
+                        vec![0.; self.composite_buffer_size],
+                    ],
+                    composite_signal: vec![0.; self.composite_buffer_size],
+                    up_sampled_signal: vec![0.; rf_len],
+                    modulate_signal: vec![0.; rf_len],
+                }
+            })
+            .collect();
+    }
+    /// signal (希望局の RF 信号) に妨害局の信号を足す
+    pub fn process(&mut self, signal: &mut [f64]) {
+        for station in &mut self.stations {
+            station.generate_audio(self.composite_sample_rate);
+            station.composite.process(
+                &station.audio[0],
+                &station.audio[1],
+                &mut station.composite_signal,
+            );
+            unsafe {
+                upsample(
+                    self.backend,
+                    station.up_sampled_signal.as_mut_ptr(),
+                    station.composite_signal.as_ptr(),
+                    &raw mut station.upsampler,
+                );
+            }
+            station
+                .modulator
+                .process(&station.up_sampled_signal, &mut station.modulate_signal);
+            for (v, s) in signal.iter_mut().zip(station.modulate_signal.iter()) {
+                *v += station.gain * s;
+            }
+        }
+    }
+}
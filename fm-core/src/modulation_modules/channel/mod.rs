@@ -0,0 +1,238 @@
+/**
+ * 送信機と受信機の間の伝搬路
+ * 変調器の出力 (RF のサンプルレート) に対して、その場で劣化を加える。
+*/
+use crate::config::ConfigError;
+use std::f64::consts::TAU;
+pub mod fading;
+pub mod impulse;
+pub mod interferer;
+pub mod multipath;
+pub use fading::Fading;
+pub use impulse::{ImpulseNoise, PulseAmplitude, PulseShape, PulseTiming};
+pub use interferer::{Interferer, InterfererSource};
+pub use multipath::MultipathTap;
+
+/// ボルツマン定数 [J/K]
+const BOLTZMANN: f64 = 1.380_649e-23;
+/// 雑音指数の基準温度 [K]
+const REFERENCE_TEMPERATURE: f64 = 290.;
+/// アンテナ入力のインピーダンス [Ω]
+const INPUT_IMPEDANCE: f64 = 75.;
+/// 変調器の出力 (振幅 1 の cos) の電力
+const CARRIER_POWER: f64 = 0.5;
+
+/// 決まった系列を返す乱数 (xoshiro256++、種は splitmix64 で展開する)
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: [u64; 4],
+    spare_gaussian: Option<f64>,
+}
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut sm = seed;
+        let state = [0; 4].map(|_| {
+            sm = sm.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = sm;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        });
+        Self {
+            state,
+            spare_gaussian: None,
+        }
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.state;
+        let result = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+    /// この系列から種を取り、独立した系列を作る
+    pub fn fork(&mut self) -> Self {
+        Self::new(self.next_u64())
+    }
+    /// (0, 1] の一様乱数
+    pub fn uniform(&mut self) -> f64 {
+        ((self.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+    /// 平均 0、分散 1 の正規乱数 (Box-Muller、2 つ目は次の呼び出しで返す)
+    pub fn gaussian(&mut self) -> f64 {
+        if let Some(v) = self.spare_gaussian.take() {
+            return v;
+        }
+        let r = (-2. * self.uniform().ln()).sqrt();
+        let (sin, cos) = (TAU * self.uniform()).sin_cos();
+        self.spare_gaussian = Some(r * sin);
+        r * cos
+    }
+}
+
+/// 受信機に加わる熱雑音のレベル
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoiseLevel {
+    /// 中間周波数帯の帯域幅 (if_bandwidth) 内の搬送波電力対雑音電力比 [dB]
+    Cnr(f64),
+    /// アンテナ入力レベル [dBμV] (75Ω) と受信機の雑音指数 [dB] から C/N を決める
+    InputLevel { dbuv: f64, noise_figure: f64 },
+}
+impl NoiseLevel {
+    /// 帯域幅 bandwidth [Hz] 内の C/N [dB]
+    pub fn cnr_db(&self, bandwidth: f64) -> f64 {
+        match *self {
+            Self::Cnr(cnr) => cnr,
+            Self::InputLevel { dbuv, noise_figure } => {
+                let volt = 10f64.powf(dbuv / 20.) * 1e-6;
+                let carrier = volt * volt / INPUT_IMPEDANCE;
+                let noise = BOLTZMANN * REFERENCE_TEMPERATURE * bandwidth;
+                10. * (carrier / noise).log10() - noise_figure
+            }
+        }
+    }
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        let (name, value) = match *self {
+            Self::Cnr(cnr) => ("cnr", cnr),
+            Self::InputLevel { dbuv, noise_figure } => {
+                if !(noise_figure >= 0. && noise_figure.is_finite()) {
+                    return Err(ConfigError::NotPositive {
+                        name: "noise_figure",
+                        value: noise_figure,
+                    });
+                }
+                ("input_level", dbuv)
+            }
+        };
+        if value.is_finite() {
+            Ok(())
+        } else {
+            Err(ConfigError::NotFinite { name, value })
+        }
+    }
+}
+
+/// 伝搬路の設定 (既定では劣化なし)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ChannelConfig {
+    pub noise: Option<NoiseLevel>,
+    /// 遅延波のタップ (空: 直接波のみ)
+    pub multipath: Vec<MultipathTap>,
+    /// 移動受信のフェージング (遅延波を合成した後の信号全体に掛かる)
+    pub fading: Option<Fading>,
+    /// パルス性雑音 (熱雑音と同じく受信機の入力に加わる)
+    pub impulse_noise: Option<ImpulseNoise>,
+    /// 妨害局 (希望局の伝搬路の劣化は受けずに、受信機の入力に足される)
+    pub interferers: Vec<Interferer>,
+    /// 乱数の種。同じ種・同じ入力からは同じ出力になる
+    pub seed: u64,
+}
+impl ChannelConfig {
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(noise) = &self.noise {
+            noise.validate()?;
+        }
+        for tap in &self.multipath {
+            tap.validate()?;
+        }
+        if let Some(fading) = &self.fading {
+            fading.validate()?;
+        }
+        if let Some(impulse_noise) = &self.impulse_noise {
+            impulse_noise.validate()?;
+        }
+        for interferer in &self.interferers {
+            interferer.validate()?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Channel {
+    sample_rate: f64,
+    noise_bandwidth: f64,
+    seed: u64,
+    // 加える雑音の標準偏差 (None: 雑音なし)
+    noise_sigma: Option<f64>,
+    rng: Rng,
+    multipath: multipath::Multipath,
+    fader: fading::Fader,
+    impulse: impulse::ImpulseGenerator,
+}
+impl Channel {
+    /// noise_bandwidth: C/N を定義する帯域幅 (受信機の中間周波数帯の帯域幅)
+    pub fn new(
+        sample_rate: f64,
+        carrier_freq: f64,
+        noise_bandwidth: f64,
+        config: &ChannelConfig,
+    ) -> Self {
+        let mut rng = Rng::new(config.seed);
+        let fader = fading::Fader::new(sample_rate, carrier_freq, config.fading, rng.fork());
+        let impulse = impulse::ImpulseGenerator::new(sample_rate, config.impulse_noise, rng.fork());
+        let mut channel = Self {
+            sample_rate,
+            noise_bandwidth,
+            seed: config.seed,
+            noise_sigma: None,
+            rng,
+            multipath: multipath::Multipath::new(sample_rate, carrier_freq, &config.multipath),
+            fader,
+            impulse,
+        };
+        channel.set_noise(config.noise);
+        channel
+    }
+    pub fn set_noise(&mut self, noise: Option<NoiseLevel>) {
+        // 実信号の白色雑音は 0..fs/2 に分散 σ² が均等に広がるので、
+        // 帯域幅 B 内の雑音電力は σ² * 2B / fs
+        self.noise_sigma = noise.map(|noise| {
+            let cnr = 10f64.powf(noise.cnr_db(self.noise_bandwidth) / 10.);
+            (CARRIER_POWER * self.sample_rate / (2. * self.noise_bandwidth * cnr)).sqrt()
+        });
+    }
+    pub fn set_multipath(&mut self, taps: &[MultipathTap]) {
+        self.multipath.set_taps(taps);
+    }
+    pub fn set_fading(&mut self, fading: Option<Fading>) {
+        self.fader.set_fading(fading);
+    }
+    pub fn set_impulse_noise(&mut self, noise: Option<ImpulseNoise>) {
+        self.impulse.set_noise(noise);
+    }
+    /// 送信側の搬送波周波数 (遅延波の位相・ドップラー周波数の基準) を変更する
+    pub fn set_carrier_freq(&mut self, freq: f64) {
+        self.multipath.set_carrier_freq(freq);
+        self.fader.set_carrier_freq(freq);
+    }
+    /// 乱数の系列を種から作り直す
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.reseed();
+    }
+    pub fn reset(&mut self) {
+        self.reseed();
+        self.multipath.reset();
+    }
+    fn reseed(&mut self) {
+        let mut rng = Rng::new(self.seed);
+        self.fader.reseed(rng.fork());
+        self.impulse.reseed(rng.fork());
+        self.rng = rng;
+    }
+    pub fn process(&mut self, signal: &mut [f64]) {
+        self.multipath.process(signal);
+        self.fader.process(signal);
+        self.impulse.process(signal);
+        if let Some(sigma) = self.noise_sigma {
+            for v in signal.iter_mut() {
+                *v += sigma * self.rng.gaussian();
+            }
+        }
+    }
+}
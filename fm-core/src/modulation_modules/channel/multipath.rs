@@ -0,0 +1,166 @@
+/**
+ * マルチパス (遅延波) のタップ付き遅延線
+ * RF の実信号に対して、各タップの遅延・利得・位相を与えた波を足し合わせる。
+*/
+use crate::config::ConfigError;
+use crate::modulation_modules::quadrature::Quadrature;
+use std::f64::consts::{PI, TAU};
+
+/// 到来波 1 つ分の遅延・利得・位相
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MultipathTap {
+    /// 遅延 [μs]
+    pub delay: f64,
+    /// 振幅の利得
+    pub gain: f64,
+    /// 搬送波に対する位相 [rad] (遅延による搬送波の位相回転は含まない)
+    pub phase: f64,
+}
+impl MultipathTap {
+    pub fn new(delay: f64, gain: f64, phase: f64) -> Self {
+        Self { delay, gain, phase }
+    }
+    /// 市街地 (COST207 Typical Urban の 6 波)
+    pub fn urban() -> Vec<Self> {
+        Self::normalized(&[
+            (0.0, -3., 0.0),
+            (0.2, 0., 2.1),
+            (0.5, -2., -1.3),
+            (1.6, -6., 0.7),
+            (2.3, -8., -2.6),
+            (5.0, -10., 1.9),
+        ])
+    }
+    /// 山間部 (COST207 Hilly Terrain の 6 波、15μs 付近に山からの反射)
+    pub fn hilly_terrain() -> Vec<Self> {
+        Self::normalized(&[
+            (0.0, 0., 0.0),
+            (0.1, -1.5, -2.2),
+            (0.3, -4.5, 1.1),
+            (0.5, -7.5, 2.8),
+            (15.0, -8., -0.9),
+            (17.2, -17.7, 1.6),
+        ])
+    }
+    /// 建物からの強い反射が 1 つ (行路差 約 1km、反射で位相が反転)
+    pub fn building_reflection() -> Vec<Self> {
+        Self::normalized(&[(0.0, 0., 0.0), (3.3, -3., PI)])
+    }
+    /// (遅延 [μs], 電力 [dB], 位相) から、全体の電力が 1 になるタップ列を作る
+    fn normalized(profile: &[(f64, f64, f64)]) -> Vec<Self> {
+        let total: f64 = profile.iter().map(|&(_, db, _)| 10f64.powf(db / 10.)).sum();
+        profile
+            .iter()
+            .map(|&(delay, db, phase)| {
+                Self::new(delay, (10f64.powf(db / 10.) / total).sqrt(), phase)
+            })
+            .collect()
+    }
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        if !(self.delay >= 0. && self.delay.is_finite()) {
+            return Err(ConfigError::NotPositive {
+                name: "multipath_delay",
+                value: self.delay,
+            });
+        }
+        for (name, value) in [
+            ("multipath_gain", self.gain),
+            ("multipath_phase", self.phase),
+        ] {
+            if !value.is_finite() {
+                return Err(ConfigError::NotFinite { name, value });
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct Multipath {
+    sample_rate: f64,
+    carrier_freq: f64,
+    taps: Vec<MultipathTap>,
+    // (遅延サンプル数, x[n-d] の係数, x[n-d-1] の係数)
+    coeffs: Vec<(usize, f64, f64)>,
+    // 直前のブロックの末尾 (最大遅延 + 1 サンプル)
+    history: Vec<f64>,
+    work: Vec<f64>,
+}
+impl Multipath {
+    pub fn new(sample_rate: f64, carrier_freq: f64, taps: &[MultipathTap]) -> Self {
+        let mut multipath = Self {
+            sample_rate,
+            carrier_freq,
+            taps: Vec::new(),
+            coeffs: Vec::new(),
+            history: Vec::new(),
+            work: Vec::new(),
+        };
+        multipath.set_taps(taps);
+        multipath
+    }
+    /// タップを変更する。遅延線の中身は残すので、処理中に変えても途切れない
+    pub fn set_taps(&mut self, taps: &[MultipathTap]) {
+        self.taps = taps.to_vec();
+        self.update_coeffs();
+        let len = self
+            .coeffs
+            .iter()
+            .map(|&(d, _, _)| d + 1)
+            .max()
+            .unwrap_or(0);
+        if len > self.history.len() {
+            let mut history = vec![0.; len - self.history.len()];
+            history.extend_from_slice(&self.history);
+            self.history = history;
+        } else {
+            self.history.drain(..self.history.len() - len);
+        }
+    }
+    /// 遅延による搬送波の位相回転の補正に使う
+    pub fn set_carrier_freq(&mut self, freq: f64) {
+        self.carrier_freq = freq;
+        self.update_coeffs();
+    }
+    pub fn is_bypassed(&self) -> bool {
+        self.coeffs.is_empty()
+    }
+    pub fn reset(&mut self) {
+        self.history.fill(0.);
+    }
+    // 搬送波 ω0 付近の狭帯域信号では直交成分を隣り合う 2 サンプルで近似できるので、
+    // 位相 ψ の回転 x cos ψ - x̂ sin ψ は隣り合う 2 サンプルの重み付け和になる
+    fn update_coeffs(&mut self) {
+        let w0 = TAU * self.carrier_freq / self.sample_rate;
+        let quadrature = Quadrature::new(self.sample_rate, self.carrier_freq);
+        self.coeffs = self
+            .taps
+            .iter()
+            .map(|tap| {
+                let delay = (tap.delay * 1e-6 * self.sample_rate).round() as usize;
+                // 遅延 d による搬送波の回転 -ω0 d を打ち消して、指定の位相にする
+                let psi = tap.phase + w0 * delay as f64;
+                let (a, b) = quadrature.weights(tap.gain * psi.cos(), tap.gain * psi.sin());
+                (delay, a, b)
+            })
+            .collect();
+    }
+    pub fn process(&mut self, signal: &mut [f64]) {
+        if self.is_bypassed() {
+            return;
+        }
+        let offset = self.history.len();
+        self.work.clear();
+        self.work.extend_from_slice(&self.history);
+        self.work.extend_from_slice(signal);
+        for (i, v) in signal.iter_mut().enumerate() {
+            let n = offset + i;
+            *v = self
+                .coeffs
+                .iter()
+                .map(|&(d, a, b)| a * self.work[n - d] + b * self.work[n - d - 1])
+                .sum();
+        }
+        self.history
+            .copy_from_slice(&self.work[self.work.len() - offset..]);
+    }
+}
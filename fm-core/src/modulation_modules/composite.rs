@@ -1,7 +1,10 @@
 /**
  * コンポジット信号を作成、復元するコード群
 */
+use super::audio_processor::{AudioProcessor, AudioProcessorConfig};
 use super::filter::{Emphasis, FilterInfo, Hpf, Lpf, Notch};
+use super::mpx::{MpxConfig, MpxMeter, MpxStage};
+use super::rds::{RdsConfig, RdsDecoder, RdsEncoder, RdsStatus};
 use std::f64::consts::TAU;
 
 use super::filter::Deemphasis;
@@ -11,24 +14,80 @@ pub struct CompositeSignal {
     t: f64,
     filter_info: [FilterInfo; 4],
     emphasis: Emphasis,
+    pilot_level: f64,
+    audio_level: f64,
+    rds: Option<RdsEncoder>,
+    processor: Option<AudioProcessor>,
+    mpx: MpxStage,
 }
 impl CompositeSignal {
     const PILOT_FREQ: f64 = 19_000.;
     const CARRIER_FREQ: f64 = Self::PILOT_FREQ * 2.;
     const CUT_OFF_FREQ: f64 = 15_000f64;
-    pub const DEFAULT_SAMPLE_RATE: f64 = (Self::CARRIER_FREQ + Self::CUT_OFF_FREQ) * 3.;
-    pub fn new(f: f64) -> Self {
+    /// コンポジット信号に含まれる最大の周波数 (L-R の上側波帯の上端)
+    pub const MAX_FREQ: f64 = Self::CARRIER_FREQ + Self::CUT_OFF_FREQ;
+    pub const DEFAULT_SAMPLE_RATE: f64 = Self::MAX_FREQ * 3.;
+    /// pilot_level: 振幅 1 (最大周波数偏移) に対するパイロット信号の振幅
+    pub fn new(f: f64, pilot_level: f64, emphasis_tau: f64) -> Self {
         Self {
             lpf: Lpf::new(f, Self::CUT_OFF_FREQ, Lpf::Q),
             sample_rate: f,
             filter_info: [FilterInfo::default(); 4],
             t: 0.,
-            emphasis: Emphasis::new(f, 50.),
+            emphasis: Emphasis::new(f, emphasis_tau),
+            pilot_level,
+            audio_level: Self::audio_level(pilot_level),
+            rds: None,
+            processor: None,
+            mpx: MpxStage::new(f, 1., &MpxConfig::default()),
         }
     }
+    /// L+R と L-R の振幅。|L|,|R| <= 1 のとき、副搬送波 (パイロット・RDS) と合わせて振幅 1 に収まる
+    /// subcarrier_level: パイロットと RDS の振幅の和
+    pub fn audio_level(subcarrier_level: f64) -> f64 {
+        (1. - subcarrier_level) / 2.
+    }
+    /// RDS を変更する (None: 送らない)。局の情報だけを変えた場合はビットの送信を途切れさせない
+    pub fn set_rds(&mut self, rds: Option<&RdsConfig>) {
+        match (&mut self.rds, rds) {
+            (Some(encoder), Some(config)) => encoder.set_config(config),
+            (_, config) => self.rds = config.map(RdsEncoder::new),
+        }
+        let injection = self.rds.as_ref().map_or(0., |rds| rds.injection());
+        self.audio_level = Self::audio_level(self.pilot_level + injection);
+    }
+    /// 音声プロセッサを変更する (None: 掛けない)。有効・無効を切り替えると遅延が変わる
+    pub fn set_audio_processor(&mut self, config: Option<&AudioProcessorConfig>) {
+        match (&mut self.processor, config) {
+            (Some(processor), Some(config)) => processor.set_config(config),
+            (_, config) => {
+                self.processor = config.map(|config| AudioProcessor::new(self.sample_rate, config))
+            }
+        }
+    }
+    /// MPX のクリッパ・電力の制限を変更する。deviation: 振幅 1 に対応する周波数偏移 [Hz]
+    pub fn set_mpx(&mut self, config: &MpxConfig, deviation: f64) {
+        self.mpx.set_config(config, deviation);
+    }
+    /// 直近の最大周波数偏移と MPX 電力
+    pub fn mpx_meter(&self) -> MpxMeter {
+        self.mpx.meter()
+    }
     pub fn sample_rate(&self) -> f64 {
         self.sample_rate
     }
+    /// フィルタの状態とパイロットの位相を初期値に戻す
+    pub fn reset(&mut self) {
+        self.t = 0.;
+        self.filter_info = Default::default();
+        if let Some(rds) = &mut self.rds {
+            rds.reset();
+        }
+        if let Some(processor) = &mut self.processor {
+            processor.reset();
+        }
+        self.mpx.reset();
+    }
     pub fn process(&mut self, l_channel: &[f64], r_channel: &[f64], buffer: &mut [f64]) {
         for i in 0..l_channel.len() {
             // Low Pass
@@ -38,6 +97,11 @@ impl CompositeSignal {
             let r = self
                 .lpf
                 .process_without_buffer(r_channel[i], &mut self.filter_info[1]);
+            // AGC・コンプレッサ
+            let (l, r) = match &mut self.processor {
+                Some(processor) => processor.compress(l, r),
+                None => (l, r),
+            };
             // Pre-Emphasis
             let l = self
                 .emphasis
@@ -45,13 +109,28 @@ impl CompositeSignal {
             let r = self
                 .emphasis
                 .process_without_buffer(r, &mut self.filter_info[3]);
+            // プリエンファシス後のピークを抑える
+            let (l, r) = match &mut self.processor {
+                Some(processor) => processor.limit(l, r),
+                None => (l, r),
+            };
             // Convert to Composite Signal
             let a = l + r;
             let theta = TAU * Self::PILOT_FREQ * self.t;
             let cos = theta.cos();
             let double_sin = cos * theta.sin() * 2.;
             let b = (l - r) * double_sin;
-            buffer[i] = (a + b + cos) / 3.;
+            let mut subcarrier = cos * self.pilot_level;
+            // RDS の副搬送波はパイロットの 3 倍の周波数で同相
+            if let Some(rds) = &mut self.rds {
+                subcarrier += rds.process(self.t) * (3. * theta).cos() * rds.injection();
+            }
+            // 音声は副搬送波の残りの範囲 (2 * audio_level) に収める
+            buffer[i] = self.mpx.process(
+                (a + b) * self.audio_level,
+                2. * self.audio_level,
+                subcarrier,
+            );
             self.t += 1. / self.sample_rate;
         }
         // self.t = self.t.rem_euclid(1.);
@@ -67,22 +146,51 @@ pub struct RestoreSignal {
     t: f64,
     filter_info: [FilterInfo; 8],
     de_emphasis_info: [FilterInfo; 2],
+    input_gain: f64,
+    gain: f64,
+    rds: Option<RdsDecoder>,
 }
 impl RestoreSignal {
     const PILOT_FREQ: f64 = 19_000f64;
     const CARRIER_FREQ: f64 = Self::PILOT_FREQ * 2.;
     const CUT_OFF_FREQ: f64 = 15_000f64;
-    pub fn new(f: f64) -> Self {
+    /// input_gain: 復調信号をコンポジット信号の振幅に戻すための係数
+    /// subcarrier_level: 送信側のパイロットと RDS の振幅の和
+    pub fn new(f: f64, subcarrier_level: f64, emphasis_tau: f64, input_gain: f64) -> Self {
         Self {
             input_filter: Lpf::new(f, Self::CARRIER_FREQ + Self::CUT_OFF_FREQ, Lpf::Q),
             lpf16: Lpf::new(f, 16_000f64, Lpf::Q),
             hpf: Hpf::new(f, Self::PILOT_FREQ, Hpf::Q),
             notch: Notch::new(f, Self::PILOT_FREQ, Notch::BW),
-            de_emphasis: Deemphasis::new(f, 50.),
+            de_emphasis: Deemphasis::new(f, emphasis_tau),
             sample_rate: f,
             t: 0.,
             filter_info: [FilterInfo::default(); 8],
             de_emphasis_info: [FilterInfo::default(); 2],
+            input_gain,
+            gain: input_gain / CompositeSignal::audio_level(subcarrier_level),
+            rds: None,
+        }
+    }
+    /// 送信側の副搬送波の振幅が変わったときに、音声の利得を合わせる
+    pub fn set_subcarrier_level(&mut self, subcarrier_level: f64) {
+        self.gain = self.input_gain / CompositeSignal::audio_level(subcarrier_level);
+    }
+    /// RDS のデコーダを有効・無効にする。有効にした時点から同期をやり直す
+    pub fn set_rds_decoder(&mut self, enabled: bool) {
+        self.rds = enabled.then(|| RdsDecoder::new(self.sample_rate));
+    }
+    /// RDS のデコーダが有効な場合は、受信した内容と誤りの統計
+    pub fn rds_status(&self) -> Option<&RdsStatus> {
+        self.rds.as_ref().map(RdsDecoder::status)
+    }
+    /// フィルタの状態とキャリアの位相を初期値に戻す
+    pub fn reset(&mut self) {
+        self.t = 0.;
+        self.filter_info = Default::default();
+        self.de_emphasis_info = Default::default();
+        if let Some(rds) = &mut self.rds {
+            rds.reset();
         }
     }
     pub fn process(&mut self, signal: &[f64], l_buffer: &mut [f64], r_buffer: &mut [f64]) {
@@ -90,6 +198,10 @@ impl RestoreSignal {
         // println!("Restore HPF-Coeff: {:?}",self.hpf);
         // println!("Restore NOTCH-Coeff: {:?}",self.notch);
         for i in 0..signal.len() {
+            // RDS は入力の LPF で落とされる前の信号から取り出す
+            if let Some(rds) = &mut self.rds {
+                rds.process(signal[i]);
+            }
             let sig = self
                 .input_filter
                 .process_without_buffer(signal[i], &mut self.filter_info[6]);
@@ -133,8 +245,8 @@ impl RestoreSignal {
             let r = self
                 .de_emphasis
                 .process_without_buffer(r, &mut self.de_emphasis_info[1]);
-            l_buffer[i] = l * 3. * 4.;
-            r_buffer[i] = r * 3. * 4.;
+            l_buffer[i] = l * self.gain;
+            r_buffer[i] = r * self.gain;
             self.t += 1. / self.sample_rate;
         }
         // self.t = self.t.rem_euclid(1.);
@@ -1,14 +1,14 @@
-use std::f64::consts::{FRAC_1_SQRT_2, TAU};
+use std::f64::consts::{FRAC_1_SQRT_2, PI, TAU};
 pub type FilterInfo = [f64; 4];
 
 #[repr(C)]
 #[derive(Debug, Default)]
 pub struct Lpf {
-    c0: f64,
-    c1: f64,
-    c2: f64,
-    c3: f64,
-    c4: f64,
+    pub(crate) c0: f64,
+    pub(crate) c1: f64,
+    pub(crate) c2: f64,
+    pub(crate) c3: f64,
+    pub(crate) c4: f64,
 }
 impl Lpf {
     pub const Q: f64 = FRAC_1_SQRT_2;
@@ -133,16 +133,16 @@ impl Hpf {
 #[repr(C)]
 #[derive(Default)]
 pub struct Bpf {
-    c0: f64,
-    c1: f64,
-    c2: f64,
-    c3: f64,
-    c4: f64,
+    pub(crate) c0: f64,
+    pub(crate) c1: f64,
+    pub(crate) c2: f64,
+    pub(crate) c3: f64,
+    pub(crate) c4: f64,
 }
 impl Bpf {
     pub const Q: f64 = FRAC_1_SQRT_2;
     pub fn new(sample_rate: f64, cut_off: f64, bw: f64) -> Bpf {
-        let omega = TAU * dbg!(cut_off) / dbg!(sample_rate);
+        let omega = TAU * cut_off / sample_rate;
         let alpha = (omega).sin() * ((2f64).ln() / 2.0 * bw * omega / omega.sin()).sinh();
 
         let a0 = 1.0 + alpha;
@@ -152,13 +152,20 @@ impl Bpf {
         let b1 = 0.0;
         let b2 = -alpha;
         Self {
-            c0: dbg!(b0 / a0),
-            c1: dbg!(b1 / a0),
-            c2: dbg!(b2 / a0),
-            c3: dbg!(a1 / a0),
-            c4: dbg!(a2 / a0),
+            c0: b0 / a0,
+            c1: b1 / a0,
+            c2: b2 / a0,
+            c3: a1 / a0,
+            c4: a2 / a0,
         }
     }
+    pub fn process_without_buffer(&self, signal: f64, info: &mut FilterInfo) -> f64 {
+        let [in1, in2, out1, out2] = info;
+        let buf =
+            self.c0 * signal + self.c1 * *in1 + self.c2 * *in2 - self.c3 * *out1 - self.c4 * *out2;
+        *info = [signal, *in1, buf, *out1];
+        buf
+    }
     // pub fn process(&mut self, signal: &mut [f64]) {
     //     for i in 0..signal.len() {
     //         signal[i] = self.process_without_buffer(signal[i]);
@@ -240,47 +247,76 @@ impl Notch {
         buf
     }
 }
+/// 1 次のフィルタ (1 + s/ωz) / (1 + s/ωp) の係数 [b0, b1, a1] (a0 = 1)。
+/// 双一次変換の周波数のずれは、零点・極それぞれの周波数でプリワープして打ち消す
+/// zero, pole: 周波数 [Hz] (None: その因子を持たない)
+fn first_order_shelf(sample_rate: f64, zero: Option<f64>, pole: Option<f64>) -> [f64; 3] {
+    // (1 + s/ω) -> ((1 + k) + (1 - k) z^-1) / (1 + z^-1), k = 2fs / ω'
+    let factor = |freq: Option<f64>| match freq {
+        Some(freq) => {
+            let k = 1. / (PI * freq / sample_rate).tan();
+            [1. + k, 1. - k]
+        }
+        None => [1., 1.],
+    };
+    let [n0, n1] = factor(zero);
+    let [d0, d1] = factor(pole);
+    [n0 / d0, n1 / d0, d1 / d0]
+}
+/// 時定数 tau [μs] の角周波数に対応する周波数 [Hz]
+fn corner_freq(tau: f64) -> f64 {
+    1. / (TAU * tau * 1e-6)
+}
+
+/// プリエンファシス (1 + sτ)。
+/// そのままではナイキスト周波数で利得が発散するので、コンポジット信号の帯域の上端で持ち上げを止める
 pub struct Emphasis {
-    a0: f64,
-    a1: f64,
     b0: f64,
+    b1: f64,
+    a1: f64,
 }
 impl Emphasis {
+    /// 持ち上げを止める周波数 [Hz]。ディエンファシスでは打ち消さないので、15kHz で 0.2dB (192kHz の場合) 下がる
+    pub const LIMIT_FREQ: f64 = 53_000.;
+    /// tau: 時定数 [μs] (0: エンファシスなし)
     pub fn new(sample_rate: f64, tau: f64) -> Self {
-        let sample_rate = sample_rate / 1000.;
-        let coeff = sample_rate / (sample_rate + 2. * tau);
-        let coeff_rev = 1. / coeff;
-        Self {
-            a0: coeff_rev,
-            a1: -coeff_rev * (2. * tau - sample_rate) / (2. * tau + sample_rate),
-            b0: 1.,
-        }
+        let [b0, b1, a1] = if tau > 0. {
+            first_order_shelf(
+                sample_rate,
+                Some(corner_freq(tau)),
+                Some(Self::LIMIT_FREQ.min(sample_rate * 0.45)),
+            )
+        } else {
+            [1., 0., 0.]
+        };
+        Self { b0, b1, a1 }
     }
     pub fn process_without_buffer(&self, signal: f64, info: &mut FilterInfo) -> f64 {
         let [in1, _, out1, _] = info;
-        let buf = self.a0 * signal + self.a1 * *in1 - self.b0 * *out1;
+        let buf = self.b0 * signal + self.b1 * *in1 - self.a1 * *out1;
         *info = [signal, *in1, buf, *out1];
         buf
     }
 }
+/// ディエンファシス 1 / (1 + sτ)
 pub struct Deemphasis {
-    a0: f64,
-    a1: f64,
     b0: f64,
+    b1: f64,
+    a1: f64,
 }
 impl Deemphasis {
+    /// tau: 時定数 [μs] (0: ディエンファシスなし)
     pub fn new(sample_rate: f64, tau: f64) -> Self {
-        let sample_rate = sample_rate / 1000.;
-        let coeff = sample_rate / (sample_rate + 2. * tau);
-        Self {
-            a0: coeff,
-            a1: coeff,
-            b0: (2. * tau - sample_rate) / (2. * tau + sample_rate),
-        }
+        let [b0, b1, a1] = if tau > 0. {
+            first_order_shelf(sample_rate, None, Some(corner_freq(tau)))
+        } else {
+            [1., 0., 0.]
+        };
+        Self { b0, b1, a1 }
     }
     pub fn process_without_buffer(&self, signal: f64, info: &mut FilterInfo) -> f64 {
         let [in1, _, out1, _] = info;
-        let buf = self.a0 * signal + self.a1 * *in1 + self.b0 * *out1;
+        let buf = self.b0 * signal + self.b1 * *in1 - self.a1 * *out1;
         *info = [signal, *in1, buf, *out1];
         buf
     }
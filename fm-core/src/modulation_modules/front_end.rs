@@ -0,0 +1,167 @@
+/**
+ * 受信機の RF 増幅器・ミキサーの非線形性
+ * 中間周波数への変換の前に、RF の信号 (希望局・妨害局・雑音の和) を無記憶の非線形関数に通す。
+ * 強い妨害局があると、相互変調の積が中間周波数帯の通過帯域に入る。
+*/
+use crate::config::ConfigError;
+use std::f64::consts::TAU;
+
+/// 3 次の歪み (利得の圧縮) のモデル
+/// レベルはすべて希望局の搬送波電力を 0dB とした入力換算値 [dB]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Nonlinearity {
+    /// y = x + a3 x³ (a3 < 0)。a3 は IIP3 から決め、P1dB は IIP3 - 9.6dB になる
+    /// 出力が極大になる入力より大きい所では極大値で頭打ちにする
+    Polynomial { iip3: f64 },
+    /// y = L tanh(x / L)。L は P1dB から決める (IIP3 は P1dB + 約 9dB)
+    /// 高次の歪みも出るので、強い入力で飽和する様子に近い
+    Tanh { p1db: f64 },
+}
+
+/// 受信機のフロントエンドの設定
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrontEndConfig {
+    pub nonlinearity: Nonlinearity,
+    /// 2 次の IIP2 [dB] (None: 2 次の歪みなし)
+    pub iip2: Option<f64>,
+}
+impl FrontEndConfig {
+    pub fn polynomial(iip3: f64) -> Self {
+        Self {
+            nonlinearity: Nonlinearity::Polynomial { iip3 },
+            iip2: None,
+        }
+    }
+    pub fn tanh(p1db: f64) -> Self {
+        Self {
+            nonlinearity: Nonlinearity::Tanh { p1db },
+            iip2: None,
+        }
+    }
+    /// 入力換算の 3 次インターセプトポイント [dB]
+    pub fn iip3(&self) -> f64 {
+        match self.nonlinearity {
+            Nonlinearity::Polynomial { iip3 } => iip3,
+            // tanh(u) = u - u³/3 + ... より a3 = -1 / (3 L²)
+            Nonlinearity::Tanh { p1db } => db(2. * tanh_limit(p1db)),
+        }
+    }
+    /// 入力換算の 1dB 利得圧縮点 [dB]
+    pub fn p1db(&self) -> f64 {
+        match self.nonlinearity {
+            Nonlinearity::Polynomial { iip3 } => {
+                // 振幅 A の基本波の利得は 1 + 3/4 a3 A²
+                let a3 = cubic_coeff(iip3);
+                db(((10f64.powf(-1. / 20.) - 1.) / (0.75 * a3)).sqrt())
+            }
+            Nonlinearity::Tanh { p1db } => p1db,
+        }
+    }
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        let (name, value) = match self.nonlinearity {
+            Nonlinearity::Polynomial { iip3 } => ("front_end_iip3", iip3),
+            Nonlinearity::Tanh { p1db } => ("front_end_p1db", p1db),
+        };
+        for (name, value) in [(name, value), ("front_end_iip2", self.iip2.unwrap_or(0.))] {
+            if !value.is_finite() {
+                return Err(ConfigError::NotFinite { name, value });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 電力 [dB] (搬送波 = 振幅 1 の cos が 0dB) の正弦波の振幅
+fn amplitude(level: f64) -> f64 {
+    10f64.powf(level / 20.)
+}
+fn db(amplitude: f64) -> f64 {
+    20. * amplitude.log10()
+}
+/// 2 波の相互変調の積が基本波と等しくなる振幅 A = sqrt(4 / (3 |a3|)) から a3 を決める
+fn cubic_coeff(iip3: f64) -> f64 {
+    -4. / (3. * amplitude(iip3).powi(2))
+}
+/// P1dB が p1db になる tanh の飽和レベル L
+fn tanh_limit(p1db: f64) -> f64 {
+    // 振幅 r の正弦波を tanh に通したときの基本波の利得 (記述関数)
+    let gain = |r: f64| {
+        const POINTS: usize = 256;
+        (0..POINTS)
+            .map(|k| {
+                let cos = (TAU * k as f64 / POINTS as f64).cos();
+                (r * cos).tanh() * cos
+            })
+            .sum::<f64>()
+            * 2.
+            / (POINTS as f64 * r)
+    };
+    // 利得は r について単調に減るので二分法で -1dB になる r を探す
+    let target = 10f64.powf(-1. / 20.);
+    let (mut low, mut high) = (1e-3, 10.);
+    for _ in 0..60 {
+        let mid = (low + high) / 2.;
+        if gain(mid) > target {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    amplitude(p1db) / low
+}
+
+pub struct FrontEnd {
+    config: Option<FrontEndConfig>,
+    // 2 次の係数
+    a2: f64,
+    // Polynomial: (a3, 頭打ちにする入力の振幅), Tanh: (L, 0)
+    shape: (f64, f64),
+}
+impl FrontEnd {
+    pub fn new(config: Option<FrontEndConfig>) -> Self {
+        let mut front_end = Self {
+            config: None,
+            a2: 0.,
+            shape: (0., 0.),
+        };
+        front_end.set_config(config);
+        front_end
+    }
+    pub fn set_config(&mut self, config: Option<FrontEndConfig>) {
+        self.config = config;
+        let Some(config) = config else {
+            return;
+        };
+        // 2 波の差の周波数の積の振幅は a2 A² なので、A = 1 / a2 で基本波と等しくなる
+        self.a2 = config.iip2.map_or(0., |iip2| 1. / amplitude(iip2));
+        self.shape = match config.nonlinearity {
+            Nonlinearity::Polynomial { iip3 } => {
+                let a3 = cubic_coeff(iip3);
+                (a3, (-1. / (3. * a3)).sqrt())
+            }
+            Nonlinearity::Tanh { p1db } => (tanh_limit(p1db), 0.),
+        };
+    }
+    /// 高調波は RF のサンプルレートで折り返すが、既定の周波数では中間周波数帯には入らない
+    pub fn process(&mut self, signal: &mut [f64]) {
+        let Some(config) = self.config else {
+            return;
+        };
+        let a2 = self.a2;
+        match config.nonlinearity {
+            Nonlinearity::Polynomial { .. } => {
+                let (a3, peak) = self.shape;
+                for v in signal.iter_mut() {
+                    let x = v.clamp(-peak, peak);
+                    *v = x + a3 * x * x * x + a2 * *v * *v;
+                }
+            }
+            Nonlinearity::Tanh { .. } => {
+                let limit = self.shape.0;
+                for v in signal.iter_mut() {
+                    *v = limit * (*v / limit).tanh() + a2 * *v * *v;
+                }
+            }
+        }
+    }
+}
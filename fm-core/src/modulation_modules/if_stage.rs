@@ -0,0 +1,146 @@
+/**
+ * 復調前の中間周波数段 (AGC・リミッタ)
+ * 復調器の出力は中間周波数の信号の振幅の 2 乗に比例するので、伝搬路で減衰すると音量が変わる。
+ * AGC で包絡線を 1 に揃え、リミッタで振幅の変動 (AM 成分) を取り除く。
+*/
+use super::quadrature::Quadrature;
+use crate::config::ConfigError;
+use std::f64::consts::FRAC_PI_4;
+
+/// 中間周波数段の AGC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Agc {
+    /// 入力が大きくなったときに利得を下げる時定数 [ms]
+    pub attack: f64,
+    /// 入力が小さくなったときに利得を上げる時定数 [ms]
+    pub decay: f64,
+    /// 利得の上限 [dB] (雑音だけのときに増幅しすぎないようにする)
+    pub max_gain: f64,
+}
+impl Agc {
+    pub fn new(attack: f64, decay: f64) -> Self {
+        Self {
+            attack,
+            decay,
+            max_gain: 60.,
+        }
+    }
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        for (name, value) in [("agc_attack", self.attack), ("agc_decay", self.decay)] {
+            if !(value > 0. && value.is_finite()) {
+                return Err(ConfigError::NotPositive { name, value });
+            }
+        }
+        if !self.max_gain.is_finite() {
+            return Err(ConfigError::NotFinite {
+                name: "agc_max_gain",
+                value: self.max_gain,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// 中間周波数段のリミッタ。出力の基本波の振幅が 1 (搬送波と同じ) になるように揃える
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limiter {
+    /// 符号だけを残す (出力は ±π/4 の矩形波)
+    Hard,
+    /// π/4 tanh(x / A)。A は電力 threshold [dB] (搬送波を 0dB) の正弦波の振幅で、
+    /// それより十分小さい入力はそのまま比例して通る
+    Soft { threshold: f64 },
+}
+impl Limiter {
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        match *self {
+            Self::Hard => Ok(()),
+            Self::Soft { threshold } if threshold.is_finite() => Ok(()),
+            Self::Soft { threshold } => Err(ConfigError::NotFinite {
+                name: "limiter_threshold",
+                value: threshold,
+            }),
+        }
+    }
+}
+
+pub struct IfStage {
+    sample_rate: f64,
+    // 包絡線の検出に使う直交成分
+    quadrature: Quadrature,
+    agc: Option<Agc>,
+    // (attack, decay) の 1 次の平滑化の係数
+    agc_coeffs: (f64, f64),
+    max_gain: f64,
+    limiter: Option<Limiter>,
+    // 包絡線の平滑値
+    level: f64,
+}
+impl IfStage {
+    /// intermediate_freq: 復調器に入る中間周波数
+    pub fn new(
+        sample_rate: f64,
+        intermediate_freq: f64,
+        agc: Option<Agc>,
+        limiter: Option<Limiter>,
+    ) -> Self {
+        let mut stage = Self {
+            sample_rate,
+            quadrature: Quadrature::new(sample_rate, intermediate_freq),
+            agc: None,
+            agc_coeffs: (0., 0.),
+            max_gain: 1.,
+            limiter,
+            level: 1.,
+        };
+        stage.set_agc(agc);
+        stage
+    }
+    /// AGC を変更する。検出した包絡線は引き継ぐ
+    pub fn set_agc(&mut self, agc: Option<Agc>) {
+        self.agc = agc;
+        if let Some(agc) = agc {
+            let coeff = |ms: f64| (-1. / (ms * 1e-3 * self.sample_rate)).exp();
+            self.agc_coeffs = (coeff(agc.attack), coeff(agc.decay));
+            self.max_gain = 10f64.powf(agc.max_gain / 20.);
+        }
+    }
+    pub fn set_limiter(&mut self, limiter: Option<Limiter>) {
+        self.limiter = limiter;
+    }
+    pub fn reset(&mut self) {
+        self.level = 1.;
+        self.quadrature.reset();
+    }
+    pub fn process(&mut self, signal: &mut [f64]) {
+        if self.agc.is_some() {
+            self.process_agc(signal);
+        }
+        match self.limiter {
+            None => {}
+            Some(Limiter::Hard) => {
+                for v in signal.iter_mut() {
+                    // 0 は雑音の無い無信号なのでそのまま
+                    *v = if *v == 0. { 0. } else { FRAC_PI_4.copysign(*v) };
+                }
+            }
+            Some(Limiter::Soft { threshold }) => {
+                let scale = 10f64.powf(threshold / 20.);
+                for v in signal.iter_mut() {
+                    *v = FRAC_PI_4 * (*v / scale).tanh();
+                }
+            }
+        }
+    }
+    /// 包絡線 |x + j x̂| を平滑化し、その逆数を掛ける
+    fn process_agc(&mut self, signal: &mut [f64]) {
+        let (attack, decay) = self.agc_coeffs;
+        for v in signal.iter_mut() {
+            let x = *v;
+            let quadrature = self.quadrature.process(x);
+            let envelope = (x * x + quadrature * quadrature).sqrt();
+            let coeff = if envelope > self.level { attack } else { decay };
+            self.level = coeff * self.level + (1. - coeff) * envelope;
+            *v = x * (1. / self.level).min(self.max_gain);
+        }
+    }
+}
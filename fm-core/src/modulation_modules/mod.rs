@@ -1,6 +1,16 @@
+pub mod adc;
+pub mod audio_processor;
+pub mod channel;
 pub mod composite;
 pub mod filter;
+pub mod front_end;
+pub mod if_stage;
 pub mod modulator;
+pub mod mpx;
+pub mod oscillator;
+pub mod quadrature;
+pub mod rds;
+pub mod superhet;
 #[inline]
 pub fn get_8x_sample_rate(fs1: usize, fs2: usize) -> usize {
     let tmp = (fs1 as f64 / fs2 as f64).ceil() as usize;
@@ -3,12 +3,14 @@ use std::f64::consts::TAU;
 
 // pub type SampleType = f32;
 use super::filter::{fast_filter, Bpf, FilterInfo, Lpf};
+use super::quadrature::Quadrature;
+use crate::kernels::{self, Backend};
 
 #[repr(C)]
 #[derive(Default)]
 pub struct CnvFiInfos {
-    angle: [f64; 4],
-    delta_angle: f64,
+    pub(crate) angle: [f64; 4],
+    pub(crate) delta_angle: f64,
     prev_sig: [f64; 4],
     prev_cos: [f64; 4],
     next_cos: [f64; 4],
@@ -28,25 +30,41 @@ impl CnvFiInfos {
             ..Default::default()
         }
     }
+    fn reset(&mut self) {
+        *self = Self {
+            filter_coeff: self.filter_coeff,
+            angle: retune_lanes([0.; 4], self.delta_angle),
+            delta_angle: self.delta_angle,
+            ..Default::default()
+        };
+    }
+}
+/// レーン 0 (次に処理するサンプル) の位相を保ったまま、レーン毎の位相を新しい刻みで作り直す
+fn retune_lanes(angle: [f64; 4], delta_angle: f64) -> [f64; 4] {
+    [0., 1., 2., 3.].map(|k| angle[0] + k * delta_angle)
 }
 pub struct CvtIntermediateFreq {
     fc1: f64,
     fc2: f64,
     sample_periodic: f64,
     info: CnvFiInfos,
+    backend: Backend,
+    // イメージ抑圧ミキサーの Q 側の利得 (None: 通常のミキサー) と、Q 側の移相器
+    image_gain: Option<f64>,
+    image_shifter: Quadrature,
 }
 
 #[repr(C)]
 #[derive(Default)]
 pub struct DemodulationInfo {
-    angle: [f64; 4],
-    prev_sin: [f64; 4],
-    prev_sig: [f64; 8],
-    prev_internal: [f64; 8],
-    filter_coeff: Lpf,
+    pub(crate) angle: [f64; 4],
+    pub(crate) prev_sin: [f64; 4],
+    pub(crate) prev_sig: [f64; 8],
+    pub(crate) prev_internal: [f64; 8],
+    pub(crate) filter_coeff: Lpf,
     // filter_info: [FilterInfo; 6],
     // filter_coeff: f64,
-    filter_info: [f64; 16],
+    pub(crate) filter_info: [f64; 16],
 }
 impl DemodulationInfo {
     pub fn new(fs: f64, fc: f64, cutoff: f64) -> Self {
@@ -58,6 +76,13 @@ impl DemodulationInfo {
             ..Default::default()
         }
     }
+    fn reset(&mut self, delta_angle: f64) {
+        self.angle = retune_lanes([0.; 4], delta_angle);
+        self.prev_sin = Default::default();
+        self.prev_sig = Default::default();
+        self.prev_internal = Default::default();
+        self.filter_info = Default::default();
+    }
 }
 
 impl CvtIntermediateFreq {
@@ -68,11 +93,50 @@ impl CvtIntermediateFreq {
             fc2,
             sample_periodic: 1. / fs,
             info: CnvFiInfos::new(fs * 2., 1. / fs * TAU * (dbg!(fc1 - fc2)), fc2 * 2.),
+            backend: Backend::detect(),
+            image_gain: None,
+            image_shifter: Quadrature::new(fs, fc2),
         }
     }
+    /// イメージ抑圧比 [dB] を指定する (None: 通常のミキサー)
+    /// I 側と Q 側の利得の不一致 g で抑圧比 (1 + g) / (1 - g) になるとしてモデル化する
+    pub fn set_image_rejection(&mut self, rejection: Option<f64>) {
+        self.image_gain = rejection.map(|rejection| {
+            let ratio = 10f64.powf(rejection / 20.);
+            (ratio - 1.) / (ratio + 1.)
+        });
+    }
+    /// 受信周波数 (局部発振 = fc1 - fc2) を位相連続で変更する
+    pub fn set_tuned_freq(&mut self, fc1: f64) {
+        self.fc1 = fc1;
+        self.info.delta_angle = self.sample_periodic * TAU * (fc1 - self.fc2);
+        self.info.angle = retune_lanes(self.info.angle, self.info.delta_angle);
+    }
+    /// 局部発振の周波数を直接指定する (発振器の周波数のずれ用)。位相は連続
+    pub fn set_local_freq(&mut self, freq: f64) {
+        self.set_tuned_freq(freq + self.fc2);
+    }
+    pub fn intermediate_freq(&self) -> f64 {
+        self.fc2
+    }
+    /// 局部発振の位相とフィルタの状態を初期値に戻す
+    pub fn reset(&mut self) {
+        self.info.reset();
+        self.image_shifter.reset();
+    }
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+    /// この CPU で実行できないバックエンドを選ぶと未定義動作になるので、ここで弾く
+    pub(crate) fn set_backend(&mut self, backend: Backend) {
+        assert!(backend.is_supported(), "unsupported backend: {backend}");
+        self.backend = backend;
+    }
     pub fn process(&mut self, input: &[f64], dst: &mut [f64]) {
+        let start_angle = self.info.angle[0];
         unsafe {
-            crate::convert_intermediate_freq(
+            kernels::convert_intermediate_freq(
+                self.backend,
                 dst.as_mut_ptr(),
                 input.as_ptr(),
                 // self.sample_periodic,
@@ -82,6 +146,20 @@ impl CvtIntermediateFreq {
                 input.len(),
             );
         }
+        if let Some(gain) = self.image_gain {
+            self.reject_image(gain, start_angle, input, dst);
+        }
+    }
+    /// Hartley 型のイメージ抑圧ミキサー: I + H{Q} で希望波は強め合い、イメージは打ち消し合う
+    /// (I = x cos θ はカーネルの出力、Q = x sin θ)。
+    /// H{Q} は中間周波数の付近の直交成分の近似 (`Quadrature`) で求める
+    fn reject_image(&mut self, gain: f64, start_angle: f64, input: &[f64], dst: &mut [f64]) {
+        let delta_angle = self.info.delta_angle;
+        for (n, (x, y)) in input.iter().zip(dst.iter_mut()).enumerate() {
+            let quadrature = x * (start_angle + n as f64 * delta_angle).sin();
+            let hilbert = self.image_shifter.process(quadrature);
+            *y = (*y + gain * hilbert) / (1. + gain);
+        }
     }
 }
 #[repr(C)]
@@ -89,12 +167,14 @@ impl CvtIntermediateFreq {
 pub struct Modulator {
     integral: [f64; 2], // int_{0}^{t} x(\tau) d\tau ( 符号拡張)
     // t: f64,        // 時刻t
-    t: [f64; 4], // 時刻t
+    pub(crate) t: [f64; 4], // 時刻t
     prev_sig: [f64; 8],
-    sample_period: f64,
-    carrier_freq: f64,
-    modulation_index: f64,
+    pub(crate) sample_period: f64,
+    pub(crate) carrier_freq: f64,
+    pub(crate) modulation_index: f64,
     prev_inter_sig: [f64; 4],
+    // C 側の ModulationInfo には含まれない
+    backend: Backend,
 }
 impl Modulator {
     // pub fn new() -> Self {
@@ -109,7 +189,8 @@ impl Modulator {
     //         // buffer: Vec::new(),
     //     }
     // }
-    pub fn from(f: f64, sample_rate: f64) -> Self {
+    /// deviation: 入力の振幅 1 に対する周波数偏移 [Hz]
+    pub fn from(f: f64, sample_rate: f64, deviation: f64) -> Self {
         let sample_period = 1. / sample_rate;
         Self {
             // integral: 0.0,
@@ -122,13 +203,35 @@ impl Modulator {
             ],
             // prev_sig: 0.0,
             // modulation_index: 2.,
-            modulation_index: 47. / 53.,
+            // modulation_index: 47. / 53.,
+            modulation_index: TAU * deviation,
             // sample_rate,
             sample_period,
             carrier_freq: f,
+            backend: Backend::detect(),
             ..Default::default()
         }
     }
+    /// 搬送波周波数を位相連続で変更する
+    pub fn set_carrier_freq(&mut self, f: f64) {
+        self.carrier_freq = f;
+        self.t = retune_lanes(self.t, TAU * f * self.sample_period);
+    }
+    /// 積分値・搬送波の位相を初期値に戻す
+    pub fn reset(&mut self) {
+        self.integral = Default::default();
+        self.prev_sig = Default::default();
+        self.prev_inter_sig = Default::default();
+        self.t = retune_lanes([0.; 4], TAU * self.carrier_freq * self.sample_period);
+    }
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+    /// この CPU で実行できないバックエンドを選ぶと未定義動作になるので、ここで弾く
+    pub(crate) fn set_backend(&mut self, backend: Backend) {
+        assert!(backend.is_supported(), "unsupported backend: {backend}");
+        self.backend = backend;
+    }
     pub fn process(&mut self, signal: &[f64], buffer: &mut [f64]) {
         // for i in 0..signal.len() {
         //     self.integral += self.prev_sig + signal[i];
@@ -143,7 +246,8 @@ impl Modulator {
 
         // self.t[0] = self.t[0].rem_euclid(TAU);
         unsafe {
-            crate::fm_modulate(
+            kernels::fm_modulate(
+                self.backend,
                 buffer.as_mut_ptr(),
                 signal.as_ptr(),
                 buffer.len() as u64,
@@ -158,6 +262,7 @@ pub struct DeModulator {
     info: DemodulationInfo,
     sample_period: f64,
     carrier_freq: f64,
+    backend: Backend,
     // result_filter: Lpf,
     // filter_info: [FilterInfo; 4],
 }
@@ -173,13 +278,33 @@ impl DeModulator {
             // sample_rate,
             sample_period: (1. / sample_rate),
             carrier_freq: f,
+            backend: Backend::detect(),
             // result_filter: Lpf::new(sample_rate, cut_off, Lpf::Q),
             // filter_info: Default::default(),
         }
     }
+    /// 復調出力を変調前の振幅に戻すための係数
+    /// 出力は -(瞬時角周波数偏移) / 4 に、差分で作る cos の振幅と位相のずれ (sinδ/δ) が掛かる
+    pub fn output_gain(&self, deviation: f64) -> f64 {
+        let delta_angle = TAU * self.carrier_freq * self.sample_period;
+        -4. / (TAU * deviation) * delta_angle / delta_angle.sin()
+    }
+    pub fn reset(&mut self) {
+        self.info
+            .reset(TAU * self.carrier_freq * self.sample_period);
+    }
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+    /// この CPU で実行できないバックエンドを選ぶと未定義動作になるので、ここで弾く
+    pub(crate) fn set_backend(&mut self, backend: Backend) {
+        assert!(backend.is_supported(), "unsupported backend: {backend}");
+        self.backend = backend;
+    }
     pub fn process(&mut self, signal: &[f64], buffer: &mut [f64]) {
         unsafe {
-            crate::fm_demodulate(
+            kernels::fm_demodulate(
+                self.backend,
                 buffer.as_mut_ptr(),
                 signal.as_ptr(),
                 self.sample_period,
@@ -194,12 +319,14 @@ impl DeModulator {
 #[derive(Default)]
 #[repr(C)]
 pub struct BandPassFilter {
-    prev_sig: [f64; 2],
-    prev_prev_sig: [f64; 2],
-    prev_out: [f64; 2],
-    prev_prev_out: [f64; 2],
-    stage: [f64; 4],
-    filter_coeff: Bpf,
+    pub(crate) prev_sig: [f64; 2],
+    pub(crate) prev_prev_sig: [f64; 2],
+    pub(crate) prev_out: [f64; 2],
+    pub(crate) prev_prev_out: [f64; 2],
+    pub(crate) stage: [f64; 4],
+    pub(crate) filter_coeff: Bpf,
+    // C 側の FilteringInfo には含まれない
+    backend: Backend,
 }
 impl BandPassFilter {
     // const BAND_WIDTH: f64 = 0.2; // +- 124kHz when fc = 10.7MHz
@@ -207,12 +334,42 @@ impl BandPassFilter {
     pub fn new(fs: f64, cutoff: f64) -> Self {
         Self {
             filter_coeff: Bpf::new(fs, cutoff, Self::BAND_WIDTH),
+            backend: Backend::detect(),
             ..Default::default()
         }
     }
+    /// 帯域幅を Hz で指定する (中心周波数 cutoff に対して幾何対称な帯域)
+    pub fn with_bandwidth(fs: f64, cutoff: f64, bandwidth: f64) -> Self {
+        Self {
+            filter_coeff: Bpf::new(fs, cutoff, Self::bandwidth_octave(cutoff, bandwidth)),
+            backend: Backend::detect(),
+            ..Default::default()
+        }
+    }
+    pub fn reset(&mut self) {
+        self.prev_sig = Default::default();
+        self.prev_prev_sig = Default::default();
+        self.prev_out = Default::default();
+        self.prev_prev_out = Default::default();
+        self.stage = Default::default();
+    }
+    pub(crate) fn bandwidth_octave(center: f64, bandwidth: f64) -> f64 {
+        // f_high - f_low = bandwidth, f_high * f_low = center^2
+        let f_low = ((bandwidth * bandwidth + 4. * center * center).sqrt() - bandwidth) / 2.;
+        ((f_low + bandwidth) / f_low).log2()
+    }
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+    /// この CPU で実行できないバックエンドを選ぶと未定義動作になるので、ここで弾く
+    pub(crate) fn set_backend(&mut self, backend: Backend) {
+        assert!(backend.is_supported(), "unsupported backend: {backend}");
+        self.backend = backend;
+    }
     pub fn process(&mut self, input: &[f64], dst: &mut [f64]) {
         unsafe {
-            crate::filtering_with_resample(
+            kernels::filtering_with_resample(
+                self.backend,
                 dst.as_mut_ptr(),
                 input.as_ptr(),
                 self as *mut Self,
@@ -222,7 +379,8 @@ impl BandPassFilter {
     }
     pub fn process_no_resample(&mut self, input: &[f64], dst: &mut [f64]) {
         unsafe {
-            crate::filtering(
+            kernels::filtering(
+                self.backend,
                 dst.as_mut_ptr(),
                 input.as_ptr(),
                 self as *mut Self,
@@ -0,0 +1,209 @@
+/**
+ * MPX (コンポジット信号) の処理と測定
+ * 音声の部分だけをクリップしてパイロット・RDS を守り、ITU-R BS.412 の MPX 電力
+ * (一定時間の平均電力。0dBr は ±19kHz の周波数偏移の正弦波の電力) を上限以下に抑える。
+ * 最大周波数偏移と MPX 電力は常に測定する。
+*/
+use super::filter::{FilterInfo, Notch};
+use crate::config::ConfigError;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MpxConfig {
+    /// 音声をクリップする前に掛ける利得 [dB]。None の場合はクリップしない
+    pub clipper_drive: Option<f64>,
+    /// MPX 電力の上限 [dBr]。None の場合は制限しない
+    pub power_limit: Option<f64>,
+    /// MPX 電力・最大周波数偏移を測定する時間 [s]
+    pub power_window: f64,
+}
+impl Default for MpxConfig {
+    fn default() -> Self {
+        Self {
+            clipper_drive: None,
+            power_limit: None,
+            power_window: Self::BS412_WINDOW,
+        }
+    }
+}
+impl MpxConfig {
+    pub const BS412_WINDOW: f64 = 60.;
+    /// クリッパと、+0dBr / 60s の MPX 電力の制限
+    pub fn bs412() -> Self {
+        Self {
+            clipper_drive: Some(0.),
+            power_limit: Some(0.),
+            power_window: Self::BS412_WINDOW,
+        }
+    }
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        for (name, value) in [
+            ("mpx_clipper_drive", self.clipper_drive),
+            ("mpx_power_limit", self.power_limit),
+        ] {
+            if let Some(value) = value.filter(|v| !v.is_finite()) {
+                return Err(ConfigError::NotFinite { name, value });
+            }
+        }
+        if !(self.power_window >= MpxStage::SEGMENT && self.power_window.is_finite()) {
+            return Err(ConfigError::NotPositive {
+                name: "mpx_power_window",
+                value: self.power_window,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// 直近 `power_window` 秒の測定値
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MpxMeter {
+    /// 最大周波数偏移 [Hz]
+    pub peak_deviation: f64,
+    /// MPX 電力 [dBr]
+    pub mpx_power: f64,
+    /// MPX 電力の制限で音声に掛けている利得 [dB]
+    pub limiter_gain: f64,
+    /// クリップした音声のサンプルの割合
+    pub clip_ratio: f64,
+}
+
+/// 測定の単位 (SEGMENT 秒) ごとの集計
+#[derive(Debug, Clone, Copy, Default)]
+struct Segment {
+    samples: usize,
+    // クリッパの後、電力の制限の前の音声の電力の和
+    audio_power: f64,
+    subcarrier_power: f64,
+    output_power: f64,
+    peak: f64,
+    clipped: usize,
+}
+
+pub struct MpxStage {
+    config: MpxConfig,
+    deviation: f64,
+    segment_len: usize,
+    // クリップの歪みからパイロットと RDS の周波数の成分を取り除くフィルタ
+    protection: [Notch; 2],
+    protection_info: [FilterInfo; 2],
+    segments: VecDeque<Segment>,
+    current: Segment,
+    // セグメントの先頭と終わりの利得 (その間は直線で変える)
+    gain_from: f64,
+    gain_to: f64,
+}
+impl MpxStage {
+    /// 測定と利得の更新の間隔 [s]
+    pub const SEGMENT: f64 = 0.1;
+    /// 0dBr の周波数偏移 [Hz]
+    const REFERENCE_DEVIATION: f64 = 19_000.;
+    const PILOT_FREQ: f64 = 19_000.;
+    const RDS_FREQ: f64 = 57_000.;
+    /// 歪みを取り除く帯域幅 [oct]。広くすると取り除いた後のピークが大きくなる
+    const PROTECTION_BW: f64 = 0.05;
+    /// deviation: コンポジット信号の振幅 1 に対応する周波数偏移 [Hz]
+    pub fn new(sample_rate: f64, deviation: f64, config: &MpxConfig) -> Self {
+        Self {
+            config: *config,
+            deviation,
+            segment_len: (sample_rate * Self::SEGMENT).round() as usize,
+            protection: [
+                Notch::new(sample_rate, Self::PILOT_FREQ, Self::PROTECTION_BW),
+                Notch::new(sample_rate, Self::RDS_FREQ, Self::PROTECTION_BW),
+            ],
+            protection_info: [FilterInfo::default(); 2],
+            segments: VecDeque::new(),
+            current: Segment::default(),
+            gain_from: 1.,
+            gain_to: 1.,
+        }
+    }
+    /// 設定を変更する。測定値は引き継ぐ
+    pub fn set_config(&mut self, config: &MpxConfig, deviation: f64) {
+        self.config = *config;
+        self.deviation = deviation;
+        while self.segments.len() > self.window_len() {
+            self.segments.pop_front();
+        }
+        if config.power_limit.is_none() {
+            (self.gain_from, self.gain_to) = (1., 1.);
+        }
+    }
+    pub fn reset(&mut self) {
+        self.protection_info = Default::default();
+        self.segments.clear();
+        self.current = Segment::default();
+        (self.gain_from, self.gain_to) = (1., 1.);
+    }
+    fn window_len(&self) -> usize {
+        ((self.config.power_window / Self::SEGMENT).round() as usize).max(1)
+    }
+    /// 0dBr の電力 (コンポジット信号の振幅 1 が `deviation`)
+    fn reference_power(&self) -> f64 {
+        let amplitude = Self::REFERENCE_DEVIATION / self.deviation;
+        amplitude * amplitude / 2.
+    }
+    /// audio: 音声の部分 (L+R, L-R)、ceiling: 音声に許される最大の振幅、subcarrier: パイロットと RDS
+    pub fn process(&mut self, audio: f64, ceiling: f64, subcarrier: f64) -> f64 {
+        let mut audio = audio;
+        if let Some(drive) = self.config.clipper_drive {
+            audio *= 10f64.powf(drive / 20.);
+            if audio.abs() > ceiling {
+                self.current.clipped += 1;
+            }
+            // クリップで生じた歪み (クリップ後 - クリップ前) から、パイロット・RDS の周波数の成分を取り除く
+            let mut distortion = audio.clamp(-ceiling, ceiling) - audio;
+            for (notch, info) in self.protection.iter_mut().zip(&mut self.protection_info) {
+                distortion = notch.process_without_buffer(distortion, info);
+            }
+            audio += distortion;
+        }
+        let position = self.current.samples as f64 / self.segment_len as f64;
+        let gain = self.gain_from + (self.gain_to - self.gain_from) * position;
+        let output = audio * gain + subcarrier;
+        let segment = &mut self.current;
+        segment.samples += 1;
+        segment.audio_power += audio * audio;
+        segment.subcarrier_power += subcarrier * subcarrier;
+        segment.output_power += output * output;
+        segment.peak = segment.peak.max(output.abs());
+        if segment.samples == self.segment_len {
+            self.end_segment();
+        }
+        output
+    }
+    fn end_segment(&mut self) {
+        self.segments.push_back(std::mem::take(&mut self.current));
+        if self.segments.len() > self.window_len() {
+            self.segments.pop_front();
+        }
+        self.gain_from = self.gain_to;
+        if let Some(limit) = self.config.power_limit {
+            // 利得を一定とみなして、窓の平均電力がちょうど上限になる利得を求める
+            let samples = self.total(|s| s.samples as f64);
+            let audio = self.total(|s| s.audio_power) / samples;
+            let subcarrier = self.total(|s| s.subcarrier_power) / samples;
+            let available = self.reference_power() * 10f64.powf(limit / 10.) - subcarrier;
+            self.gain_to = if audio > 0. {
+                (available.max(0.) / audio).sqrt().min(1.)
+            } else {
+                1.
+            };
+        }
+    }
+    fn total(&self, f: impl Fn(&Segment) -> f64) -> f64 {
+        self.segments.iter().map(f).sum()
+    }
+    pub fn meter(&self) -> MpxMeter {
+        let samples = self.total(|s| s.samples as f64).max(1.);
+        MpxMeter {
+            peak_deviation: self.segments.iter().fold(0f64, |acc, s| acc.max(s.peak))
+                * self.deviation,
+            mpx_power: 10.
+                * (self.total(|s| s.output_power) / samples / self.reference_power()).log10(),
+            limiter_gain: 20. * self.gain_from.log10(),
+            clip_ratio: self.total(|s| s.clipped as f64) / samples,
+        }
+    }
+}
@@ -0,0 +1,256 @@
+/**
+ * 発振器の不完全性 (周波数偏差・温度ドリフト・位相雑音)
+ * 送信機の搬送波と受信機の局部発振で共通に使う。
+ * 周波数のずれはブロック毎に位相連続で周波数を変えて与え、位相雑音は出力の位相を回して与える。
+*/
+use super::channel::Rng;
+use super::quadrature::Quadrature;
+use crate::config::ConfigError;
+use std::f64::consts::TAU;
+
+/// 送信機・受信機の位相雑音の乱数系列 (伝搬路の雑音とは別の系列にする)
+pub(crate) const TX_STREAM: u64 = 0x7478_5f6f_7363;
+pub(crate) const RX_STREAM: u64 = 0x7278_5f6f_7363;
+/// 位相雑音を計算し直す間隔 [サンプル] (間は線形補間する)
+/// 位相雑音はサンプルレート / (2 * UPDATE_INTERVAL) までの離調周波数で有効
+pub const UPDATE_INTERVAL: usize = 64;
+
+/// 電源投入後の温度ドリフト。周波数偏差が時定数 time_constant [s] で ppm だけ動く
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Drift {
+    pub ppm: f64,
+    pub time_constant: f64,
+}
+
+/// 発振器の設定 (既定では理想的な発振器)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct OscillatorConfig {
+    /// 周波数偏差 [ppm]
+    pub offset_ppm: f64,
+    pub drift: Option<Drift>,
+    /// 位相雑音のマスク (離調周波数 [Hz], SSB 位相雑音 [dBc/Hz])。離調周波数の昇順
+    /// 点の間は 1 次の雑音源の和で近似し、最後の点より外側は -20dB/dec で下がる
+    pub phase_noise: Vec<(f64, f64)>,
+}
+impl OscillatorConfig {
+    pub fn is_ideal(&self) -> bool {
+        self.offset_ppm == 0. && self.drift.is_none() && self.phase_noise.is_empty()
+    }
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        let mut finite = vec![("oscillator_offset", self.offset_ppm)];
+        if let Some(drift) = self.drift {
+            finite.push(("drift_ppm", drift.ppm));
+            if !(drift.time_constant > 0. && drift.time_constant.is_finite()) {
+                return Err(ConfigError::NotPositive {
+                    name: "drift_time_constant",
+                    value: drift.time_constant,
+                });
+            }
+        }
+        let name = "phase_noise_offset";
+        let mut prev = None;
+        for &(offset, level) in &self.phase_noise {
+            // 離調周波数は正で、昇順に並んでいる必要がある
+            if !(offset > 0. && offset.is_finite()) {
+                return Err(ConfigError::NotPositive {
+                    name,
+                    value: offset,
+                });
+            }
+            if prev.is_some_and(|prev| offset <= prev) {
+                return Err(ConfigError::NotAscending {
+                    name,
+                    value: offset,
+                });
+            }
+            prev = Some(offset);
+            finite.push(("phase_noise_level", level));
+        }
+        for (name, value) in finite {
+            if !value.is_finite() {
+                return Err(ConfigError::NotFinite { name, value });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// マスクに合わせた位相雑音 φ[n] を作る
+/// 1 次の LPF を通した白色雑音 (Lorentz 型のスペクトル) を、マスクの点毎に 1 つずつ足し合わせる
+pub struct PhaseNoise {
+    // (a, b, 状態): φk[n] = a φk[n-1] + b w[n]
+    sources: Vec<(f64, f64, f64)>,
+    rng: Rng,
+}
+impl PhaseNoise {
+    /// sample_rate: φ を作るサンプルレート
+    pub fn new(sample_rate: f64, mask: &[(f64, f64)], rng: Rng) -> Self {
+        // 折れ点 fk の雑音源の片側スペクトルは pk / (1 + (f / fk)^2)
+        // マスクの点で L(f) = S(f) / 2 になるように pk を連立方程式で決める
+        let n = mask.len();
+        let mut matrix: Vec<Vec<f64>> = mask
+            .iter()
+            .map(|&(f, level)| {
+                let mut row: Vec<f64> = mask
+                    .iter()
+                    .map(|&(fk, _)| 1. / (1. + (f / fk).powi(2)))
+                    .collect();
+                row.push(2. * 10f64.powf(level / 10.));
+                row
+            })
+            .collect();
+        for col in 0..n {
+            let pivot = (col..n)
+                .max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))
+                .unwrap();
+            matrix.swap(col, pivot);
+            let pivot_row = matrix[col].clone();
+            for (row, values) in matrix.iter_mut().enumerate() {
+                if row != col {
+                    let ratio = values[col] / pivot_row[col];
+                    for (v, p) in values.iter_mut().zip(&pivot_row).skip(col) {
+                        *v -= ratio * p;
+                    }
+                }
+            }
+        }
+        let sources = mask
+            .iter()
+            .enumerate()
+            .map(|(k, &(fk, _))| {
+                // マスクの傾きが -20dB/dec より急な区間では負になるので、その雑音源は使わない
+                let power = (matrix[k][n] / matrix[k][k]).max(0.);
+                let a = (-TAU * fk / sample_rate).exp();
+                (a, (1. - a) * (power * sample_rate / 2.).sqrt(), 0.)
+            })
+            .collect();
+        Self { sources, rng }
+    }
+    pub fn next(&mut self) -> f64 {
+        self.sources
+            .iter_mut()
+            .map(|(a, b, state)| {
+                *state = *a * *state + *b * self.rng.gaussian();
+                *state
+            })
+            .sum()
+    }
+}
+
+pub struct Oscillator {
+    sample_rate: f64,
+    nominal_freq: f64,
+    config: OscillatorConfig,
+    seed_rng: Rng,
+    // 作成 (reset) からの経過時間 [s]
+    elapsed: f64,
+    phase_noise: Option<PhaseNoise>,
+    // 位相の回転 (cos φ, sin φ) と 1 サンプルあたりの変化量
+    rotation: (f64, f64),
+    step: (f64, f64),
+    remain: usize,
+    quadrature: Quadrature,
+    // 前のブロックで周波数をずらしたか
+    detuned: bool,
+}
+impl Oscillator {
+    pub fn new(sample_rate: f64, nominal_freq: f64, config: &OscillatorConfig, rng: Rng) -> Self {
+        let mut oscillator = Self {
+            sample_rate,
+            nominal_freq,
+            config: OscillatorConfig::default(),
+            seed_rng: rng,
+            elapsed: 0.,
+            phase_noise: None,
+            rotation: (1., 0.),
+            step: (0., 0.),
+            remain: 0,
+            quadrature: Quadrature::new(sample_rate, nominal_freq),
+            detuned: false,
+        };
+        oscillator.set_config(config);
+        oscillator
+    }
+    /// 設定を変更する。経過時間 (ドリフト) は引き継ぐ
+    pub fn set_config(&mut self, config: &OscillatorConfig) {
+        if self.config.phase_noise != config.phase_noise {
+            self.phase_noise = (!config.phase_noise.is_empty()).then(|| {
+                PhaseNoise::new(
+                    self.sample_rate / UPDATE_INTERVAL as f64,
+                    &config.phase_noise,
+                    self.seed_rng.clone(),
+                )
+            });
+        }
+        self.config = config.clone();
+    }
+    /// 設定上の周波数 (ずれの基準) を変更する
+    pub fn set_nominal_freq(&mut self, freq: f64) {
+        self.nominal_freq = freq;
+    }
+    /// 経過時間 0 (電源投入直後) からやり直す
+    pub fn reset(&mut self, rng: Rng) {
+        self.elapsed = 0.;
+        self.reseed(rng);
+    }
+    /// 位相雑音の乱数を差し替える
+    pub fn reseed(&mut self, rng: Rng) {
+        self.seed_rng = rng;
+        self.phase_noise = (!self.config.phase_noise.is_empty()).then(|| {
+            PhaseNoise::new(
+                self.sample_rate / UPDATE_INTERVAL as f64,
+                &self.config.phase_noise,
+                self.seed_rng.clone(),
+            )
+        });
+        self.rotation = (1., 0.);
+        self.step = (0., 0.);
+        self.remain = 0;
+        self.quadrature.reset();
+    }
+    /// 経過時間 elapsed [s] での周波数偏差 [ppm]
+    pub fn error_ppm(&self, elapsed: f64) -> f64 {
+        self.config.offset_ppm
+            + self.config.drift.map_or(0., |drift| {
+                drift.ppm * (1. - (-elapsed / drift.time_constant).exp())
+            })
+    }
+    /// 次の len サンプルのブロックで使う周波数を返し、経過時間を進める
+    /// (周波数を変える必要が無い場合は None)
+    pub fn next_block_freq(&mut self, len: usize) -> Option<f64> {
+        let elapsed = self.elapsed;
+        self.elapsed += len as f64 / self.sample_rate;
+        let was_detuned = self.detuned;
+        self.detuned = self.config.offset_ppm != 0. || self.config.drift.is_some();
+        if self.detuned {
+            Some(self.nominal_freq * (1. + self.error_ppm(elapsed) * 1e-6))
+        } else {
+            // ずれを無くした直後は設定上の周波数に戻す
+            was_detuned.then_some(self.nominal_freq)
+        }
+    }
+    /// 周波数 center_freq 付近の信号に位相雑音を加える (位相を φ だけ回す)
+    pub fn process_phase_noise(&mut self, signal: &mut [f64], center_freq: f64) {
+        let Some(phase_noise) = &mut self.phase_noise else {
+            return;
+        };
+        // Re{e^jφ (x + j x̂)} = x cos φ - x̂ sin φ
+        self.quadrature.set_freq(self.sample_rate, center_freq);
+        for v in signal.iter_mut() {
+            if self.remain == 0 {
+                let (sin, cos) = phase_noise.next().sin_cos();
+                self.step = (
+                    (cos - self.rotation.0) / UPDATE_INTERVAL as f64,
+                    (sin - self.rotation.1) / UPDATE_INTERVAL as f64,
+                );
+                self.remain = UPDATE_INTERVAL;
+            }
+            let x = *v;
+            let quadrature = self.quadrature.process(x);
+            *v = self.rotation.0 * x - self.rotation.1 * quadrature;
+            self.rotation.0 += self.step.0;
+            self.rotation.1 += self.step.1;
+            self.remain -= 1;
+        }
+    }
+}
@@ -0,0 +1,44 @@
+/**
+ * 狭帯域信号の直交成分 (ヒルベルト変換) の近似
+ * 角周波数 ω 付近の信号では x̂[n] = (x[n-1] - x[n] cos ω) / sin ω で近似できる。
+ * 位相の回転・包絡線の検出など、RF や中間周波数の実信号を複素信号のように扱うステージで使う。
+*/
+use std::f64::consts::TAU;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Quadrature {
+    sin_w: f64,
+    cos_w: f64,
+    // 直前の入力
+    last: f64,
+}
+impl Quadrature {
+    /// freq [Hz] 付近の信号の直交成分を近似する
+    pub fn new(sample_rate: f64, freq: f64) -> Self {
+        let mut quadrature = Self {
+            sin_w: 0.,
+            cos_w: 0.,
+            last: 0.,
+        };
+        quadrature.set_freq(sample_rate, freq);
+        quadrature
+    }
+    /// 中心の周波数を変更する。直前の入力は引き継ぐ
+    pub fn set_freq(&mut self, sample_rate: f64, freq: f64) {
+        (self.sin_w, self.cos_w) = (TAU * freq / sample_rate).sin_cos();
+    }
+    pub fn reset(&mut self) {
+        self.last = 0.;
+    }
+    /// x[n] を入れて x̂[n] を返す
+    pub fn process(&mut self, x: f64) -> f64 {
+        let quadrature = (self.last - x * self.cos_w) / self.sin_w;
+        self.last = x;
+        quadrature
+    }
+    /// 複素利得 re + j im を掛けた Re{(re + j im)(x + j x̂)} = re x - im x̂ を
+    /// x[n] と x[n-1] の重み付け和で表したときの重み
+    pub fn weights(&self, re: f64, im: f64) -> (f64, f64) {
+        (re + im * self.cos_w / self.sin_w, -im / self.sin_w)
+    }
+}
@@ -0,0 +1,420 @@
+/**
+ * RDS のデコーダ
+ * パイロットに PLL で同期し、その 3 倍の位相で 57kHz の副搬送波を同期検波する。
+ * ビットのクロックもパイロット (1/16) から作り、ビット内のどの時点で判定するかだけを信号から学習する。
+ * ブロックの同期はシンドロームがオフセットワードに一致する位置を探して取り、
+ * 同期後は 5bit 以下のバースト誤りを訂正する。
+*/
+use super::{
+    ClockTime, BIT_RATE, GENERATOR, OFFSET_A, OFFSET_B, OFFSET_C, OFFSET_C_PRIME, OFFSET_D,
+    SUBCARRIER_FREQ,
+};
+use crate::modulation_modules::filter::{Bpf, FilterInfo, Lpf};
+use crate::modulation_modules::modulator::BandPassFilter;
+use std::collections::{HashMap, VecDeque};
+use std::f64::consts::TAU;
+
+const PILOT_FREQ: f64 = SUBCARRIER_FREQ / 3.;
+/// PLL の固有周波数 [Hz] と減衰係数
+const PLL_NATURAL_FREQ: f64 = 20.;
+const PLL_DAMPING: f64 = 0.707;
+/// 1 ビットを何個に分けて判定の時点を探すか
+const TIMING_BINS: usize = 32;
+/// 判定の時点の評価値の平滑化の係数
+const TIMING_SMOOTHING: f64 = 0.02;
+/// 判定の時点を切り替える評価値の比
+const TIMING_HYSTERESIS: f64 = 1.2;
+/// 同期を外すまでの、訂正できないブロックの連続数
+const MAX_CONSECUTIVE_ERRORS: u32 = 10;
+/// 修正ユリウス日で 1970-01-01
+const MJD_UNIX_EPOCH: u64 = 40_587;
+
+/// 受信した局の情報とブロックの誤りの統計
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RdsStatus {
+    /// ブロックの同期が取れている
+    pub synchronized: bool,
+    pub pi: Option<u16>,
+    /// 4 セグメントとも受信した PS
+    pub ps: Option<String>,
+    /// 終端 (CR) まで受信した RadioText
+    pub radio_text: Option<String>,
+    pub pty: Option<u8>,
+    pub tp: bool,
+    pub ta: bool,
+    /// 最後に受信した CT
+    pub clock_time: Option<ClockTime>,
+    /// 同期後に受信したブロック数と、そのうち訂正したもの・訂正できなかったもの
+    pub blocks: u64,
+    pub corrected_blocks: u64,
+    pub error_blocks: u64,
+    /// 4 ブロックとも正しく受信したグループ数
+    pub groups: u64,
+}
+impl RdsStatus {
+    /// 訂正できなかったブロックの割合 (ブロックを受信していない場合は 0)
+    pub fn block_error_rate(&self) -> f64 {
+        if self.blocks == 0 {
+            0.
+        } else {
+            self.error_blocks as f64 / self.blocks as f64
+        }
+    }
+}
+
+/// 26bit のワードのシンドローム (誤りのないブロックではオフセットワードに一致する)
+fn syndrome(word: u32) -> u16 {
+    let mut reg = word;
+    for bit in (10..26).rev() {
+        if reg & (1 << bit) != 0 {
+            reg ^= GENERATOR << (bit - 10);
+        }
+    }
+    reg as u16
+}
+/// ブロックの種類 (A, B, C, D) ごとに許すオフセットワード
+fn offsets(block: usize) -> &'static [u16] {
+    match block {
+        0 => &[OFFSET_A],
+        1 => &[OFFSET_B],
+        2 => &[OFFSET_C, OFFSET_C_PRIME],
+        _ => &[OFFSET_D],
+    }
+}
+
+pub struct RdsDecoder {
+    sample_rate: f64,
+    // パイロットの PLL (位相はパイロットの周期数)
+    pilot_filter: Bpf,
+    pilot_info: FilterInfo,
+    pll_gains: (f64, f64),
+    pll_integrator: f64,
+    pilot_iq: (f64, f64),
+    iq_coeff: f64,
+    cycles: f64,
+    // 57kHz の BPF と、同期検波後の LPF
+    band_filter: Bpf,
+    lpf: Lpf,
+    filter_info: [FilterInfo; 4],
+    // 半ビット前までの検波出力と、判定の時点の評価値
+    history: VecDeque<f64>,
+    half_bit: usize,
+    timing: [f64; TIMING_BINS],
+    best_bin: usize,
+    last_bin: usize,
+    last_bit: bool,
+    // ブロックの同期
+    burst_table: HashMap<u16, u32>,
+    word: u32,
+    bit_count: u64,
+    candidates: VecDeque<(u64, usize)>,
+    synchronized: bool,
+    block: usize,
+    block_bits: usize,
+    consecutive_errors: u32,
+    group: [Option<u16>; 4],
+    // 受信した内容
+    status: RdsStatus,
+    ps: [u8; 8],
+    ps_received: u8,
+    radio_text: [u8; 64],
+    radio_text_received: u16,
+    radio_text_end: Option<usize>,
+    text_ab: Option<bool>,
+}
+impl RdsDecoder {
+    pub fn new(sample_rate: f64) -> Self {
+        let wt = TAU * PLL_NATURAL_FREQ / sample_rate;
+        // 5bit 以下のバースト誤りのシンドローム
+        let mut burst_table = HashMap::new();
+        for len in 1..=5 {
+            for pattern in 0u32..1 << len {
+                if pattern & 1 == 0 || pattern >> (len - 1) == 0 {
+                    continue;
+                }
+                for shift in 0..=26 - len {
+                    let error = pattern << shift;
+                    burst_table.entry(syndrome(error)).or_insert(error);
+                }
+            }
+        }
+        Self {
+            sample_rate,
+            pilot_filter: Bpf::new(
+                sample_rate,
+                PILOT_FREQ,
+                BandPassFilter::bandwidth_octave(PILOT_FREQ, 1000.),
+            ),
+            pilot_info: FilterInfo::default(),
+            pll_gains: (2. * PLL_DAMPING * wt, wt * wt),
+            pll_integrator: 0.,
+            pilot_iq: (0., 0.),
+            iq_coeff: 1. - (-TAU * 100. / sample_rate).exp(),
+            cycles: 0.,
+            band_filter: Bpf::new(
+                sample_rate,
+                SUBCARRIER_FREQ,
+                BandPassFilter::bandwidth_octave(SUBCARRIER_FREQ, 6000.),
+            ),
+            lpf: Lpf::new(sample_rate, BIT_RATE * 2., Lpf::Q),
+            filter_info: Default::default(),
+            history: VecDeque::new(),
+            half_bit: (sample_rate / BIT_RATE / 2.).round() as usize,
+            timing: [0.; TIMING_BINS],
+            best_bin: 0,
+            last_bin: 0,
+            last_bit: false,
+            burst_table,
+            word: 0,
+            bit_count: 0,
+            candidates: VecDeque::new(),
+            synchronized: false,
+            block: 0,
+            block_bits: 0,
+            consecutive_errors: 0,
+            group: [None; 4],
+            status: RdsStatus::default(),
+            ps: [b' '; 8],
+            ps_received: 0,
+            radio_text: [b' '; 64],
+            radio_text_received: 0,
+            radio_text_end: None,
+            text_ab: None,
+        }
+    }
+    pub fn status(&self) -> &RdsStatus {
+        &self.status
+    }
+    /// PLL・同期・受信した内容・統計を初期状態に戻す
+    pub fn reset(&mut self) {
+        self.pilot_info = FilterInfo::default();
+        self.pll_integrator = 0.;
+        self.pilot_iq = (0., 0.);
+        self.cycles = 0.;
+        self.filter_info = Default::default();
+        self.history.clear();
+        self.timing = [0.; TIMING_BINS];
+        self.best_bin = 0;
+        self.last_bin = 0;
+        self.last_bit = false;
+        self.word = 0;
+        self.bit_count = 0;
+        self.candidates.clear();
+        self.synchronized = false;
+        self.block = 0;
+        self.block_bits = 0;
+        self.consecutive_errors = 0;
+        self.group = [None; 4];
+        self.status = RdsStatus::default();
+        self.ps = [b' '; 8];
+        self.ps_received = 0;
+        self.radio_text = [b' '; 64];
+        self.radio_text_received = 0;
+        self.radio_text_end = None;
+        self.text_ab = None;
+    }
+    /// コンポジット信号の 1 サンプルを処理する
+    pub fn process(&mut self, signal: f64) {
+        // パイロットの PLL。I/Q の振幅で正規化した位相差 sin Δ で追従する
+        let pilot = self
+            .pilot_filter
+            .process_without_buffer(signal, &mut self.pilot_info);
+        let (sin, cos) = (TAU * self.cycles.fract()).sin_cos();
+        let (i, q) = (2. * pilot * cos, -2. * pilot * sin);
+        self.pilot_iq.0 += self.iq_coeff * (i - self.pilot_iq.0);
+        self.pilot_iq.1 += self.iq_coeff * (q - self.pilot_iq.1);
+        let amplitude = self.pilot_iq.0.hypot(self.pilot_iq.1).max(1e-12);
+        let error = (q / amplitude).clamp(-2., 2.);
+        self.pll_integrator += self.pll_gains.1 * error;
+        let correction = self.pll_gains.0 * error + self.pll_integrator;
+        self.cycles += PILOT_FREQ / self.sample_rate + correction / TAU;
+
+        // パイロットの 3 倍で同期検波
+        let band = self
+            .band_filter
+            .process_without_buffer(signal, &mut self.filter_info[0]);
+        let band = self
+            .band_filter
+            .process_without_buffer(band, &mut self.filter_info[1]);
+        let mixed = 2. * band * (3. * TAU * self.cycles.fract()).cos();
+        let baseband = self
+            .lpf
+            .process_without_buffer(mixed, &mut self.filter_info[2]);
+        let baseband = self
+            .lpf
+            .process_without_buffer(baseband, &mut self.filter_info[3]);
+
+        // バイフェーズ符号の前半と後半の差が最も大きくなる時点で判定する
+        self.history.push_back(baseband);
+        if self.history.len() <= self.half_bit {
+            return;
+        }
+        let first = self.history.pop_front().unwrap_or_default();
+        let phase = (self.cycles / 16. - 0.5).rem_euclid(1.);
+        let bin = ((phase * TIMING_BINS as f64) as usize).min(TIMING_BINS - 1);
+        self.timing[bin] += TIMING_SMOOTHING * ((first - baseband).abs() - self.timing[bin]);
+        if self.timing[bin] > self.timing[self.best_bin] * TIMING_HYSTERESIS {
+            self.best_bin = bin;
+        }
+        if bin == self.best_bin && bin != self.last_bin {
+            // 差動符号を戻す
+            let bit = first > baseband;
+            self.receive_bit(bit != self.last_bit);
+            self.last_bit = bit;
+        }
+        self.last_bin = bin;
+    }
+    fn receive_bit(&mut self, bit: bool) {
+        self.word = (self.word << 1 | bit as u32) & 0x3ff_ffff;
+        self.bit_count += 1;
+        if !self.synchronized {
+            self.search_sync();
+            return;
+        }
+        self.block_bits += 1;
+        if self.block_bits < 26 {
+            return;
+        }
+        self.block_bits = 0;
+        let block = self.block;
+        self.block = (block + 1) % 4;
+        self.status.blocks += 1;
+        let syndrome = syndrome(self.word);
+        let info = if offsets(block).contains(&syndrome) {
+            self.consecutive_errors = 0;
+            Some((self.word >> 10) as u16)
+        } else if let Some(error) = offsets(block)
+            .iter()
+            .find_map(|offset| self.burst_table.get(&(syndrome ^ offset)))
+        {
+            self.consecutive_errors = 0;
+            self.status.corrected_blocks += 1;
+            Some(((self.word ^ error) >> 10) as u16)
+        } else {
+            self.consecutive_errors += 1;
+            self.status.error_blocks += 1;
+            None
+        };
+        self.group[block] = info;
+        if block == 3 {
+            self.decode_group();
+            self.group = [None; 4];
+        }
+        if self.consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+            self.synchronized = false;
+            self.status.synchronized = false;
+            self.candidates.clear();
+        }
+    }
+    /// 26bit 毎に続けて正しい順序のオフセットワードが見つかったら同期する
+    fn search_sync(&mut self) {
+        if self.bit_count < 26 {
+            return;
+        }
+        let syndrome = syndrome(self.word);
+        let Some(block) = (0..4).find(|block| offsets(*block).contains(&syndrome)) else {
+            return;
+        };
+        let found = self.candidates.iter().any(|(position, previous)| {
+            let distance = self.bit_count - position;
+            distance.is_multiple_of(26) && (previous + (distance / 26) as usize) % 4 == block
+        });
+        self.candidates.push_back((self.bit_count, block));
+        while let Some((position, _)) = self.candidates.front() {
+            if self.bit_count - position > 26 * 4 {
+                self.candidates.pop_front();
+            } else {
+                break;
+            }
+        }
+        if found {
+            self.synchronized = true;
+            self.status.synchronized = true;
+            self.consecutive_errors = 0;
+            self.group = [None; 4];
+            self.group[block] = Some((self.word >> 10) as u16);
+            self.block = (block + 1) % 4;
+            self.block_bits = 0;
+            if block == 3 {
+                self.decode_group();
+                self.group = [None; 4];
+            }
+        }
+    }
+    fn decode_group(&mut self) {
+        let Some(b) = self.group[1] else {
+            return;
+        };
+        if self.group.iter().all(Option::is_some) {
+            self.status.groups += 1;
+        }
+        let version_b = b >> 11 & 1 == 1;
+        if let Some(pi) = self.group[0].or(if version_b { self.group[2] } else { None }) {
+            self.status.pi = Some(pi);
+        }
+        self.status.tp = b >> 10 & 1 == 1;
+        self.status.pty = Some((b >> 5 & 0x1f) as u8);
+        match (b >> 12, version_b) {
+            (0, _) => {
+                self.status.ta = b >> 4 & 1 == 1;
+                let Some(d) = self.group[3] else {
+                    return;
+                };
+                let segment = (b & 0b11) as usize;
+                self.ps[segment * 2..segment * 2 + 2].copy_from_slice(&d.to_be_bytes());
+                self.ps_received |= 1 << segment;
+                if self.ps_received == 0xf {
+                    self.status.ps = Some(String::from_utf8_lossy(&self.ps).into_owned());
+                }
+            }
+            (2, _) => {
+                // A/B フラグが変わったら新しい RadioText として受け直す
+                let text_ab = b >> 4 & 1 == 1;
+                if self.text_ab != Some(text_ab) {
+                    self.text_ab = Some(text_ab);
+                    self.radio_text = [b' '; 64];
+                    self.radio_text_received = 0;
+                    self.radio_text_end = None;
+                }
+                // バージョン A は 4 文字 x 16 セグメント、B は 2 文字 x 16 セグメント
+                let segment = (b & 0xf) as usize;
+                let (chars, words) = if version_b {
+                    (2, vec![self.group[3]])
+                } else {
+                    (4, vec![self.group[2], self.group[3]])
+                };
+                let Some(words) = words.into_iter().collect::<Option<Vec<_>>>() else {
+                    return;
+                };
+                for (i, c) in words.iter().flat_map(|w| w.to_be_bytes()).enumerate() {
+                    let position = segment * chars + i;
+                    self.radio_text[position] = c;
+                    if c == 0x0d {
+                        self.radio_text_end = Some(position);
+                    }
+                }
+                self.radio_text_received |= 1 << segment;
+                let end = self.radio_text_end.unwrap_or(chars * 16);
+                let segments = (end + 1).div_ceil(chars).min(16);
+                if (0..segments).all(|s| self.radio_text_received & (1 << s) != 0) {
+                    self.status.radio_text =
+                        Some(String::from_utf8_lossy(&self.radio_text[..end]).into_owned());
+                }
+            }
+            (4, false) => {
+                let (Some(c), Some(d)) = (self.group[2], self.group[3]) else {
+                    return;
+                };
+                let mjd = ((b & 0b11) as u64) << 15 | (c >> 1) as u64;
+                let hour = ((c & 1) << 4 | d >> 12) as u64;
+                let minute = (d >> 6 & 0x3f) as u64;
+                let offset = (d & 0x1f) as i8;
+                self.status.clock_time = Some(ClockTime {
+                    utc: (mjd.saturating_sub(MJD_UNIX_EPOCH) * 24 + hour) * 3600 + minute * 60,
+                    local_offset: if d & 0x20 != 0 { -offset } else { offset },
+                });
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,223 @@
+/**
+ * RDS のエンコーダ
+ * グループを組み立て、差動符号化したビットをバイフェーズ符号の波形にする。
+ * ビットのクロックはパイロットと同じ時刻から作るので、副搬送波・ビットともパイロットに同期する。
+*/
+use super::{block, RdsConfig, BIT_RATE, OFFSET_A, OFFSET_B, OFFSET_C, OFFSET_D};
+use std::collections::VecDeque;
+use std::f64::consts::PI;
+
+/// グループの種類 (上位 4bit が種類、最下位 bit が バージョン B)
+const GROUP_0A: u16 = 0x00;
+const GROUP_2A: u16 = 0x04;
+const GROUP_4A: u16 = 0x08;
+/// 0A グループの 3 番目のブロック (AF なし + 埋め草)
+const NO_AF: u16 = 0xe0cd;
+/// 修正ユリウス日で 1970-01-01
+const MJD_UNIX_EPOCH: u64 = 40_587;
+
+pub struct RdsEncoder {
+    config: RdsConfig,
+    // PS と RadioText の次に送るセグメント
+    ps_segment: usize,
+    rt_segment: usize,
+    // RadioText を変えたときに切り替える A/B フラグ
+    text_ab: bool,
+    // 0A と 2A を交互に送る
+    next_is_text: bool,
+    // 最後に CT を送った分 (UNIX 時刻 / 60)
+    last_minute: Option<u64>,
+    // 送信中のグループ (104bit) と、次に送る bit の位置
+    group: [u32; 4],
+    group_bit: usize,
+    // 差動符号化の直前の出力
+    last_bit: bool,
+    // 波形に寄与しているビットの符号 (±1) と、その先頭のビット番号
+    symbols: VecDeque<f64>,
+    first_index: u64,
+    // 波形の最大振幅が 1 になるように掛ける係数
+    normalize: f64,
+}
+impl RdsEncoder {
+    /// 1 ビットの波形を打ち切る範囲 [bit]。波形はこの分だけ遅れて出力される
+    pub(crate) const SPAN: f64 = 2.;
+    pub fn new(config: &RdsConfig) -> Self {
+        // 重なり合う全てのビットが同じ向きに揃ったときの最大値で正規化する
+        let steps = 256;
+        let peak = (0..steps)
+            .map(|i| {
+                let tau = i as f64 / steps as f64;
+                (-3..=3)
+                    .map(|k| Self::symbol(tau + k as f64).abs())
+                    .sum::<f64>()
+            })
+            .fold(0f64, f64::max);
+        let mut encoder = Self {
+            config: config.clone(),
+            ps_segment: 0,
+            rt_segment: 0,
+            text_ab: false,
+            next_is_text: false,
+            last_minute: None,
+            group: [0; 4],
+            group_bit: 104,
+            last_bit: false,
+            symbols: VecDeque::new(),
+            first_index: 0,
+            normalize: 1. / peak,
+        };
+        encoder.reset();
+        encoder
+    }
+    pub fn injection(&self) -> f64 {
+        self.config.injection
+    }
+    /// 局の情報を変更する。RadioText が変わった場合は A/B フラグを切り替え、最初から送り直す
+    pub fn set_config(&mut self, config: &RdsConfig) {
+        if config.radio_text != self.config.radio_text {
+            self.text_ab ^= true;
+            self.rt_segment = 0;
+        }
+        self.config = config.clone();
+    }
+    /// 最初のグループから送り直す
+    pub fn reset(&mut self) {
+        self.ps_segment = 0;
+        self.rt_segment = 0;
+        self.next_is_text = false;
+        self.last_minute = None;
+        self.group_bit = 104;
+        self.last_bit = false;
+        self.symbols.clear();
+        self.first_index = 0;
+    }
+    /// 時刻 t [s] (パイロットと共通) の基底帯域の波形 (最大振幅 1)
+    pub fn process(&mut self, t: f64) -> f64 {
+        let position = t * BIT_RATE;
+        // ビット k は k <= position から (k + 2 * SPAN + 1/2) まで寄与する
+        while ((self.first_index + self.symbols.len() as u64) as f64) <= position {
+            let bit = self.next_bit();
+            self.symbols.push_back(if bit { 1. } else { -1. });
+        }
+        while !self.symbols.is_empty()
+            && (self.first_index as f64) + 2. * Self::SPAN + 1. < position
+        {
+            self.symbols.pop_front();
+            self.first_index += 1;
+        }
+        let mut sum = 0.;
+        for (k, symbol) in self.symbols.iter().enumerate() {
+            let tau = position - (self.first_index + k as u64) as f64 - Self::SPAN;
+            sum += symbol * Self::symbol(tau);
+        }
+        sum * self.normalize
+    }
+    /// バイフェーズ符号の 1 ビットの波形 (τ [bit])。
+    /// +δ(τ) - δ(τ - 1/2) を、|f| < 2 * ビットレート で cos(π f / 4 ビットレート) のフィルタに通したもの
+    fn symbol(tau: f64) -> f64 {
+        Self::impulse_response(tau) - Self::impulse_response(tau - 0.5)
+    }
+    fn impulse_response(tau: f64) -> f64 {
+        if tau.abs() > Self::SPAN {
+            return 0.;
+        }
+        // τ = ±1/8 は 0/0 になるので極限値を使う
+        let denominator = tau * tau - 1. / 64.;
+        if denominator.abs() < 1e-9 {
+            return 2.;
+        }
+        -(4. * PI * tau).cos() / (8. * PI * denominator)
+    }
+    /// 差動符号化した次のビット
+    fn next_bit(&mut self) -> bool {
+        if self.group_bit == 104 {
+            let time = self.first_index + self.symbols.len() as u64;
+            self.group = self.next_group(time as f64 / BIT_RATE);
+            self.group_bit = 0;
+        }
+        let (block, bit) = (self.group_bit / 26, 25 - self.group_bit % 26);
+        self.group_bit += 1;
+        self.last_bit ^= self.group[block] & (1 << bit) != 0;
+        self.last_bit
+    }
+    /// 時刻 t [s] から送るグループ。分が変わったら CT を、それ以外は PS と RadioText を交互に送る
+    fn next_group(&mut self, t: f64) -> [u32; 4] {
+        let info = self.next_group_info(t);
+        [
+            block(info[0], OFFSET_A),
+            block(info[1], OFFSET_B),
+            block(info[2], OFFSET_C),
+            block(info[3], OFFSET_D),
+        ]
+    }
+    pub(crate) fn next_group_info(&mut self, t: f64) -> [u16; 4] {
+        if let Some(clock_time) = self.config.clock_time {
+            let utc = clock_time.utc + t as u64;
+            if self.last_minute != Some(utc / 60) {
+                self.last_minute = Some(utc / 60);
+                return self.clock_time_group(utc, clock_time.local_offset);
+            }
+        }
+        let text = self.next_is_text && !self.config.radio_text.is_empty();
+        self.next_is_text ^= true;
+        if text {
+            self.radio_text_group()
+        } else {
+            self.ps_group()
+        }
+    }
+    fn block2(&self, group_type: u16, low: u16) -> u16 {
+        group_type << 11 | (self.config.tp as u16) << 10 | (self.config.pty as u16) << 5 | low
+    }
+    fn ps_group(&mut self) -> [u16; 4] {
+        let segment = self.ps_segment;
+        self.ps_segment = (segment + 1) % 4;
+        // 8 文字に満たない部分は空白で埋める
+        let ps = self.config.ps.as_bytes();
+        let char_at = |i: usize| *ps.get(i).unwrap_or(&b' ') as u16;
+        // DI のうちセグメント 3 (ステレオ) だけを立てる。M/S は音楽
+        let di = (segment == 3) as u16;
+        let low = (self.config.ta as u16) << 4 | 1 << 3 | di << 2 | segment as u16;
+        [
+            self.config.pi,
+            self.block2(GROUP_0A, low),
+            NO_AF,
+            char_at(segment * 2) << 8 | char_at(segment * 2 + 1),
+        ]
+    }
+    fn radio_text_group(&mut self) -> [u16; 4] {
+        // 64 文字に満たない場合は CR で終端し、そこまでのセグメントだけを送る
+        let text = self.config.radio_text.as_bytes();
+        let len = if text.len() < RdsConfig::RADIO_TEXT_LEN {
+            text.len() + 1
+        } else {
+            text.len()
+        };
+        let char_at = |i: usize| match text.get(i) {
+            Some(c) => *c as u16,
+            None if i == text.len() => 0x0d,
+            None => b' ' as u16,
+        };
+        let segment = self.rt_segment;
+        self.rt_segment = (segment + 1) % len.div_ceil(4);
+        let low = (self.text_ab as u16) << 4 | segment as u16;
+        [
+            self.config.pi,
+            self.block2(GROUP_2A, low),
+            char_at(segment * 4) << 8 | char_at(segment * 4 + 1),
+            char_at(segment * 4 + 2) << 8 | char_at(segment * 4 + 3),
+        ]
+    }
+    fn clock_time_group(&self, utc: u64, local_offset: i8) -> [u16; 4] {
+        let mjd = utc / 86_400 + MJD_UNIX_EPOCH;
+        let hour = (utc / 3600 % 24) as u16;
+        let minute = (utc / 60 % 60) as u16;
+        let offset = ((local_offset < 0) as u16) << 5 | local_offset.unsigned_abs() as u16;
+        [
+            self.config.pi,
+            self.block2(GROUP_4A, (mjd >> 15) as u16 & 0b11),
+            ((mjd & 0x7fff) as u16) << 1 | hour >> 4,
+            (hour & 0xf) << 12 | minute << 6 | offset,
+        ]
+    }
+}
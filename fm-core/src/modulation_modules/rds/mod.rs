@@ -0,0 +1,126 @@
+/**
+ * RDS (Radio Data System, 北米では RBDS)
+ * 57kHz の副搬送波で 1187.5bps のデータを送る。データは 26bit のブロック 4 つからなるグループ単位で、
+ * 各ブロックは 16bit の情報と 10bit のチェックワード (CRC + オフセットワード) でできている。
+*/
+use crate::config::ConfigError;
+pub mod decoder;
+pub mod encoder;
+pub use decoder::{RdsDecoder, RdsStatus};
+pub use encoder::RdsEncoder;
+
+/// 副搬送波の周波数 (パイロットの 3 倍)
+pub const SUBCARRIER_FREQ: f64 = 57_000.;
+/// ビットレート (副搬送波の 1/48)
+pub const BIT_RATE: f64 = SUBCARRIER_FREQ / 48.;
+/// 変調された信号に含まれる最大の周波数 (バイフェーズ符号の帯域 ±2.4kHz)
+pub const MAX_FREQ: f64 = SUBCARRIER_FREQ + BIT_RATE * 2.;
+
+/// チェックワードの生成多項式 x^10 + x^8 + x^7 + x^5 + x^4 + x^3 + 1
+const GENERATOR: u32 = 0b101_1011_1001;
+/// 各ブロックのオフセットワード (C' はバージョン B のグループの 3 番目のブロック)
+pub(crate) const OFFSET_A: u16 = 0x0fc;
+pub(crate) const OFFSET_B: u16 = 0x198;
+pub(crate) const OFFSET_C: u16 = 0x168;
+pub(crate) const OFFSET_C_PRIME: u16 = 0x350;
+pub(crate) const OFFSET_D: u16 = 0x1b4;
+
+/// 情報ワードの CRC (info * x^10 mod g(x))
+pub(crate) fn checkword(info: u16) -> u16 {
+    let mut reg = (info as u32) << 10;
+    for bit in (10..26).rev() {
+        if reg & (1 << bit) != 0 {
+            reg ^= GENERATOR << (bit - 10);
+        }
+    }
+    reg as u16
+}
+/// 26bit のブロック (上位 16bit が情報、下位 10bit がチェックワード)
+pub(crate) fn block(info: u16, offset: u16) -> u32 {
+    (info as u32) << 10 | (checkword(info) ^ offset) as u32
+}
+
+/// CT (時刻) グループで送る時刻
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClockTime {
+    /// 処理を始めた時点の UTC (UNIX 時刻 [s])。以降はシミュレーション上の経過時間だけ進む
+    pub utc: u64,
+    /// UTC に対する地方時のずれ (30 分単位、日本は +18)
+    pub local_offset: i8,
+}
+
+/// 送信する局の情報と副搬送波の振幅
+#[derive(Debug, Clone, PartialEq)]
+pub struct RdsConfig {
+    /// PI コード (局の識別)
+    pub pi: u16,
+    /// PS (局名、ASCII 8 文字まで)
+    pub ps: String,
+    /// RadioText (ASCII 64 文字まで、空の場合は送らない)
+    pub radio_text: String,
+    /// 番組の種類 (0..=31。RDS と RBDS で番号の意味が異なる)
+    pub pty: u8,
+    /// 交通情報を放送する局
+    pub tp: bool,
+    /// 交通情報を放送中
+    pub ta: bool,
+    /// None の場合は CT グループを送らない
+    pub clock_time: Option<ClockTime>,
+    /// 副搬送波の振幅 (最大周波数偏移に対する比)
+    pub injection: f64,
+}
+impl RdsConfig {
+    /// 3kHz / 75kHz
+    pub const DEFAULT_INJECTION: f64 = 0.04;
+    pub const PS_LEN: usize = 8;
+    pub const RADIO_TEXT_LEN: usize = 64;
+    pub fn new(pi: u16, ps: &str) -> Self {
+        Self {
+            pi,
+            ps: ps.to_string(),
+            radio_text: String::new(),
+            pty: 0,
+            tp: false,
+            ta: false,
+            clock_time: None,
+            injection: Self::DEFAULT_INJECTION,
+        }
+    }
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        for (name, text, max_len) in [
+            ("rds_ps", &self.ps, Self::PS_LEN),
+            ("rds_radio_text", &self.radio_text, Self::RADIO_TEXT_LEN),
+        ] {
+            if text.len() > max_len || !text.bytes().all(|c| c.is_ascii() && !c.is_ascii_control())
+            {
+                return Err(ConfigError::RdsTextInvalid {
+                    name,
+                    text: text.clone(),
+                    max_len,
+                });
+            }
+        }
+        if self.pty > 31 {
+            return Err(ConfigError::RdsFieldOutOfRange {
+                name: "rds_pty",
+                value: self.pty as i64,
+            });
+        }
+        if let Some(clock_time) = self.clock_time {
+            // 5bit の絶対値と符号で送る
+            if clock_time.local_offset.unsigned_abs() > 31 {
+                return Err(ConfigError::RdsFieldOutOfRange {
+                    name: "rds_local_offset",
+                    value: clock_time.local_offset as i64,
+                });
+            }
+        }
+        if !(self.injection > 0. && self.injection.is_finite()) {
+            return Err(ConfigError::NotPositive {
+                name: "rds_injection",
+                value: self.injection,
+            });
+        }
+        Ok(())
+    }
+}
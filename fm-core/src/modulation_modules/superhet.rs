@@ -0,0 +1,154 @@
+/**
+ * スーパーヘテロダイン受信機の構成
+ * RF のプリセレクタ (同調周波数に追従する BPF)、イメージ抑圧ミキサー、
+ * 2 回目の周波数変換 (例: 10.7MHz -> 450kHz) を指定する。
+ * 局部発振は同調周波数より低い (fc - fi) ので、イメージ周波数は同調周波数 - 2 * 中間周波数になる。
+*/
+use super::filter::{Bpf, FilterInfo};
+use super::modulator::BandPassFilter;
+use crate::config::ConfigError;
+use std::f64::consts::TAU;
+
+/// RF のプリセレクタ。同じ 2 次の BPF を sections 段重ねる
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Preselector {
+    /// 全体の帯域幅 (-3dB) [Hz]
+    pub bandwidth: f64,
+    pub sections: usize,
+}
+/// 2 回目の周波数変換の後の中間周波数と、そのフィルタの帯域幅 (-3dB) [Hz]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SecondIf {
+    pub freq: f64,
+    pub bandwidth: f64,
+}
+impl SecondIf {
+    pub const DEFAULT_FREQ: f64 = 450_000.;
+}
+
+/// 受信機の構成 (既定ではプリセレクタなし・イメージ抑圧なしのシングルスーパー)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SuperhetConfig {
+    pub preselector: Option<Preselector>,
+    /// イメージ抑圧ミキサーのイメージ抑圧比 [dB] (None: 通常のミキサー、イメージも同じ利得で通る)
+    pub image_rejection: Option<f64>,
+    pub second_if: Option<SecondIf>,
+}
+impl SuperhetConfig {
+    /// 復調器に入る中間周波数
+    pub fn demodulation_freq(&self, intermediate_freq: f64) -> f64 {
+        self.second_if
+            .map_or(intermediate_freq, |second_if| second_if.freq)
+    }
+    pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+        let mut positive = Vec::new();
+        if let Some(preselector) = self.preselector {
+            positive.push(("preselector_bandwidth", preselector.bandwidth));
+            positive.push(("preselector_sections", preselector.sections as f64));
+        }
+        if let Some(second_if) = self.second_if {
+            positive.push(("second_if_freq", second_if.freq));
+            positive.push(("second_if_bandwidth", second_if.bandwidth));
+        }
+        for (name, value) in positive {
+            if !(value > 0. && value.is_finite()) {
+                return Err(ConfigError::NotPositive { name, value });
+            }
+        }
+        if let Some(value) = self.image_rejection {
+            if !(value >= 0. && value.is_finite()) {
+                return Err(ConfigError::NotPositive {
+                    name: "image_rejection",
+                    value,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 同調周波数に追従するプリセレクタ
+pub struct PreselectorFilter {
+    sample_rate: f64,
+    preselector: Option<Preselector>,
+    coeff: Bpf,
+    states: Vec<FilterInfo>,
+}
+impl PreselectorFilter {
+    pub fn new(sample_rate: f64, tuned_freq: f64, preselector: Option<Preselector>) -> Self {
+        let mut filter = Self {
+            sample_rate,
+            preselector,
+            coeff: Bpf::default(),
+            states: vec![Default::default(); preselector.map_or(0, |p| p.sections)],
+        };
+        filter.set_tuned_freq(tuned_freq);
+        filter
+    }
+    /// 中心周波数を変更する。フィルタの状態は引き継ぐ
+    pub fn set_tuned_freq(&mut self, freq: f64) {
+        let Some(preselector) = self.preselector else {
+            return;
+        };
+        // n 段で -3dB になる 1 段あたりの帯域幅: (1 + (B / Bs)²)^n = 2
+        let sections = preselector.sections as f64;
+        let bandwidth = preselector.bandwidth / (2f64.powf(1. / sections) - 1.).sqrt();
+        self.coeff = Bpf::new(
+            self.sample_rate,
+            freq,
+            BandPassFilter::bandwidth_octave(freq, bandwidth),
+        );
+    }
+    pub fn reset(&mut self) {
+        self.states.fill(Default::default());
+    }
+    pub fn process(&mut self, signal: &mut [f64]) {
+        for state in self.states.iter_mut() {
+            for v in signal.iter_mut() {
+                *v = self.coeff.process_without_buffer(*v, state);
+            }
+        }
+    }
+}
+
+/// 2 回目の周波数変換 (ミキサー + 2 回目の中間周波数の BPF)
+/// 局部発振は 1 回目の中間周波数 - 2 回目の中間周波数で、位相雑音・周波数のずれは無いものとする
+pub struct SecondConversion {
+    delta_angle: f64,
+    angle: f64,
+    filter: Option<BandPassFilter>,
+    buffer: Vec<f64>,
+}
+impl SecondConversion {
+    /// sample_rate: 1 回目の中間周波数の信号 (間引いた後) のサンプルレート
+    pub fn new(sample_rate: f64, intermediate_freq: f64, second_if: Option<SecondIf>) -> Self {
+        Self {
+            delta_angle: second_if.map_or(0., |s| TAU * (intermediate_freq - s.freq) / sample_rate),
+            angle: 0.,
+            filter: second_if
+                .map(|s| BandPassFilter::with_bandwidth(sample_rate, s.freq, s.bandwidth)),
+            buffer: Vec::new(),
+        }
+    }
+    pub fn reset(&mut self) {
+        self.angle = 0.;
+        if let Some(filter) = &mut self.filter {
+            filter.reset();
+        }
+    }
+    pub fn process(&mut self, signal: &mut [f64]) {
+        let Some(filter) = &mut self.filter else {
+            return;
+        };
+        // 差の周波数の成分の振幅は 1/2 になるので 2 倍して戻す
+        self.buffer.clear();
+        self.buffer.extend(
+            signal
+                .iter()
+                .enumerate()
+                .map(|(n, v)| 2. * v * (self.angle + n as f64 * self.delta_angle).cos()),
+        );
+        self.angle = (self.angle + signal.len() as f64 * self.delta_angle) % TAU;
+        filter.process_no_resample(&self.buffer, signal);
+    }
+}
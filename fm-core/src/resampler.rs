@@ -1,8 +1,8 @@
 #[repr(C)]
 pub struct ResamplerInfo {
-    prev: f64,
-    multiplier: usize,
-    input_len: usize,
+    pub(crate) prev: f64,
+    pub(crate) multiplier: usize,
+    pub(crate) input_len: usize,
 }
 
 impl ResamplerInfo {
@@ -20,4 +20,7 @@ impl ResamplerInfo {
             input_len: input_size,
         }
     }
+    pub fn reset(&mut self) {
+        self.prev = 0.0;
+    }
 }
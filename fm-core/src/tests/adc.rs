@@ -0,0 +1,84 @@
+use super::signals;
+use crate::modulation_modules::adc::Adc;
+use crate::modulation_modules::channel::Rng;
+use crate::AdcConfig;
+use std::f64::consts::TAU;
+
+const SAMPLE_RATE: f64 = 1_000_000.;
+const LEN: usize = 200_000;
+
+fn convert(config: AdcConfig, input: &[f64]) -> Vec<f64> {
+    let mut dut = Adc::new(SAMPLE_RATE, Some(config), Rng::new(1));
+    let mut output = input.to_vec();
+    for block in output.chunks_mut(4096) {
+        dut.process(block);
+    }
+    output
+}
+
+/// 入力に対する出力の誤差の S/N [dB]
+fn snr(input: &[f64], output: &[f64]) -> f64 {
+    let signal: f64 = input.iter().map(|v| v * v).sum();
+    let error: f64 = input.iter().zip(output).map(|(x, y)| (y - x).powi(2)).sum();
+    10. * (signal / error).log10()
+}
+
+#[test]
+fn quantization_noise_follows_bits() {
+    for bits in [8, 14] {
+        // フルスケールの正弦波の S/N は 6.02N + 1.76dB
+        let step = 2f64.powi(1 - bits as i32);
+        let input = signals::tone(SAMPLE_RATE, 12_345.678, 1. - step, LEN);
+        let measured = snr(&input, &convert(AdcConfig::new(bits, 0.), &input));
+        let expected = 6.02 * bits as f64 + 1.76;
+        assert!(
+            (measured - expected).abs() < 0.5,
+            "bits: {bits}, measured: {measured}dB"
+        );
+    }
+}
+
+#[test]
+fn clips_at_full_scale() {
+    // フルスケール -6dB (振幅 0.5) に振幅 1 を入れる
+    let input = signals::tone(SAMPLE_RATE, 1000., 1., LEN);
+    let output = convert(AdcConfig::new(8, 20. * 0.5f64.log10()), &input);
+    let max = output.iter().fold(f64::MIN, |acc, v| acc.max(*v));
+    let min = output.iter().fold(f64::MAX, |acc, v| acc.min(*v));
+    assert!((max - 0.5 * 127. / 128.).abs() < 1e-9, "max: {max}");
+    assert!((min + 0.5).abs() < 1e-9, "min: {min}");
+}
+
+#[test]
+fn dither_linearizes_small_signals() {
+    // 0.3LSB の直流は、ディザなしでは 0 に丸められる
+    let step = 2f64.powi(-7);
+    let input = vec![0.3 * step; LEN];
+    let plain = convert(AdcConfig::new(8, 0.), &input);
+    assert!(plain.iter().all(|v| *v == 0.));
+    let dithered = convert(
+        AdcConfig {
+            dither: true,
+            ..AdcConfig::new(8, 0.)
+        },
+        &input,
+    );
+    let mean = dithered.iter().sum::<f64>() / LEN as f64 / step;
+    assert!((mean - 0.3).abs() < 0.01, "mean: {mean}LSB");
+}
+
+#[test]
+fn aperture_jitter_limits_snr() {
+    // ジッタ σ での S/N は -20 log10(2π f σ) (100kHz, 1ns で 64dB)
+    let input = signals::tone(SAMPLE_RATE, 100_000., 0.5, LEN);
+    let config = AdcConfig {
+        jitter: 1000.,
+        ..AdcConfig::new(32, 0.)
+    };
+    let measured = snr(&input, &convert(config, &input));
+    let expected = -20. * (TAU * 100_000. * 1e-9f64).log10();
+    assert!(
+        (measured - expected).abs() < 0.5,
+        "measured: {measured}dB, expected: {expected}dB"
+    );
+}
@@ -0,0 +1,146 @@
+use super::signals;
+use crate::modulation_modules::audio_processor::AudioProcessor;
+use crate::modulation_modules::composite::CompositeSignal;
+use crate::{AudioProcessorConfig, CompressorBands, ConfigError, FmRadioSimConfig};
+
+const SAMPLE_RATE: f64 = 48_000.;
+
+fn process(config: &AudioProcessorConfig, input: &[f64]) -> Vec<f64> {
+    let mut dut = AudioProcessor::new(SAMPLE_RATE, config);
+    input
+        .iter()
+        .map(|v| {
+            let (l, r) = dut.compress(*v, *v);
+            dut.limit(l, r).0
+        })
+        .collect()
+}
+/// AGC・コンプレッサ・ドライブを全て止めた設定
+fn bypass(bands: CompressorBands) -> AudioProcessorConfig {
+    AudioProcessorConfig {
+        agc_target: None,
+        threshold: 0.,
+        drive: 0.,
+        ..AudioProcessorConfig::new(bands)
+    }
+}
+
+#[test]
+fn transparent_below_threshold() {
+    let len = 9600;
+    let input: Vec<f64> = [50., 1000., 10_000.]
+        .iter()
+        .map(|f| signals::tone(SAMPLE_RATE, *f, 0.15, len))
+        .fold(vec![0.; len], |acc, tone| {
+            acc.iter().zip(&tone).map(|(a, b)| a + b).collect()
+        });
+    for bands in [CompressorBands::Three, CompressorBands::Five] {
+        let output = process(&bypass(bands), &input);
+        // 帯域に分けても和は入力に戻り、先読みの分だけ遅れる
+        let delay = (AudioProcessor::LOOKAHEAD * 1e-3 * SAMPLE_RATE) as usize - 1;
+        for (out, expected) in output[delay..].iter().zip(&input) {
+            assert!((out - expected).abs() < 1e-12, "{bands:?}");
+        }
+    }
+}
+
+#[test]
+fn compressor_bands_are_independent() {
+    let len = 24_000;
+    let bass = signals::tone(SAMPLE_RATE, 60., 0.9, len);
+    let treble = signals::tone(SAMPLE_RATE, 8000., 0.03, len);
+    let input: Vec<f64> = bass.iter().zip(&treble).map(|(a, b)| a + b).collect();
+    let config = AudioProcessorConfig {
+        threshold: -20.,
+        ..bypass(CompressorBands::Five)
+    };
+    let output = process(&config, &input);
+    let tail = &output[len - 4800..];
+    let bass_gain = 20. * (signals::tone_amplitude(tail, SAMPLE_RATE, 60.) / 0.9).log10();
+    let treble_gain = 20. * (signals::tone_amplitude(tail, SAMPLE_RATE, 8000.) / 0.03).log10();
+    // 低音は (-0.9dB - -20dB) * (1 - 1/4) 程度圧縮され、しきい値より小さい高音はそのまま通る
+    assert!(bass_gain < -10., "bass: {bass_gain}dB");
+    assert!(treble_gain.abs() < 0.5, "treble: {treble_gain}dB");
+}
+
+#[test]
+fn agc_brings_programme_to_target() {
+    let config = AudioProcessorConfig {
+        agc_target: Some(-20.),
+        agc_time: 100.,
+        agc_max_gain: 30.,
+        ..bypass(CompressorBands::Three)
+    };
+    let len = 48_000;
+    for level in [-40., -5.] {
+        // 正弦波の RMS は振幅の 1/√2
+        let input = signals::tone(
+            SAMPLE_RATE,
+            1000.,
+            10f64.powf(level / 20.) * 2f64.sqrt(),
+            len,
+        );
+        let output = process(&config, &input);
+        let rms = signals::tone_amplitude(&output[len - 4800..], SAMPLE_RATE, 1000.) / 2f64.sqrt();
+        let measured = 20. * rms.log10();
+        assert!(
+            (measured + 20.).abs() < 0.5,
+            "input: {level}dB, output: {measured}dB"
+        );
+    }
+}
+
+/// 雑音を入れたときのコンポジット信号のピーク
+fn composite_peak(tau: f64, processor: Option<&AudioProcessorConfig>) -> f64 {
+    let fs = 192_000.;
+    let mut composite = CompositeSignal::new(fs, 0.1, tau);
+    composite.set_audio_processor(processor);
+    let len = 96_000;
+    let l = signals::noise(1, 1., len);
+    let r = signals::noise(2, 1., len);
+    let mut buffer = vec![0.; len];
+    composite.process(&l, &r, &mut buffer);
+    buffer.iter().fold(0f64, |acc, v| acc.max(v.abs()))
+}
+
+#[test]
+fn limiter_prevents_overmodulation() {
+    let processor = AudioProcessorConfig::new(CompressorBands::Five);
+    for tau in [50., 75.] {
+        // プリエンファシスで高域が持ち上がるので、そのままでは最大周波数偏移を超える
+        let raw = composite_peak(tau, None);
+        assert!(raw > 1.5, "tau: {tau}, raw: {raw}");
+        let processed = composite_peak(tau, Some(&processor));
+        assert!(processed <= 1. + 1e-9, "tau: {tau}, processed: {processed}");
+        assert!(processed > 0.9, "tau: {tau}, processed: {processed}");
+    }
+}
+
+#[test]
+fn invalid_processor_is_rejected() {
+    let build = |processor| {
+        FmRadioSimConfig::builder(44100, 64, 79_500_000.)
+            .audio_processor(processor)
+            .build()
+    };
+    let processor = AudioProcessorConfig::new(CompressorBands::Three);
+    assert!(build(processor).is_ok());
+    assert_eq!(
+        build(AudioProcessorConfig {
+            ratio: 0.5,
+            ..processor
+        })
+        .unwrap_err(),
+        ConfigError::CompressorRatioOutOfRange(0.5)
+    );
+    assert!(matches!(
+        build(AudioProcessorConfig {
+            attack: 0.,
+            ..processor
+        }),
+        Err(ConfigError::NotPositive {
+            name: "processor_attack",
+            ..
+        })
+    ));
+}
@@ -0,0 +1,92 @@
+use crate::modulation_modules::channel::{Channel, ChannelConfig, NoiseLevel};
+use crate::{ConfigError, FmRadioSim, FmSimError};
+
+const RF_SAMPLE_RATE: f64 = 187_392_000.;
+const CARRIER_FREQ: f64 = 79_500_000.;
+const IF_BANDWIDTH: f64 = 1_860_000.;
+const LEN: usize = 1 << 16;
+
+fn noise_only(channel: &mut Channel) -> Vec<f64> {
+    let mut signal = vec![0.; LEN];
+    channel.process(&mut signal);
+    signal
+}
+
+/// 加えた雑音の電力を IF 帯域幅に換算すると、指定した C/N になる
+#[test]
+fn noise_matches_requested_cnr() {
+    for cnr in [0., 20., 40.] {
+        let config = ChannelConfig {
+            noise: Some(NoiseLevel::Cnr(cnr)),
+            seed: 1,
+            ..Default::default()
+        };
+        let mut channel = Channel::new(RF_SAMPLE_RATE, CARRIER_FREQ, IF_BANDWIDTH, &config);
+        let noise = noise_only(&mut channel);
+        let variance = noise.iter().map(|v| v * v).sum::<f64>() / LEN as f64;
+        let in_band = variance * 2. * IF_BANDWIDTH / RF_SAMPLE_RATE;
+        let measured = 10. * (0.5 / in_band).log10();
+        assert!(
+            (measured - cnr).abs() < 0.1,
+            "cnr: {cnr}, measured: {measured}"
+        );
+    }
+}
+
+#[test]
+fn input_level_is_converted_with_thermal_noise() {
+    // 20dBμV (75Ω) は 1.33pW、290K で 1.86MHz の熱雑音は 7.45fW
+    let level = NoiseLevel::InputLevel {
+        dbuv: 20.,
+        noise_figure: 0.,
+    };
+    assert!((level.cnr_db(IF_BANDWIDTH) - 22.529).abs() < 1e-3);
+    let level = NoiseLevel::InputLevel {
+        dbuv: 20.,
+        noise_figure: 6.,
+    };
+    assert!((level.cnr_db(IF_BANDWIDTH) - 16.529).abs() < 1e-3);
+}
+
+/// 同じ種からは同じ雑音になり、reset で最初からやり直す
+#[test]
+fn noise_is_deterministic() {
+    let config = ChannelConfig {
+        noise: Some(NoiseLevel::Cnr(10.)),
+        seed: 42,
+        ..Default::default()
+    };
+    let mut a = Channel::new(RF_SAMPLE_RATE, CARRIER_FREQ, IF_BANDWIDTH, &config);
+    let mut b = Channel::new(RF_SAMPLE_RATE, CARRIER_FREQ, IF_BANDWIDTH, &config);
+    let first = noise_only(&mut a);
+    assert_eq!(first, noise_only(&mut b));
+    a.reset();
+    assert_eq!(first, noise_only(&mut a));
+    a.set_seed(43);
+    assert_ne!(first, noise_only(&mut a));
+}
+
+#[test]
+fn rejects_invalid_noise_level() {
+    let mut sim = FmRadioSim::from(44100, 64, 79_500_000.).unwrap();
+    assert!(matches!(
+        sim.set_noise(Some(NoiseLevel::Cnr(f64::NAN))),
+        Err(FmSimError::Config(ConfigError::NotFinite {
+            name: "cnr",
+            ..
+        }))
+    ));
+    assert!(matches!(
+        sim.set_noise(Some(NoiseLevel::InputLevel {
+            dbuv: 30.,
+            noise_figure: -1.,
+        })),
+        Err(FmSimError::Config(ConfigError::NotPositive {
+            name: "noise_figure",
+            ..
+        }))
+    ));
+    assert_eq!(sim.config().channel().noise, None);
+    sim.set_noise(Some(NoiseLevel::Cnr(30.))).unwrap();
+    assert_eq!(sim.config().channel().noise, Some(NoiseLevel::Cnr(30.)));
+}
@@ -0,0 +1,72 @@
+use crate::{ConfigError, FmRadioSimConfig};
+
+#[test]
+fn default_config_matches_legacy_rates() {
+    let config = FmRadioSimConfig::builder(44100, 700, 79_500_000.)
+        .build()
+        .unwrap();
+    assert_eq!(config.composite_sample_rate(), 192_000);
+    assert_eq!(config.rf_sample_rate(), 187_392_000);
+    assert_eq!(config.intermediate_sample_rate(), 46_848_000);
+}
+
+#[test]
+fn rejects_inconsistent_parameters() {
+    let builder = FmRadioSimConfig::builder(44100, 700, 79_500_000.);
+    assert!(matches!(
+        builder.clone().rf_sample_rate(185_000_000).build(),
+        Err(ConfigError::RfRateNotMultiple { .. })
+    ));
+    assert!(matches!(
+        builder.clone().decimation(8).build(),
+        Err(ConfigError::UnsupportedDecimation(8))
+    ));
+    assert!(matches!(
+        builder.clone().composite_sample_rate(96_000).build(),
+        Err(ConfigError::AboveNyquist { .. })
+    ));
+    assert!(matches!(
+        builder.clone().rf_sample_rate(192_000 * 16 * 50).build(),
+        Err(ConfigError::AboveNyquist {
+            name: "carrier_freq",
+            ..
+        })
+    ));
+    assert!(matches!(
+        builder.clone().intermediate_freq(80_000_000.).build(),
+        Err(ConfigError::IntermediateAboveCarrier { .. })
+    ));
+    assert!(matches!(
+        builder.clone().pilot_level(1.).build(),
+        Err(ConfigError::PilotLevelOutOfRange(_))
+    ));
+    assert!(matches!(
+        builder.clone().deviation(0.).build(),
+        Err(ConfigError::NotPositive {
+            name: "deviation",
+            ..
+        })
+    ));
+    assert!(matches!(
+        builder.clone().emphasis_tau(f64::NAN).build(),
+        Err(ConfigError::NotFinite {
+            name: "tx_emphasis_tau",
+            ..
+        })
+    ));
+    assert!(matches!(
+        builder.clone().emphasis_tau(-50.).build(),
+        Err(ConfigError::Negative {
+            name: "tx_emphasis_tau",
+            ..
+        })
+    ));
+    assert!(builder.clone().emphasis_tau(0.).build().is_ok());
+    assert!(matches!(
+        FmRadioSimConfig::builder(44100, 0, 79_500_000.).build(),
+        Err(ConfigError::NotPositive {
+            name: "buffer_size",
+            ..
+        })
+    ));
+}
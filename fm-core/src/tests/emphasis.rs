@@ -0,0 +1,151 @@
+use super::signals;
+use crate::modulation_modules::composite::{CompositeSignal, RestoreSignal};
+use crate::modulation_modules::filter::{Deemphasis, Emphasis, FilterInfo};
+use crate::{ConfigError, FmRadioSimConfig};
+use std::f64::consts::{PI, TAU};
+
+const SAMPLE_RATE: f64 = 192_000.;
+const LEN: usize = 38_400;
+
+/// freq [Hz] の成分の振幅 (フィルタの過渡応答を避けて末尾の半分で測る)
+fn amplitude(signal: &[f64], freq: f64) -> f64 {
+    signals::tone_amplitude(&signal[signal.len() / 2..], SAMPLE_RATE, freq)
+}
+fn tone(freq: f64) -> Vec<f64> {
+    signals::tone(SAMPLE_RATE, freq, 1., LEN)
+}
+fn db(gain: f64) -> f64 {
+    20. * gain.log10()
+}
+/// |1 + jωτ| [dB]
+fn analog(freq: f64, tau: f64) -> f64 {
+    let wt = TAU * freq * tau * 1e-6;
+    10. * (1. + wt * wt).log10()
+}
+/// 周波数 corner [Hz] でプリワープした双一次変換の (1 + s/ωc) の利得 [dB]
+fn digital(freq: f64, corner: f64) -> f64 {
+    let ratio = (PI * freq / SAMPLE_RATE).tan() / (PI * corner / SAMPLE_RATE).tan();
+    10. * (1. + ratio * ratio).log10()
+}
+
+#[test]
+fn emphasis_matches_analog_response() {
+    for tau in [50., 75.] {
+        let corner = 1e6 / (TAU * tau);
+        for freq in [100., 1000., corner, 10_000., 15_000.] {
+            let emphasis = Emphasis::new(SAMPLE_RATE, tau);
+            let deemphasis = Deemphasis::new(SAMPLE_RATE, tau);
+            let (mut info_e, mut info_d) = (FilterInfo::default(), FilterInfo::default());
+            let input = tone(freq);
+            let pre: Vec<f64> = input
+                .iter()
+                .map(|v| emphasis.process_without_buffer(*v, &mut info_e))
+                .collect();
+            let de: Vec<f64> = input
+                .iter()
+                .map(|v| deemphasis.process_without_buffer(*v, &mut info_d))
+                .collect();
+            let expected_pre = analog(freq, tau) - analog(freq, 1e6 / (TAU * Emphasis::LIMIT_FREQ));
+            let expected_de = -analog(freq, tau);
+            let (measured_pre, measured_de) = (db(amplitude(&pre, freq)), db(amplitude(&de, freq)));
+            // プリワープした角周波数では一致する。15kHz での双一次変換の誤差は 0.3dB 程度
+            let tolerance = if freq == corner { 0.01 } else { 0.4 };
+            assert!(
+                (measured_pre - expected_pre).abs() < tolerance
+                    && (measured_de - expected_de).abs() < tolerance,
+                "tau: {tau}, freq: {freq}, pre: {measured_pre} ({expected_pre}), de: {measured_de} ({expected_de})"
+            );
+        }
+    }
+}
+
+#[test]
+fn zero_tau_is_flat() {
+    let emphasis = Emphasis::new(SAMPLE_RATE, 0.);
+    let deemphasis = Deemphasis::new(SAMPLE_RATE, 0.);
+    let (mut info_e, mut info_d) = (FilterInfo::default(), FilterInfo::default());
+    for v in tone(12_345.) {
+        assert_eq!(emphasis.process_without_buffer(v, &mut info_e), v);
+        assert_eq!(deemphasis.process_without_buffer(v, &mut info_d), v);
+    }
+}
+
+/// 送信側 tx、受信側 rx の時定数で、コンポジット信号を経由した freq [Hz] のトーンの振幅
+fn chain_level(tx: f64, rx: f64, freq: f64) -> f64 {
+    let mut composite = CompositeSignal::new(SAMPLE_RATE, 0.1, tx);
+    let mut restore = RestoreSignal::new(SAMPLE_RATE, 0.1, rx, 1.);
+    let input = tone(freq);
+    let mut signal = vec![0.; LEN];
+    composite.process(&input, &input, &mut signal);
+    let (mut l, mut r) = (vec![0.; LEN], vec![0.; LEN]);
+    restore.process(&signal, &mut l, &mut r);
+    amplitude(&l, freq)
+}
+
+#[test]
+fn tx_rx_mismatch_tilts_response() {
+    let corner = |tau: f64| 1e6 / (TAU * tau);
+    // プリエンファシスの持ち上げを止める極は受信側では打ち消さない
+    let limit = Emphasis::LIMIT_FREQ.min(SAMPLE_RATE * 0.45);
+    let pre = |freq: f64, tau: f64| {
+        if tau > 0. {
+            digital(freq, corner(tau)) - digital(freq, limit)
+        } else {
+            0.
+        }
+    };
+    let de = |freq: f64, tau: f64| {
+        if tau > 0. {
+            -digital(freq, corner(tau))
+        } else {
+            0.
+        }
+    };
+    for freq in [1000., 5000., 10_000., 15_000.] {
+        let reference = chain_level(0., 0., freq);
+        for (tx, rx) in [
+            (50., 50.),
+            (75., 75.),
+            (75., 50.),
+            (50., 75.),
+            (0., 50.),
+            (75., 0.),
+        ] {
+            let measured = db(chain_level(tx, rx, freq) / reference);
+            let expected = pre(freq, tx) + de(freq, rx);
+            assert!(
+                (measured - expected).abs() < 0.02,
+                "tx: {tx}, rx: {rx}, freq: {freq}, {measured}dB (expected {expected}dB)"
+            );
+        }
+    }
+}
+
+#[test]
+fn tx_and_rx_tau_are_independent() {
+    let builder = || FmRadioSimConfig::builder(44100, 64, 79_500_000.);
+    let config = builder().build().unwrap();
+    assert_eq!(config.tx_emphasis_tau(), 50.);
+    assert_eq!(config.rx_emphasis_tau(), 50.);
+    let config = builder()
+        .emphasis_tau(75.)
+        .rx_emphasis_tau(0.)
+        .build()
+        .unwrap();
+    assert_eq!(config.tx_emphasis_tau(), 75.);
+    assert_eq!(config.rx_emphasis_tau(), 0.);
+    assert_eq!(
+        builder().rx_emphasis_tau(-50.).build().unwrap_err(),
+        ConfigError::Negative {
+            name: "rx_emphasis_tau",
+            value: -50.
+        }
+    );
+    assert!(matches!(
+        builder().tx_emphasis_tau(f64::INFINITY).build(),
+        Err(ConfigError::NotFinite {
+            name: "tx_emphasis_tau",
+            ..
+        })
+    ));
+}
@@ -0,0 +1,66 @@
+use crate::modulation_modules::channel::fading::Fader;
+use crate::modulation_modules::channel::Rng;
+use crate::Fading;
+use std::f64::consts::TAU;
+
+// RF のレートでは統計を取るのに時間がかかるので、低いレートで
+// ドップラー周波数が 200Hz になる速度を与える (ω0 = π/2)
+const SAMPLE_RATE: f64 = 1_000_000.;
+const CARRIER_FREQ: f64 = 250_000.;
+const SPEED: f64 = 200. * 299_792_458. / CARRIER_FREQ * 3.6;
+const LEN: usize = 4_000_000;
+// 包絡線を求める窓 (搬送波の整数周期)
+const WINDOW: usize = 100;
+
+/// 搬送波を通したときの、窓ごとの |h|^2
+fn power_gain(fading: Fading, seed: u64) -> Vec<f64> {
+    let mut fader = Fader::new(SAMPLE_RATE, CARRIER_FREQ, Some(fading), Rng::new(seed));
+    let w0 = TAU * CARRIER_FREQ / SAMPLE_RATE;
+    let mut signal: Vec<f64> = (0..LEN).map(|n| (w0 * n as f64).cos()).collect();
+    for block in signal.chunks_mut(7000) {
+        fader.process(block);
+    }
+    signal
+        .chunks(WINDOW)
+        .map(|w| 2. * w.iter().map(|v| v * v).sum::<f64>() / WINDOW as f64)
+        .collect()
+}
+
+fn mean(v: &[f64]) -> f64 {
+    v.iter().sum::<f64>() / v.len() as f64
+}
+/// |h|^2 が -10dB を下回る割合
+fn outage(v: &[f64]) -> f64 {
+    v.iter().filter(|&&p| p < 0.1).count() as f64 / v.len() as f64
+}
+
+#[test]
+fn rayleigh_envelope_statistics() {
+    let power = power_gain(Fading::rayleigh(SPEED), 1);
+    let (mean, outage) = (mean(&power), outage(&power));
+    // Rayleigh では |h|^2 が指数分布になり、P(|h|^2 < 0.1) = 1 - e^-0.1 = 0.095
+    assert!((mean - 1.).abs() < 0.15, "mean: {mean}");
+    assert!((outage - 0.095).abs() < 0.03, "outage: {outage}");
+}
+
+#[test]
+fn rician_fades_less_deeply() {
+    let power = power_gain(Fading::rician(SPEED, 10.), 1);
+    let (mean, outage) = (mean(&power), outage(&power));
+    assert!((mean - 1.).abs() < 0.1, "mean: {mean}");
+    assert!(outage < 0.005, "outage: {outage}");
+}
+
+#[test]
+fn fading_is_deterministic() {
+    let fading = Fading::rayleigh(SPEED);
+    assert_eq!(power_gain(fading, 7), power_gain(fading, 7));
+    assert_ne!(power_gain(fading, 7), power_gain(fading, 8));
+}
+
+#[test]
+fn doppler_follows_carrier() {
+    // 100km/h、79.5MHz で約 7.4Hz
+    let doppler = Fading::rayleigh(100.).doppler_freq(79_500_000.);
+    assert!((doppler - 7.366).abs() < 1e-3, "doppler: {doppler}");
+}
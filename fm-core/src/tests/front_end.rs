@@ -0,0 +1,71 @@
+use super::signals;
+use crate::modulation_modules::front_end::FrontEnd;
+use crate::FrontEndConfig;
+use std::f64::consts::TAU;
+
+const SAMPLE_RATE: f64 = 1_000_000.;
+const LEN: usize = 100_000;
+// 10Hz の整数倍の周波数は LEN サンプルでちょうど周期が揃う
+const F1: f64 = 100_000.;
+const F2: f64 = 110_000.;
+
+/// 電力 level [dB] (振幅 1 の cos が 0dB) の 2 波を通す
+fn two_tone(config: FrontEndConfig, level: f64) -> Vec<f64> {
+    let amplitude = 10f64.powf(level / 20.);
+    let mut signal: Vec<f64> = (0..LEN)
+        .map(|n| {
+            let t = n as f64 / SAMPLE_RATE;
+            amplitude * ((TAU * F1 * t).cos() + (TAU * F2 * t).cos())
+        })
+        .collect();
+    let mut dut = FrontEnd::new(Some(config));
+    for block in signal.chunks_mut(4096) {
+        dut.process(block);
+    }
+    signal
+}
+
+/// 周波数 freq の成分の電力 [dB]
+fn level_at(signal: &[f64], freq: f64) -> f64 {
+    20. * signals::tone_amplitude(signal, SAMPLE_RATE, freq).log10()
+}
+
+#[test]
+fn third_order_intermodulation_follows_iip3() {
+    // 入力 -10dB、IIP3 20dB では IM3 は基本波より 2 * (20 - (-10)) = 60dB 低い
+    let signal = two_tone(FrontEndConfig::polynomial(20.), -10.);
+    let fundamental = level_at(&signal, F1);
+    for freq in [2. * F1 - F2, 2. * F2 - F1] {
+        let im3 = level_at(&signal, freq) - fundamental;
+        assert!((im3 + 60.).abs() < 0.1, "{freq}Hz: {im3}dBc");
+    }
+}
+
+#[test]
+fn second_order_intermodulation_follows_iip2() {
+    let config = FrontEndConfig {
+        iip2: Some(40.),
+        ..FrontEndConfig::polynomial(60.)
+    };
+    let signal = two_tone(config, -10.);
+    let im2 = level_at(&signal, F2 - F1) - level_at(&signal, F1);
+    assert!((im2 + 50.).abs() < 0.1, "{im2}dBc");
+    // 2 次の歪みが無ければ差の周波数には何も出ない
+    let signal = two_tone(FrontEndConfig::polynomial(60.), -10.);
+    assert!(level_at(&signal, F2 - F1) < -200.);
+}
+
+#[test]
+fn gain_compresses_at_p1db() {
+    for config in [FrontEndConfig::polynomial(15.), FrontEndConfig::tanh(5.)] {
+        let p1db = config.p1db();
+        let amplitude = 10f64.powf(p1db / 20.);
+        let mut signal: Vec<f64> = (0..LEN)
+            .map(|n| amplitude * (TAU * F1 * n as f64 / SAMPLE_RATE).cos())
+            .collect();
+        FrontEnd::new(Some(config)).process(&mut signal);
+        let gain = level_at(&signal, F1) - p1db;
+        assert!((gain + 1.).abs() < 0.01, "{config:?}: {gain}dB");
+        assert!((8.5..10.).contains(&(config.iip3() - p1db)), "{config:?}");
+    }
+}
@@ -0,0 +1,123 @@
+use super::signals;
+use crate::modulation_modules::if_stage::IfStage;
+use crate::{Agc, ChannelConfig, FmRadioSimConfig, Limiter, MultipathTap};
+use std::f64::consts::TAU;
+
+const SAMPLE_RATE: f64 = 1_000_000.;
+// 10Hz の整数倍の周波数は LEN サンプルでちょうど周期が揃う
+const LEN: usize = 100_000;
+/// 高調波の折り返しが基本波に重ならない周波数
+const IF_FREQ: f64 = 123_450.;
+
+fn cos(amplitude: impl Fn(usize) -> f64) -> Vec<f64> {
+    (0..LEN)
+        .map(|n| amplitude(n) * (TAU * IF_FREQ * n as f64 / SAMPLE_RATE).cos())
+        .collect()
+}
+
+/// IF_FREQ の成分の振幅
+fn amplitude(signal: &[f64]) -> f64 {
+    signals::tone_amplitude(signal, SAMPLE_RATE, IF_FREQ)
+}
+
+fn process(agc: Option<Agc>, limiter: Option<Limiter>, input: &[f64]) -> Vec<f64> {
+    let mut dut = IfStage::new(SAMPLE_RATE, IF_FREQ, agc, limiter);
+    let mut output = input.to_vec();
+    for block in output.chunks_mut(4096) {
+        dut.process(block);
+    }
+    output
+}
+
+#[test]
+fn hard_limiter_normalizes_amplitude() {
+    for input_amplitude in [0.01, 1., 30.] {
+        let output = process(None, Some(Limiter::Hard), &cos(|_| input_amplitude));
+        let measured = amplitude(&output);
+        assert!(
+            (measured - 1.).abs() < 0.02,
+            "input: {input_amplitude}, output: {measured}"
+        );
+    }
+}
+
+#[test]
+fn soft_limiter_is_linear_below_threshold() {
+    let limiter = Some(Limiter::Soft { threshold: 0. });
+    // しきい値より 40dB 小さい入力は π/4 倍で比例して通る
+    let small = amplitude(&process(None, limiter, &cos(|_| 0.01))) / 0.01;
+    assert!(
+        (small - std::f64::consts::FRAC_PI_4).abs() < 1e-3,
+        "gain: {small}"
+    );
+    // 十分大きい入力はハードリミッタと同じく基本波が 1 になる
+    let large = amplitude(&process(None, limiter, &cos(|_| 100.)));
+    assert!((large - 1.).abs() < 0.02, "output: {large}");
+}
+
+#[test]
+fn agc_attack_is_faster_than_decay() {
+    // 50μs で立ち上がり、5ms で戻る
+    let agc = Agc::new(0.05, 5.);
+    // 1 -> 0.1 -> 1 と振幅を切り替える
+    let third = LEN / 3 / 100 * 100;
+    let input = cos(|n| {
+        if (third..2 * third).contains(&n) {
+            0.1
+        } else {
+            1.
+        }
+    });
+    let output = process(Some(agc), None, &input);
+    let window = |start: usize| amplitude(&output[start..start + 1000]);
+    // 切り替えてから十分時間が経てば、どちらの振幅でも 1 に揃う
+    for start in [third - 1000, 2 * third - 1000, LEN - 1000] {
+        let measured = window(start);
+        assert!((measured - 1.).abs() < 0.02, "at {start}: {measured}");
+    }
+    // 振幅が下がった直後は利得が追いつかない
+    let after_drop = window(third + 500);
+    assert!(after_drop < 0.5, "after drop: {after_drop}");
+    // 振幅が上がった直後はすぐに利得が下がる
+    let after_rise = window(2 * third + 500);
+    assert!((after_rise - 1.).abs() < 0.02, "after rise: {after_rise}");
+}
+
+/// 伝搬路の利得 gain [倍] で受信した 1kHz のトーンの振幅
+fn audio_tone_level(gain: f64, agc: Option<Agc>, limiter: Option<Limiter>) -> f64 {
+    let mut config = FmRadioSimConfig::builder(44100, 64, 79_500_000.).channel(ChannelConfig {
+        multipath: vec![MultipathTap::new(0., gain, 0.)],
+        ..Default::default()
+    });
+    if let Some(agc) = agc {
+        config = config.agc(agc);
+    }
+    if let Some(limiter) = limiter {
+        config = config.limiter(limiter);
+    }
+    signals::audio_tone_level(config)
+}
+
+#[test]
+fn if_stage_keeps_audio_level_under_attenuation() {
+    let diff = |agc, limiter| {
+        let reference = audio_tone_level(1., agc, limiter);
+        let attenuated = audio_tone_level(0.25, agc, limiter);
+        assert!(reference > 0.25, "reference: {reference}");
+        20. * (attenuated / reference).log10()
+    };
+    // 何もしなければ復調出力は振幅の 2 乗で下がる
+    let plain = diff(None, None);
+    assert!(plain < -6., "plain: {plain}dB");
+    for (agc, limiter) in [
+        (None, Some(Limiter::Hard)),
+        (None, Some(Limiter::Soft { threshold: -30. })),
+        (Some(Agc::new(0.01, 0.1)), None),
+    ] {
+        let measured = diff(agc, limiter);
+        assert!(
+            measured.abs() < 0.5,
+            "agc: {agc:?}, limiter: {limiter:?}, diff: {measured}dB"
+        );
+    }
+}
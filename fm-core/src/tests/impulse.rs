@@ -0,0 +1,74 @@
+use crate::modulation_modules::channel::impulse::ImpulseGenerator;
+use crate::modulation_modules::channel::Rng;
+use crate::{ImpulseNoise, PulseAmplitude, PulseShape, PulseTiming};
+
+const SAMPLE_RATE: f64 = 1_000_000.;
+const LEN: usize = 1_000_000;
+
+fn pulses(noise: ImpulseNoise, seed: u64) -> Vec<f64> {
+    let mut dut = ImpulseGenerator::new(SAMPLE_RATE, Some(noise), Rng::new(seed));
+    let mut signal = vec![0.; LEN];
+    for block in signal.chunks_mut(3000) {
+        dut.process(block);
+    }
+    signal
+}
+
+fn impulse(timing: PulseTiming) -> ImpulseNoise {
+    ImpulseNoise {
+        timing,
+        amplitude: PulseAmplitude::Fixed(2.),
+        shape: PulseShape::Impulse,
+    }
+}
+
+#[test]
+fn periodic_pulses_are_evenly_spaced() {
+    let signal = pulses(impulse(PulseTiming::Periodic { rate: 10_000. }), 1);
+    let positions: Vec<usize> = (0..LEN).filter(|&n| signal[n] != 0.).collect();
+    assert_eq!(positions.len(), LEN / 100 - 1);
+    assert!(positions.windows(2).all(|w| w[1] - w[0] == 100));
+    assert!(positions.iter().all(|&n| signal[n].abs() == 2.));
+}
+
+#[test]
+fn random_pulses_follow_mean_rate() {
+    let signal = pulses(impulse(PulseTiming::Random { rate: 5_000. }), 1);
+    let count = signal.iter().filter(|v| **v != 0.).count() as f64;
+    // 1 秒間に平均 5000 個 (標準偏差 約 71)
+    assert!((count - 5000.).abs() < 300., "count: {count}");
+}
+
+#[test]
+fn shaped_pulse_decays() {
+    let noise = ImpulseNoise {
+        timing: PulseTiming::Periodic { rate: 1_000. },
+        amplitude: PulseAmplitude::Fixed(1.),
+        shape: PulseShape::Exponential { decay: 10. },
+    };
+    let signal = pulses(noise, 1);
+    // 最初のパルスは 1000 サンプル目、時定数 10 サンプル
+    let peak = signal[1000];
+    assert_eq!(peak.abs(), 1.);
+    for k in [5, 10, 30] {
+        let expected = peak * (-(k as f64) / 10.).exp();
+        assert!((signal[1000 + k] - expected).abs() < 1e-12, "k: {k}");
+    }
+}
+
+#[test]
+fn impulse_noise_is_deterministic() {
+    let noise = ImpulseNoise {
+        timing: PulseTiming::Random { rate: 2_000. },
+        amplitude: PulseAmplitude::LogNormal {
+            median: 0.5,
+            sigma: 6.,
+        },
+        shape: PulseShape::DampedSine {
+            freq: 100_000.,
+            decay: 20.,
+        },
+    };
+    assert_eq!(pulses(noise, 3), pulses(noise, 3));
+    assert_ne!(pulses(noise, 3), pulses(noise, 4));
+}
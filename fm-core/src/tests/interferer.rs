@@ -0,0 +1,87 @@
+use super::signals;
+use crate::modulation_modules::channel::interferer::Interferers;
+use crate::{ChannelConfig, FmRadioSimConfig, Interferer, InterfererSource};
+use std::sync::Arc;
+
+const CARRIER_FREQ: f64 = 79_500_000.;
+const COMPOSITE_BUFFER_SIZE: usize = 160;
+const BLOCKS: usize = 4;
+
+fn config(interferers: Vec<Interferer>) -> FmRadioSimConfig {
+    FmRadioSimConfig::builder(44100, 64, CARRIER_FREQ)
+        .channel(ChannelConfig {
+            interferers,
+            ..Default::default()
+        })
+        .build()
+        .unwrap()
+}
+
+/// 希望局なし (0) に妨害局だけを足した RF 信号
+fn interference_only(config: &FmRadioSimConfig) -> Vec<f64> {
+    let mut dut = Interferers::new(config, COMPOSITE_BUFFER_SIZE);
+    let rf_len = COMPOSITE_BUFFER_SIZE * config.rf_sample_rate() / config.composite_sample_rate();
+    let mut signal = Vec::with_capacity(rf_len * BLOCKS);
+    for _ in 0..BLOCKS {
+        let mut block = vec![0.; rf_len];
+        dut.process(&mut block);
+        signal.extend(block);
+    }
+    signal
+}
+
+#[test]
+fn interferer_power_follows_level() {
+    for level in [0., -6., -20.] {
+        let config = config(vec![Interferer::new(
+            InterfererSource::Noise { amplitude: 0.3 },
+            200_000.,
+            level,
+        )]);
+        let signal = interference_only(&config);
+        let power = signal.iter().map(|v| v * v).sum::<f64>() / signal.len() as f64;
+        let measured = 10. * (power / 0.5).log10();
+        assert!(
+            (measured - level).abs() < 0.05,
+            "level: {level}, measured: {measured}"
+        );
+    }
+}
+
+#[test]
+fn interferer_lands_at_offset() {
+    // 無音ではパイロット (周波数偏移 7.5kHz) のみで変調され、
+    // 搬送波の振幅は J0(7.5k/19k) = 0.962 になる
+    let config = config(vec![Interferer::new(
+        InterfererSource::Tone {
+            freq: 1000.,
+            amplitude: 0.,
+        },
+        -100_000.,
+        -6.,
+    )]);
+    let signal = interference_only(&config);
+    let fs = config.rf_sample_rate() as f64;
+    let gain = 10f64.powf(-6. / 20.);
+    let at_offset = signals::tone_amplitude(&signal, fs, CARRIER_FREQ - 100_000.);
+    assert!(
+        (at_offset - 0.962 * gain).abs() < 0.01,
+        "at offset: {at_offset}"
+    );
+    assert!(signals::tone_amplitude(&signal, fs, CARRIER_FREQ) < 1e-3);
+}
+
+#[test]
+fn sample_source_is_deterministic() {
+    let left: Arc<[f32]> = (0..441).map(|n| (n as f32 * 0.1).sin() * 0.5).collect();
+    let source = InterfererSource::Samples {
+        sample_rate: 44100.,
+        right: left.clone(),
+        left,
+    };
+    let config = config(vec![
+        Interferer::new(source, 0., -10.),
+        Interferer::new(InterfererSource::Noise { amplitude: 0.2 }, 200_000., -3.),
+    ]);
+    assert_eq!(interference_only(&config), interference_only(&config));
+}
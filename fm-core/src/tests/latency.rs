@@ -0,0 +1,78 @@
+use super::signals;
+use crate::{AudioProcessorConfig, CompressorBands, FmRadioSim, FmRadioSimConfig};
+
+const AUDIO_SAMPLE_RATE: usize = 44100;
+const BUFFER_SIZE: usize = 64;
+const LEN: usize = BUFFER_SIZE * 16;
+
+/// 入力と出力の相互相関が最大になる遅延
+fn estimate_lag(input: &[f32], output: &[f32], max_lag: usize) -> usize {
+    (0..max_lag)
+        .map(|lag| {
+            let corr = input
+                .iter()
+                .zip(&output[lag..])
+                .map(|(x, y)| (*x * *y) as f64)
+                .sum::<f64>();
+            (lag, corr)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap()
+        .0
+}
+
+#[test]
+fn reported_latency_matches_measured_lag() {
+    let source = signals::noise(7, 0.3, LEN)
+        .into_iter()
+        .map(|v| v as f32)
+        .collect::<Vec<_>>();
+    for threaded in [false, true] {
+        let mut sim = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, 79_500_000.).unwrap();
+        let mut latency = sim.latency_samples();
+        if threaded {
+            sim.init_thread();
+        } else {
+            latency -= sim.pipeline_latency();
+        }
+        // 遅延分の無音を足して、入力の最後まで出力させる
+        let mut input = source.clone();
+        input.resize(LEN + latency, 0.);
+        let (mut dst_l, mut dst_r) = (vec![0f32; input.len()], vec![0f32; input.len()]);
+        if threaded {
+            sim.process(&input, &input, &mut dst_l, &mut dst_r).unwrap();
+        } else {
+            sim.process_serial(&input, &input, &mut dst_l, &mut dst_r)
+                .unwrap();
+        }
+        let lag = estimate_lag(&source, &dst_l, latency + BUFFER_SIZE);
+        println!("threaded: {threaded}, reported: {latency}, measured: {lag}");
+        assert!(lag.abs_diff(latency) <= 1, "threaded: {threaded}");
+    }
+}
+
+#[test]
+fn reported_latency_includes_audio_processor() {
+    let source = signals::noise(7, 0.3, LEN)
+        .into_iter()
+        .map(|v| v as f32)
+        .collect::<Vec<_>>();
+    let config = FmRadioSimConfig::builder(AUDIO_SAMPLE_RATE, BUFFER_SIZE, 79_500_000.)
+        .audio_processor(AudioProcessorConfig::new(CompressorBands::Three))
+        .build()
+        .unwrap();
+    let mut sim = FmRadioSim::with_config(config).unwrap();
+    let plain = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, 79_500_000.).unwrap();
+    // 先読みのリミッタの分 (1.5ms) だけ遅れる
+    let extra = sim.latency_samples() - plain.latency_samples();
+    assert!(extra.abs_diff(66) <= 1, "extra: {extra}");
+    let latency = sim.latency_samples() - sim.pipeline_latency();
+    let mut input = source.clone();
+    input.resize(LEN + latency, 0.);
+    let (mut dst_l, mut dst_r) = (vec![0f32; input.len()], vec![0f32; input.len()]);
+    sim.process_serial(&input, &input, &mut dst_l, &mut dst_r)
+        .unwrap();
+    let lag = estimate_lag(&source, &dst_l, latency + BUFFER_SIZE);
+    println!("reported: {latency}, measured: {lag}");
+    assert!(lag.abs_diff(latency) <= 1);
+}
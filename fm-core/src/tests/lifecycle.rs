@@ -0,0 +1,395 @@
+use super::signals;
+use crate::{
+    AdcConfig, Agc, Drift, Fading, FmRadioSim, FmSimError, FrontEndConfig, ImpulseNoise,
+    Interferer, InterfererSource, Limiter, MpxConfig, MultipathTap, OscillatorConfig, RdsConfig,
+    Result,
+};
+use std::ops::Range;
+
+const AUDIO_SAMPLE_RATE: usize = 44100;
+const BUFFER_SIZE: usize = 64;
+const CARRIER_FREQ: f64 = 79_500_000.;
+
+fn run_blocks(sim: &mut FmRadioSim, blocks: usize) {
+    let input = vec![0.25f32; BUFFER_SIZE];
+    let mut dst_l = vec![0f32; BUFFER_SIZE];
+    let mut dst_r = vec![0f32; BUFFER_SIZE];
+    for _ in 0..blocks {
+        sim.process(&input, &input, &mut dst_l, &mut dst_r).unwrap();
+    }
+}
+
+#[test]
+fn create_and_drop_many_simulators() {
+    for _ in 0..8 {
+        let mut sim = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, CARRIER_FREQ).unwrap();
+        sim.init_thread();
+        run_blocks(&mut sim, 3);
+        // Drop で停止・join される
+    }
+}
+
+#[test]
+fn shutdown_and_reinit() {
+    let mut sim = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, CARRIER_FREQ).unwrap();
+    // 未初期化での shutdown は何もしない
+    sim.shutdown().unwrap();
+    for _ in 0..3 {
+        sim.init_thread();
+        assert!(sim.is_running());
+        run_blocks(&mut sim, 4);
+        sim.shutdown().unwrap();
+        assert!(!sim.is_running());
+    }
+    sim.reinit_thread().unwrap();
+    run_blocks(&mut sim, 2);
+    sim.reinit_thread().unwrap();
+    run_blocks(&mut sim, 2);
+}
+
+#[test]
+fn reports_misuse_as_errors() {
+    let mut sim = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, CARRIER_FREQ).unwrap();
+    let input = vec![0f32; BUFFER_SIZE];
+    let mut dst = vec![0f32; BUFFER_SIZE];
+    let mut dst_short = vec![0f32; BUFFER_SIZE - 1];
+    assert!(matches!(
+        sim.process(&input, &input, &mut dst.clone(), &mut dst),
+        Err(FmSimError::ThreadsNotInitialized)
+    ));
+    assert!(matches!(
+        sim.process_serial(&input, &input, &mut dst.clone(), &mut dst_short),
+        Err(FmSimError::LengthMismatch {
+            expected: BUFFER_SIZE,
+            actual: 63
+        })
+    ));
+    sim.init_thread();
+    assert!(matches!(
+        sim.process(&input[1..], &input, &mut dst.clone(), &mut dst),
+        Err(FmSimError::LengthMismatch { .. })
+    ));
+    // 長さの不一致ではバリアを待たないので、その後も処理を続けられる
+    run_blocks(&mut sim, 2);
+    assert!(matches!(
+        FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, 1_000_000.),
+        Err(FmSimError::Config(_))
+    ));
+}
+
+/// ステージの失敗は報告した後に消え、ステージが直れば処理を続けられる
+#[test]
+fn recovers_after_stage_error() {
+    let mut sim = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, CARRIER_FREQ).unwrap();
+    let input = vec![0.25f32; BUFFER_SIZE];
+    let (mut dst_l, mut dst_r) = (vec![0f32; BUFFER_SIZE], vec![0f32; BUFFER_SIZE]);
+    sim.init_thread();
+    // ステージを持ったまま panic させて、ロックを poison にする
+    let modulator = &sim.modulator;
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let _guard = modulator.lock().unwrap();
+            panic!("poison the modulate stage");
+        })
+        .join()
+        .unwrap_err();
+    });
+    // ワーカーの失敗は、遅くとも次の呼び出しで報告される
+    let errors = (0..2)
+        .filter(|_| {
+            matches!(
+                sim.process(&input, &input, &mut dst_l, &mut dst_r),
+                Err(FmSimError::PoisonedStage("modulate"))
+            )
+        })
+        .count();
+    assert!(errors > 0);
+    sim.modulator.clear_poison();
+    // poison を解く前に始まったブロックの失敗は 1 回だけ報告される
+    let _ = sim.process(&input, &input, &mut dst_l, &mut dst_r);
+    run_blocks(&mut sim, 3);
+}
+
+/// 処理中に変更できる設定
+struct RuntimeSetting {
+    name: &'static str,
+    /// 基準と変更するシミュレータの両方に、起動前に設定しておくこと
+    prepare: fn(&mut FmRadioSim) -> Result<()>,
+    set: fn(&mut FmRadioSim) -> Result<()>,
+    /// 基準と同じ設定に戻す
+    clear: fn(&mut FmRadioSim) -> Result<()>,
+    /// 拒否される設定 (None: 拒否される値がない)
+    invalid: Option<fn(&mut FmRadioSim) -> Result<()>>,
+    expected: Change,
+}
+/// 設定を変えている間の出力の変化
+#[derive(Debug)]
+enum Change {
+    /// 1kHz のトーンの振幅の変化 [dB] の範囲
+    Level(Range<f64>),
+    /// 基準との差の電力 (トーンの電力との比) [dB] の下限
+    Noise(f64),
+    /// 出力は変わらない (受信状態の取得だけに効く設定)
+    Unchanged,
+}
+
+const INTERFERER_TONE: InterfererSource = InterfererSource::Tone {
+    freq: 400.,
+    amplitude: 0.5,
+};
+
+fn no_condition(_: &mut FmRadioSim) -> Result<()> {
+    Ok(())
+}
+/// 搬送波を 12dB 減衰させる (復調出力は 24dB 下がる)
+fn attenuate(sim: &mut FmRadioSim) -> Result<()> {
+    sim.set_multipath(&[MultipathTap::new(0., 0.25, 0.)])
+}
+
+fn impaired_oscillator() -> OscillatorConfig {
+    OscillatorConfig {
+        offset_ppm: 10.,
+        drift: Some(Drift {
+            ppm: -5.,
+            time_constant: 30.,
+        }),
+        phase_noise: vec![(1_000., -80.), (100_000., -120.)],
+    }
+}
+
+// 位相雑音のマスクが離調周波数の昇順に並んでいない
+fn unsorted_oscillator() -> OscillatorConfig {
+    OscillatorConfig {
+        phase_noise: vec![(10_000., -90.), (1_000., -80.)],
+        ..Default::default()
+    }
+}
+
+/// 処理中に設定を変えると出力が変わり、設定を戻すと基準と同じ出力に戻る
+#[test]
+fn runtime_settings_change_output() {
+    let settings = [
+        RuntimeSetting {
+            name: "carrier_freq",
+            prepare: no_condition,
+            // 同調がずれた分は復調出力の直流分になる
+            set: |sim| sim.set_carrier_freq(CARRIER_FREQ + 100_000.),
+            clear: |sim| sim.set_carrier_freq(CARRIER_FREQ),
+            // RF のサンプルレートのナイキスト周波数を超える
+            invalid: Some(|sim| sim.set_carrier_freq(100_000_000.)),
+            expected: Change::Noise(0.),
+        },
+        RuntimeSetting {
+            name: "tuned_freq",
+            prepare: no_condition,
+            set: |sim| sim.set_tuned_freq(CARRIER_FREQ + 100_000.),
+            clear: |sim| sim.set_tuned_freq(CARRIER_FREQ),
+            // 局部発振が負になる
+            invalid: Some(|sim| sim.set_tuned_freq(10_000_000.)),
+            expected: Change::Noise(0.),
+        },
+        RuntimeSetting {
+            name: "multipath",
+            prepare: no_condition,
+            // 遅延波との干渉で復調出力が歪む
+            set: |sim| sim.set_multipath(&MultipathTap::urban()),
+            clear: |sim| sim.set_multipath(&[]),
+            invalid: Some(|sim| sim.set_multipath(&[MultipathTap::new(-1., 1., 0.)])),
+            expected: Change::Noise(-20.),
+        },
+        RuntimeSetting {
+            name: "fading",
+            prepare: no_condition,
+            set: |sim| sim.set_fading(Some(Fading::rayleigh(60.))),
+            clear: |sim| sim.set_fading(None),
+            invalid: Some(|sim| sim.set_fading(Some(Fading::rician(60., -1.)))),
+            expected: Change::Noise(-30.),
+        },
+        RuntimeSetting {
+            name: "interferers",
+            prepare: no_condition,
+            // 同一周波数の妨害局のトーンが混ざる
+            set: |sim| sim.set_interferers(&[Interferer::new(INTERFERER_TONE, 0., -20.)]),
+            clear: |sim| sim.set_interferers(&[]),
+            // 搬送波 + offset がナイキスト周波数を超える
+            invalid: Some(|sim| {
+                sim.set_interferers(&[Interferer::new(INTERFERER_TONE, 20_000_000., 0.)])
+            }),
+            expected: Change::Noise(-30.),
+        },
+        RuntimeSetting {
+            name: "impulse_noise",
+            prepare: no_condition,
+            set: |sim| sim.set_impulse_noise(Some(ImpulseNoise::ignition(1000., 10.))),
+            clear: |sim| sim.set_impulse_noise(None),
+            invalid: Some(|sim| sim.set_impulse_noise(Some(ImpulseNoise::ignition(0., 10.)))),
+            expected: Change::Noise(-80.),
+        },
+        RuntimeSetting {
+            name: "tx_oscillator",
+            prepare: no_condition,
+            set: |sim| sim.set_tx_oscillator(&impaired_oscillator()),
+            clear: |sim| sim.set_tx_oscillator(&OscillatorConfig::default()),
+            invalid: Some(|sim| sim.set_tx_oscillator(&unsorted_oscillator())),
+            expected: Change::Noise(-40.),
+        },
+        RuntimeSetting {
+            name: "rx_oscillator",
+            prepare: no_condition,
+            set: |sim| sim.set_rx_oscillator(&impaired_oscillator()),
+            clear: |sim| sim.set_rx_oscillator(&OscillatorConfig::default()),
+            invalid: Some(|sim| sim.set_rx_oscillator(&unsorted_oscillator())),
+            expected: Change::Noise(-40.),
+        },
+        RuntimeSetting {
+            name: "front_end",
+            prepare: no_condition,
+            // 1dB 圧縮点の入力では、振幅の 2 乗に比例する復調出力は 2dB 下がる
+            set: |sim| sim.set_front_end(Some(FrontEndConfig::tanh(0.))),
+            clear: |sim| sim.set_front_end(None),
+            invalid: Some(|sim| sim.set_front_end(Some(FrontEndConfig::polynomial(f64::NAN)))),
+            expected: Change::Level(-2.5..-1.5),
+        },
+        RuntimeSetting {
+            name: "adc",
+            prepare: no_condition,
+            // 量子化雑音で雑音の床が上がる
+            set: |sim| sim.set_adc(Some(AdcConfig::new(8, 3.))),
+            clear: |sim| sim.set_adc(None),
+            invalid: Some(|sim| sim.set_adc(Some(AdcConfig::new(0, 0.)))),
+            expected: Change::Noise(-80.),
+        },
+        RuntimeSetting {
+            name: "agc",
+            prepare: attenuate,
+            set: |sim| sim.set_agc(Some(Agc::new(0.1, 1.))),
+            clear: |sim| sim.set_agc(None),
+            invalid: Some(|sim| sim.set_agc(Some(Agc::new(0., 1.)))),
+            expected: Change::Level(20.0..28.0),
+        },
+        RuntimeSetting {
+            name: "limiter",
+            prepare: attenuate,
+            set: |sim| sim.set_limiter(Some(Limiter::Hard)),
+            clear: |sim| sim.set_limiter(None),
+            invalid: Some(|sim| {
+                sim.set_limiter(Some(Limiter::Soft {
+                    threshold: f64::NAN,
+                }))
+            }),
+            expected: Change::Level(20.0..28.0),
+        },
+        RuntimeSetting {
+            name: "rds",
+            prepare: no_condition,
+            set: |sim| sim.set_rds(Some(&RdsConfig::new(0x1234, "FM SIM"))),
+            clear: |sim| sim.set_rds(None),
+            // PS は 8 文字まで
+            invalid: Some(|sim| sim.set_rds(Some(&RdsConfig::new(0x1234, "TOO LONG PS")))),
+            expected: Change::Noise(-70.),
+        },
+        RuntimeSetting {
+            name: "rds_decoder",
+            prepare: no_condition,
+            set: |sim| sim.set_rds_decoder(true),
+            clear: |sim| sim.set_rds_decoder(false),
+            // 既定のサンプルレートでは 57kHz の副搬送波を表せるので、拒否されない
+            invalid: None,
+            expected: Change::Unchanged,
+        },
+        RuntimeSetting {
+            name: "mpx",
+            prepare: no_condition,
+            // クリップするまで利得を上げる (音声は大きくなるが、クリップした分だけ 12dB に届かない)
+            set: |sim| {
+                sim.set_mpx(&MpxConfig {
+                    clipper_drive: Some(12.),
+                    ..Default::default()
+                })
+            },
+            clear: |sim| sim.set_mpx(&MpxConfig::default()),
+            invalid: Some(|sim| {
+                sim.set_mpx(&MpxConfig {
+                    power_window: 0.,
+                    ..MpxConfig::bs412()
+                })
+            }),
+            expected: Change::Level(6.0..12.0),
+        },
+    ];
+    // 設定を変えるブロック、戻すブロック、終わりのブロック (それぞれの後半で測る)
+    let (set_at, clear_at, end) = (8, 32, 56);
+    let input: Vec<f32> = signals::tone(AUDIO_SAMPLE_RATE as f64, 1000., 0.3, BUFFER_SIZE * end)
+        .into_iter()
+        .map(|v| v as f32)
+        .collect();
+    let samples = |blocks: Range<usize>| blocks.start * BUFFER_SIZE..blocks.end * BUFFER_SIZE;
+    for setting in settings {
+        let name = setting.name;
+        let [mut reference, mut dut] = [(); 2].map(|_| {
+            let mut sim = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, CARRIER_FREQ).unwrap();
+            (setting.prepare)(&mut sim).unwrap();
+            sim.init_thread();
+            sim
+        });
+        let (mut expected, mut actual) = (vec![0f32; input.len()], vec![0f32; input.len()]);
+        let mut dst_r = vec![0f32; BUFFER_SIZE];
+        for (block, chunk) in input.chunks(BUFFER_SIZE).enumerate() {
+            if block == set_at {
+                (setting.set)(&mut dut).unwrap();
+            }
+            if block == clear_at {
+                (setting.clear)(&mut dut).unwrap();
+                // 拒否された設定は何も変えない
+                if let Some(invalid) = setting.invalid {
+                    assert!(
+                        matches!(invalid(&mut dut), Err(FmSimError::Config(_))),
+                        "{name}"
+                    );
+                }
+            }
+            let range = samples(block..block + 1);
+            reference
+                .process(chunk, chunk, &mut expected[range.clone()], &mut dst_r)
+                .unwrap();
+            dut.process(chunk, chunk, &mut actual[range], &mut dst_r)
+                .unwrap();
+        }
+        let changed = samples((set_at + clear_at) / 2..clear_at);
+        let level = |signal: &[f32]| {
+            let signal: Vec<f64> = signal.iter().map(|v| *v as f64).collect();
+            signals::tone_amplitude(&signal, AUDIO_SAMPLE_RATE as f64, 1000.)
+        };
+        let reference_level = level(&expected[changed.clone()]);
+        // 基準に対するトーンの振幅の変化 [dB]
+        let level_change = 20. * (level(&actual[changed.clone()]) / reference_level).log10();
+        // 基準との差の電力 (基準のトーンの電力との比) [dB]
+        let noise = |range: Range<usize>| {
+            let difference = expected[range.clone()]
+                .iter()
+                .zip(&actual[range.clone()])
+                .map(|(a, b)| ((a - b) as f64).powi(2))
+                .sum::<f64>()
+                / range.len() as f64;
+            10. * (difference / (reference_level * reference_level / 2.)).log10()
+        };
+        let changed_noise = noise(changed);
+        let matched = match &setting.expected {
+            Change::Level(range) => range.contains(&level_change),
+            Change::Noise(min) => changed_noise > *min,
+            Change::Unchanged => changed_noise < -100.,
+        };
+        assert!(
+            matched,
+            "{name}: level {level_change}dB, noise {changed_noise}dB (expected {:?})",
+            setting.expected
+        );
+        // 設定を戻せば基準と同じ出力に戻る。変更がワーカーの処理中のブロックに入るかどうかで
+        // 発振器の位相がずれる場合があるので、丸め誤差は許す
+        let restored_noise = noise(samples((clear_at + end) / 2..end));
+        assert!(
+            restored_noise < -100.,
+            "{name}: restored {restored_noise}dB"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+/**
+ * fm-core のテスト
+ * 機能毎にファイルを分ける。カーネルの比較 (stages, retune) は各ステージを検出された
+ * 全バックエンドで実行し、参照モデル (reference) との誤差がステージ毎に決めた
+ * 許容量 (ErrorBudget) に収まっていることを確認する。
+ * その他はステージ・パイプライン全体の振る舞いと設定の検証を確認する。
+ * テスト用の信号は signals にまとめる。
+*/
+mod adc;
+mod audio_processor;
+mod channel;
+mod config;
+mod emphasis;
+mod fading;
+mod front_end;
+mod if_stage;
+mod impulse;
+mod interferer;
+mod latency;
+mod lifecycle;
+mod mpx;
+mod multipath;
+mod oscillator;
+mod rds;
+mod rebuffer;
+mod reference;
+mod reset;
+mod retune;
+mod signals;
+mod stages;
+mod superhet;
+
+use crate::kernels::Backend;
+
+/// この CPU で実行できるバックエンド
+pub fn backends() -> Vec<Backend> {
+    [Backend::Scalar, Backend::Avx2Fma]
+        .into_iter()
+        .filter(|b| b.is_supported())
+        .collect()
+}
+
+/// 参照モデルとの誤差の許容量 (参照信号の最大振幅で正規化)
+#[derive(Debug, Clone, Copy)]
+pub struct ErrorBudget {
+    pub max_abs: f64,
+    pub rms: f64,
+}
+impl ErrorBudget {
+    /// 倍精度の cos/sin を使う実装同士 (演算順序・FMA の差のみ)
+    pub const DOUBLE_PRECISION: Self = Self {
+        max_abs: 1e-9,
+        rms: 1e-10,
+    };
+}
+
+#[derive(Debug)]
+pub struct ErrorStats {
+    pub max_abs: f64,
+    pub rms: f64,
+}
+pub fn error_stats(actual: &[f64], expected: &[f64]) -> ErrorStats {
+    assert_eq!(actual.len(), expected.len());
+    let scale = expected
+        .iter()
+        .fold(0f64, |acc, v| acc.max(v.abs()))
+        .max(f64::MIN_POSITIVE);
+    let (max_abs, sum_sq) = actual
+        .iter()
+        .zip(expected)
+        .fold((0f64, 0f64), |(max, sum), (a, e)| {
+            let err = (a - e).abs() / scale;
+            (max.max(err), sum + err * err)
+        });
+    ErrorStats {
+        max_abs,
+        rms: (sum_sq / expected.len() as f64).sqrt(),
+    }
+}
+#[track_caller]
+pub fn assert_within(label: &str, actual: &[f64], expected: &[f64], budget: ErrorBudget) {
+    let stats = error_stats(actual, expected);
+    println!("{label}: {stats:?}");
+    assert!(
+        stats.max_abs <= budget.max_abs && stats.rms <= budget.rms,
+        "{label}: error {stats:?} exceeds budget {budget:?}"
+    );
+}
+
+/// ブロック境界での状態の引き継ぎも確認するため、長さの異なるブロックに分けて処理する
+pub const BLOCK_SIZES: [usize; 4] = [1024, 512, 2048, 256];
+pub fn process_in_blocks(
+    input: &[f64],
+    ratio: usize,
+    mut f: impl FnMut(&[f64], &mut [f64]),
+) -> Vec<f64> {
+    let mut output = vec![0.; input.len() / ratio];
+    let mut pos = 0;
+    for size in BLOCK_SIZES.iter().cycle() {
+        if pos >= input.len() {
+            break;
+        }
+        let size = (*size).min(input.len() - pos);
+        f(
+            &input[pos..pos + size],
+            &mut output[pos / ratio..(pos + size) / ratio],
+        );
+        pos += size;
+    }
+    output
+}
@@ -0,0 +1,145 @@
+use super::signals;
+use crate::modulation_modules::composite::CompositeSignal;
+use crate::{ConfigError, FmRadioSim, FmRadioSimConfig, MpxConfig, MpxMeter};
+
+const SAMPLE_RATE: f64 = 192_000.;
+const DEVIATION: f64 = 75_000.;
+
+/// L, R に雑音 (振幅 amplitude) を入れたコンポジット信号と、その測定値
+fn composite_noise(amplitude: f64, mpx: &MpxConfig, len: usize) -> (Vec<f64>, MpxMeter) {
+    let mut composite = CompositeSignal::new(SAMPLE_RATE, 0.1, 50.);
+    composite.set_mpx(mpx, DEVIATION);
+    let l = signals::noise(1, amplitude, len);
+    let r = signals::noise(2, amplitude, len);
+    let mut buffer = vec![0.; len];
+    composite.process(&l, &r, &mut buffer);
+    (buffer, composite.mpx_meter())
+}
+
+#[test]
+fn meter_reports_deviation_and_power() {
+    // L = R = 0.5 の 1kHz: 音声の振幅 0.45 + パイロット 0.1
+    let len = SAMPLE_RATE as usize / 2;
+    let mut composite = CompositeSignal::new(SAMPLE_RATE, 0.1, 0.);
+    composite.set_mpx(&MpxConfig::default(), DEVIATION);
+    let tone = signals::tone(SAMPLE_RATE, 1000., 0.5, len);
+    let mut buffer = vec![0.; len];
+    composite.process(&tone, &tone, &mut buffer);
+    let meter = composite.mpx_meter();
+    // 0.45^2 / 2 + 0.1^2 / 2 を (19/75)^2 / 2 で割った電力
+    let expected_power = 10. * ((0.45f64.powi(2) + 0.01) / (19. / 75f64).powi(2)).log10();
+    assert!(
+        (meter.mpx_power - expected_power).abs() < 0.05,
+        "{meter:?}, expected {expected_power}dBr"
+    );
+    // ピークは 1kHz と 19kHz の位相が揃ったところで 0.55 * 75kHz 近くになる
+    assert!(
+        (meter.peak_deviation - 0.55 * DEVIATION).abs() < 500.,
+        "{meter:?}"
+    );
+    assert_eq!(meter.limiter_gain, 0.);
+    assert_eq!(meter.clip_ratio, 0.);
+}
+
+#[test]
+fn clipper_protects_pilot() {
+    let len = SAMPLE_RATE as usize;
+    let (raw, raw_meter) = composite_noise(0.5, &MpxConfig::default(), len);
+    let clip = |drive| MpxConfig {
+        clipper_drive: Some(drive),
+        ..Default::default()
+    };
+    let (clipped, meter) = composite_noise(0.5, &clip(0.), len);
+    // プリエンファシスで持ち上がった音声が偏移を超えていたのを、ほぼ 75kHz に抑える
+    // (パイロットの周波数の歪みを取り除いた分だけわずかに超える)
+    assert!(raw_meter.peak_deviation > 1.4 * DEVIATION, "{raw_meter:?}");
+    assert!(meter.peak_deviation < 1.03 * DEVIATION, "{meter:?}");
+    assert!(meter.clip_ratio > 0., "{meter:?}");
+    // パイロットの振幅は変わらない
+    for signal in [&raw, &clipped] {
+        let pilot = signals::tone_amplitude(signal, SAMPLE_RATE, 19_000.);
+        assert!((pilot - 0.1).abs() < 0.005, "pilot: {pilot}");
+    }
+    // 利得を上げるほど多くのサンプルをクリップする
+    let (_, overdriven) = composite_noise(0.5, &clip(6.), len);
+    assert!(
+        overdriven.clip_ratio > 10. * meter.clip_ratio,
+        "{overdriven:?}"
+    );
+}
+
+#[test]
+fn power_limiter_meets_bs412() {
+    // 窓を 1s に縮めて、3 窓分処理する
+    let len = SAMPLE_RATE as usize * 3;
+    let window = MpxConfig {
+        power_window: 1.,
+        ..Default::default()
+    };
+    let (_, raw) = composite_noise(0.5, &window, len);
+    assert!(raw.mpx_power > 3., "{raw:?}");
+    for power_limit in [0., -3.] {
+        let mpx = MpxConfig {
+            power_limit: Some(power_limit),
+            ..window
+        };
+        let (_, meter) = composite_noise(0.5, &mpx, len);
+        assert!(
+            meter.mpx_power <= power_limit + 0.05 && meter.mpx_power > power_limit - 0.5,
+            "limit: {power_limit}, {meter:?}"
+        );
+        assert!(meter.limiter_gain < -3., "{meter:?}");
+    }
+}
+
+#[test]
+fn sim_reports_mpx_meter() {
+    let config = FmRadioSimConfig::builder(44100, 64, 79_500_000.)
+        .mpx(MpxConfig::bs412())
+        .build()
+        .unwrap();
+    let mut sim = FmRadioSim::with_config(config).unwrap();
+    // 測定は 0.1s ごとにまとめる
+    let len = 4800;
+    let input: Vec<f32> = signals::tone(44100., 1000., 0.5, len)
+        .into_iter()
+        .map(|v| v as f32)
+        .collect();
+    let (mut dst_l, mut dst_r) = (vec![0f32; len], vec![0f32; len]);
+    sim.process_serial(&input, &input, &mut dst_l, &mut dst_r)
+        .unwrap();
+    let meter = sim.mpx_meter();
+    assert!(
+        meter.peak_deviation > 30_000. && meter.peak_deviation <= 75_000.,
+        "{meter:?}"
+    );
+}
+
+#[test]
+fn rejects_invalid_mpx() {
+    let build = |mpx| {
+        FmRadioSimConfig::builder(44100, 64, 79_500_000.)
+            .mpx(mpx)
+            .build()
+    };
+    assert!(matches!(
+        build(MpxConfig {
+            power_window: 0.,
+            ..MpxConfig::bs412()
+        }),
+        Err(ConfigError::NotPositive {
+            name: "mpx_power_window",
+            ..
+        })
+    ));
+    assert!(matches!(
+        build(MpxConfig {
+            power_limit: Some(f64::NAN),
+            ..Default::default()
+        }),
+        Err(ConfigError::NotFinite {
+            name: "mpx_power_limit",
+            ..
+        })
+    ));
+}
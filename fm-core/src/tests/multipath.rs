@@ -0,0 +1,75 @@
+use super::process_in_blocks;
+use crate::modulation_modules::channel::multipath::Multipath;
+use crate::MultipathTap;
+use std::f64::consts::{PI, TAU};
+
+const RF_SAMPLE_RATE: f64 = 187_392_000.;
+const CARRIER_FREQ: f64 = 79_500_000.;
+const LEN: usize = 8192;
+
+fn carrier(fs: f64, freq: f64, phase: f64) -> Vec<f64> {
+    let w = TAU * freq / fs;
+    (0..LEN).map(|n| (w * n as f64 + phase).cos()).collect()
+}
+
+fn process(multipath: &mut Multipath, input: &[f64]) -> Vec<f64> {
+    process_in_blocks(input, 1, |i, o| {
+        o.copy_from_slice(i);
+        multipath.process(o);
+    })
+}
+
+/// 搬送波に対しては、各タップの利得・位相をそのまま掛けた波の和になる
+#[test]
+fn taps_rotate_carrier_phase() {
+    let taps = [
+        MultipathTap::new(0., 1., 0.),
+        MultipathTap::new(2.5, 0.5, 1.),
+        MultipathTap::new(7.1, 0.25, -2.),
+    ];
+    let mut dut = Multipath::new(RF_SAMPLE_RATE, CARRIER_FREQ, &taps);
+    let actual = process(&mut dut, &carrier(RF_SAMPLE_RATE, CARRIER_FREQ, 0.));
+    let expected: Vec<f64> = (0..LEN)
+        .map(|n| {
+            taps.iter()
+                .map(|tap| {
+                    let w = TAU * CARRIER_FREQ / RF_SAMPLE_RATE;
+                    tap.gain * (w * n as f64 + tap.phase).cos()
+                })
+                .sum()
+        })
+        .collect();
+    // 最大遅延までは遅延線が埋まっていない
+    let settled = (7.1e-6 * RF_SAMPLE_RATE) as usize + 2;
+    for n in settled..LEN {
+        assert!(
+            (actual[n] - expected[n]).abs() < 1e-9,
+            "n: {n}, actual: {}, expected: {}",
+            actual[n],
+            expected[n]
+        );
+    }
+}
+
+/// 同じ遅延で逆位相の波は打ち消し合う (ピケットフェンシングの谷)
+#[test]
+fn opposite_phase_cancels() {
+    let taps = [MultipathTap::new(1., 1., 0.), MultipathTap::new(1., 1., PI)];
+    let mut dut = Multipath::new(RF_SAMPLE_RATE, CARRIER_FREQ, &taps);
+    // 搬送波から少しずれた周波数でもほぼ打ち消す
+    let input = carrier(RF_SAMPLE_RATE, CARRIER_FREQ + 75_000., 0.3);
+    let actual = process(&mut dut, &input);
+    assert!(actual.iter().all(|v| v.abs() < 1e-3));
+}
+
+#[test]
+fn presets_have_unit_power() {
+    for (name, taps) in [
+        ("urban", MultipathTap::urban()),
+        ("hilly_terrain", MultipathTap::hilly_terrain()),
+        ("building_reflection", MultipathTap::building_reflection()),
+    ] {
+        let power: f64 = taps.iter().map(|tap| tap.gain * tap.gain).sum();
+        assert!((power - 1.).abs() < 1e-12, "{name}: {power}");
+    }
+}
@@ -0,0 +1,119 @@
+use crate::modulation_modules::channel::Rng;
+use crate::modulation_modules::oscillator::{Oscillator, PhaseNoise};
+use crate::{ConfigError, Drift, OscillatorConfig};
+use std::f64::consts::TAU;
+
+const SAMPLE_RATE: f64 = 1_000_000.;
+const SEGMENT: usize = 4096;
+const SEGMENTS: usize = 256;
+
+/// Hann 窓をかけた平均ピリオドグラムによる、freq での SSB 位相雑音 [dBc/Hz]
+fn measured_level(phase: &[f64], freq: f64) -> f64 {
+    let window: Vec<f64> = (0..SEGMENT)
+        .map(|n| 0.5 - 0.5 * (TAU * n as f64 / SEGMENT as f64).cos())
+        .collect();
+    let window_power: f64 = window.iter().map(|w| w * w).sum();
+    let w = TAU * freq / SAMPLE_RATE;
+    let psd = phase
+        .chunks_exact(SEGMENT)
+        .map(|segment| {
+            let (re, im) = segment.iter().zip(window.iter()).enumerate().fold(
+                (0., 0.),
+                |(re, im), (n, (x, win))| {
+                    let (sin, cos) = (w * n as f64).sin_cos();
+                    (re + x * win * cos, im - x * win * sin)
+                },
+            );
+            // 片側スペクトル
+            2. * (re * re + im * im) / (SAMPLE_RATE * window_power)
+        })
+        .sum::<f64>()
+        / SEGMENTS as f64;
+    10. * (psd / 2.).log10()
+}
+
+#[test]
+fn phase_noise_follows_mask() {
+    let mask = [(1_000., -80.), (10_000., -95.), (100_000., -112.)];
+    let mut phase_noise = PhaseNoise::new(SAMPLE_RATE, &mask, Rng::new(1));
+    let phase: Vec<f64> = (0..SEGMENT * SEGMENTS)
+        .map(|_| phase_noise.next())
+        .collect();
+    for (freq, level) in mask {
+        let measured = measured_level(&phase, freq);
+        assert!(
+            (measured - level).abs() < 1.5,
+            "{freq}Hz: {measured}dBc/Hz (mask: {level})"
+        );
+    }
+}
+
+#[test]
+fn frequency_error_drifts_after_power_on() {
+    // 1 ブロック 1 秒
+    let config = OscillatorConfig {
+        offset_ppm: 2.,
+        drift: Some(Drift {
+            ppm: 3.,
+            time_constant: 1.,
+        }),
+        ..Default::default()
+    };
+    let mut dut = Oscillator::new(1000., 1e6, &config, Rng::new(1));
+    let freqs: Vec<f64> = (0..3).map(|_| dut.next_block_freq(1000).unwrap()).collect();
+    for (t, freq) in freqs.iter().enumerate() {
+        let ppm = 2. + 3. * (1. - (-(t as f64)).exp());
+        assert!((freq - 1e6 * (1. + ppm * 1e-6)).abs() < 1e-6, "t: {t}");
+    }
+    // ずれを無くすと、設定上の周波数に一度だけ戻す
+    dut.set_config(&OscillatorConfig::default());
+    assert_eq!(dut.next_block_freq(1000), Some(1e6));
+    assert_eq!(dut.next_block_freq(1000), None);
+}
+
+#[test]
+fn phase_noise_keeps_carrier_power() {
+    let config = OscillatorConfig {
+        phase_noise: vec![(1_000., -60.), (100_000., -100.)],
+        ..Default::default()
+    };
+    let mut dut = Oscillator::new(SAMPLE_RATE, 250_000., &config, Rng::new(1));
+    let w0 = TAU * 250_000. / SAMPLE_RATE;
+    let mut signal: Vec<f64> = (0..100_000).map(|n| (w0 * n as f64).cos()).collect();
+    dut.process_phase_noise(&mut signal, 250_000.);
+    let power = signal.iter().map(|v| v * v).sum::<f64>() / signal.len() as f64;
+    assert!((power - 0.5).abs() < 0.01, "power: {power}");
+}
+
+#[test]
+fn phase_noise_mask_must_be_ascending() {
+    let mask = |phase_noise: Vec<(f64, f64)>| OscillatorConfig {
+        phase_noise,
+        ..Default::default()
+    };
+    assert!(mask(vec![(1_000., -80.), (100_000., -120.)])
+        .validate()
+        .is_ok());
+    // 順番が崩れた離調周波数をそのまま報告する
+    assert_eq!(
+        mask(vec![(10_000., -90.), (1_000., -80.), (100., -70.)]).validate(),
+        Err(ConfigError::NotAscending {
+            name: "phase_noise_offset",
+            value: 1_000.,
+        })
+    );
+    assert_eq!(
+        mask(vec![(1_000., -80.), (1_000., -90.)]).validate(),
+        Err(ConfigError::NotAscending {
+            name: "phase_noise_offset",
+            value: 1_000.,
+        })
+    );
+    assert_eq!(
+        mask(vec![(-1_000., -80.)]).validate(),
+        Err(ConfigError::NotPositive {
+            name: "phase_noise_offset",
+            value: -1_000.,
+        })
+    );
+}
@@ -0,0 +1,278 @@
+use super::signals;
+use crate::modulation_modules::channel::Rng;
+use crate::modulation_modules::composite::{CompositeSignal, RestoreSignal};
+use crate::modulation_modules::rds::{self, checkword, RdsEncoder};
+use crate::{ClockTime, ConfigError, FmRadioSim, FmRadioSimConfig, RdsConfig, RdsStatus};
+use std::f64::consts::TAU;
+
+const SAMPLE_RATE: f64 = 192_000.;
+/// 2024-01-02 03:04:00 UTC (MJD 60311)
+const UTC: u64 = 1_704_164_640;
+
+fn station() -> RdsConfig {
+    RdsConfig {
+        radio_text: "Hello RDS".to_string(),
+        pty: 10,
+        tp: true,
+        ta: true,
+        clock_time: Some(ClockTime {
+            utc: UTC,
+            local_offset: 18,
+        }),
+        ..RdsConfig::new(0x1234, "FM SIM")
+    }
+}
+
+/// 26bit のワードを生成多項式で割った余り (オフセットワードを足す前なら 0)
+fn syndrome(word: u32) -> u32 {
+    let mut reg = word;
+    for bit in (10..26).rev() {
+        if reg & (1 << bit) != 0 {
+            reg ^= 0b101_1011_1001 << (bit - 10);
+        }
+    }
+    reg
+}
+
+#[test]
+fn checkwords_include_offsets() {
+    // x^10 mod g(x) = x^8 + x^7 + x^5 + x^4 + x^3 + 1
+    assert_eq!(checkword(0x0001), 0x1b9);
+    for (info, offset) in [
+        (0x1234, rds::OFFSET_A),
+        (0xe0cd, rds::OFFSET_C),
+        (0xffff, rds::OFFSET_D),
+    ] {
+        let block = rds::block(info, offset);
+        assert_eq!(block >> 10, info as u32);
+        assert_eq!(syndrome(block ^ offset as u32), 0);
+    }
+}
+
+#[test]
+fn groups_carry_station_info() {
+    let mut encoder = RdsEncoder::new(&station());
+    let group_duration = 104. / rds::BIT_RATE;
+    let groups: Vec<[u16; 4]> = (0..64)
+        .map(|i| encoder.next_group_info(i as f64 * group_duration))
+        .collect();
+    // 最初は CT (4A)
+    let ct = groups[0];
+    assert_eq!(ct[1] >> 11, 0x08);
+    let mjd = ((ct[1] & 0b11) as u32) << 15 | (ct[2] >> 1) as u32;
+    let hour = (ct[2] & 1) << 4 | ct[3] >> 12;
+    assert_eq!(
+        (mjd, hour, ct[3] >> 6 & 0x3f, ct[3] & 0x3f),
+        (60311, 3, 4, 18)
+    );
+    let mut ps = [0u8; 8];
+    let mut radio_text = [0u8; 64];
+    for group in &groups[1..] {
+        assert_eq!(group[0], 0x1234);
+        // TP と PTY
+        assert_eq!(group[1] >> 5 & 0x3f, 1 << 5 | 10);
+        match group[1] >> 11 {
+            0x00 => {
+                // TA と M/S、セグメント 3 で DI のステレオ
+                assert_eq!(group[1] >> 3 & 0b11, 0b11);
+                let segment = (group[1] & 0b11) as usize;
+                assert_eq!(group[1] >> 2 & 1 == 1, segment == 3);
+                ps[segment * 2..segment * 2 + 2].copy_from_slice(&group[3].to_be_bytes());
+            }
+            0x04 => {
+                let segment = (group[1] & 0xf) as usize;
+                radio_text[segment * 4..segment * 4 + 2].copy_from_slice(&group[2].to_be_bytes());
+                radio_text[segment * 4 + 2..segment * 4 + 4]
+                    .copy_from_slice(&group[3].to_be_bytes());
+            }
+            other => panic!("unexpected group type: {other:#x}"),
+        }
+    }
+    assert_eq!(&ps, b"FM SIM  ");
+    assert_eq!(&radio_text[..12], b"Hello RDS\r  ");
+    // 分が変わると CT を送り直す
+    let next = encoder.next_group_info(60.);
+    assert_eq!((next[1] >> 11, next[3] >> 6 & 0x3f), (0x08, 5));
+}
+
+#[test]
+fn subcarrier_is_pilot_locked_biphase() {
+    let config = RdsConfig {
+        injection: 0.05,
+        ..station()
+    };
+    let mut composite = CompositeSignal::new(SAMPLE_RATE, 0.1, 0.);
+    composite.set_rds(Some(&config));
+    let len = (SAMPLE_RATE * 0.2) as usize;
+    let silence = vec![0.; len];
+    let mut signal = vec![0.; len];
+    composite.process(&silence, &silence, &mut signal);
+    // パイロットを除いた残りが RDS
+    let pilot_angle = |n: usize| TAU * 19_000. * n as f64 / SAMPLE_RATE;
+    let rds_signal: Vec<f64> = signal
+        .iter()
+        .enumerate()
+        .map(|(n, v)| v - 0.1 * pilot_angle(n).cos())
+        .collect();
+    let peak = rds_signal.iter().fold(0f64, |acc, v| acc.max(v.abs()));
+    assert!(peak <= 0.05 && peak > 0.035, "peak: {peak}");
+    // パイロットの 3 倍の cos で同期検波し、8 サンプルの移動平均で 114kHz の成分を落とす
+    let baseband: Vec<f64> = rds_signal
+        .iter()
+        .enumerate()
+        .map(|(n, v)| 2. * v * (3. * pilot_angle(n)).cos())
+        .collect::<Vec<_>>()
+        .windows(8)
+        .map(|w| w.iter().sum::<f64>() / 8.)
+        .collect();
+    let sample_at = |bits: f64| {
+        let index = (bits / rds::BIT_RATE * SAMPLE_RATE - 3.5).round() as usize;
+        baseband[index]
+    };
+    // バイフェーズ符号の前半と後半の差から差動符号化したビットを戻し、さらに元のビットに戻す
+    let mut expected = RdsEncoder::new(&config);
+    let bits: Vec<bool> = [0., 1.]
+        .iter()
+        .flat_map(|i| expected.next_group_info(i * 104. / rds::BIT_RATE))
+        .zip([rds::OFFSET_A, rds::OFFSET_B, rds::OFFSET_C, rds::OFFSET_D].repeat(2))
+        .flat_map(|(info, offset)| {
+            let block = rds::block(info, offset);
+            (0..26).rev().map(move |bit| block & (1 << bit) != 0)
+        })
+        .collect();
+    let mut last = false;
+    for (k, expected_bit) in bits.iter().enumerate() {
+        let position = k as f64 + RdsEncoder::SPAN;
+        let differential = sample_at(position) > sample_at(position + 0.5);
+        assert_eq!(differential ^ last, *expected_bit, "bit {k}");
+        last = differential;
+    }
+}
+
+/// 1kHz のトーンを通した出力の 1kHz の振幅
+fn audio_tone_level(rds: Option<RdsConfig>) -> f64 {
+    let mut config = FmRadioSimConfig::builder(44100, 64, 79_500_000.);
+    if let Some(rds) = rds {
+        config = config.rds(rds);
+    }
+    signals::audio_tone_level(config)
+}
+
+#[test]
+fn rds_keeps_audio_level() {
+    let plain = audio_tone_level(None);
+    let with_rds = audio_tone_level(Some(RdsConfig {
+        injection: 0.1,
+        ..station()
+    }));
+    let diff = 20. * (with_rds / plain).log10();
+    assert!(plain > 0.25, "plain: {plain}");
+    assert!(diff.abs() < 0.5, "plain: {plain}, rds: {with_rds}");
+}
+
+/// 送信側のコンポジット信号を遅延・雑音を加えて受信側に通した RDS の受信状態
+fn decode(config: &RdsConfig, seconds: f64, delay: usize, noise: f64) -> RdsStatus {
+    let mut composite = CompositeSignal::new(SAMPLE_RATE, 0.1, 50.);
+    composite.set_rds(Some(config));
+    let subcarrier_level = 0.1 + config.injection;
+    let mut restore = RestoreSignal::new(SAMPLE_RATE, subcarrier_level, 50., 1.);
+    restore.set_rds_decoder(true);
+    let mut rng = Rng::new(7);
+    // 左右で異なる音声 (L-R の成分を含む)
+    let len = (SAMPLE_RATE * seconds) as usize;
+    let left: Vec<f64> = (0..len)
+        .map(|n| 0.8 * (TAU * 1000. * n as f64 / SAMPLE_RATE).sin())
+        .collect();
+    let right: Vec<f64> = (0..len)
+        .map(|n| 0.8 * (TAU * 6300. * n as f64 / SAMPLE_RATE).sin())
+        .collect();
+    let mut signal = vec![0.; delay + len];
+    composite.process(&left, &right, &mut signal[delay..]);
+    for v in signal.iter_mut() {
+        *v += noise * rng.gaussian();
+    }
+    let (mut l, mut r) = (vec![0.; 1000], vec![0.; 1000]);
+    for block in signal.chunks(1000) {
+        restore.process(block, &mut l[..block.len()], &mut r[..block.len()]);
+    }
+    restore.rds_status().unwrap().clone()
+}
+
+#[test]
+fn decoder_recovers_station() {
+    // 送受信のパイロットの位相が揃っていない場合も、PLL で同期する
+    // 同期前に送られる最初の CT は受信できないので、1 秒後に分が変わるようにする
+    let config = RdsConfig {
+        clock_time: Some(ClockTime {
+            utc: UTC - 1,
+            local_offset: 18,
+        }),
+        ..station()
+    };
+    let status = decode(&config, 1.5, 37, 0.);
+    assert!(status.synchronized);
+    assert_eq!(status.pi, Some(0x1234));
+    assert_eq!(status.ps.as_deref(), Some("FM SIM  "));
+    assert_eq!(status.radio_text.as_deref(), Some("Hello RDS"));
+    assert_eq!((status.pty, status.tp, status.ta), (Some(10), true, true));
+    assert_eq!(
+        status.clock_time,
+        Some(ClockTime {
+            utc: UTC,
+            local_offset: 18,
+        })
+    );
+    assert_eq!(status.error_blocks, 0, "{status:?}");
+    assert!(status.groups >= 14, "groups: {}", status.groups);
+}
+
+#[test]
+fn decoder_counts_block_errors() {
+    // 雑音が大きいほど誤りが増える
+    let clean = decode(&station(), 1., 0, 0.001);
+    let noisy = decode(&station(), 1., 0, 0.03);
+    assert_eq!(clean.block_error_rate(), 0., "{clean:?}");
+    assert!(noisy.corrected_blocks + noisy.error_blocks > 0, "{noisy:?}");
+    assert_eq!(noisy.pi, Some(0x1234));
+}
+
+#[test]
+fn decoder_works_through_radio_link() {
+    const BUFFER_SIZE: usize = 441;
+    let config = FmRadioSimConfig::builder(44100, BUFFER_SIZE, 79_500_000.)
+        .rds(station())
+        .rds_decoder(true)
+        .build()
+        .unwrap();
+    let mut sim = FmRadioSim::with_config(config).unwrap();
+    let len = BUFFER_SIZE * 25;
+    let input: Vec<f32> = (0..len)
+        .map(|n| 0.5 * (TAU * 1000. * n as f64 / 44100.).sin() as f32)
+        .collect();
+    let (mut dst_l, mut dst_r) = (vec![0f32; len], vec![0f32; len]);
+    sim.process_serial(&input, &input, &mut dst_l, &mut dst_r)
+        .unwrap();
+    let status = sim.rds_status().unwrap();
+    assert!(status.synchronized, "{status:?}");
+    assert_eq!(status.pi, Some(0x1234));
+    assert_eq!(status.error_blocks, 0, "{status:?}");
+}
+
+#[test]
+fn rds_status_follows_decoder_setting() {
+    let mut sim = FmRadioSim::from(44100, 64, 79_500_000.).unwrap();
+    assert!(sim.rds_status().is_none());
+    for enabled in [true, false] {
+        sim.set_rds_decoder(enabled).unwrap();
+        assert_eq!(sim.config().rds_decoder(), enabled);
+        assert_eq!(sim.rds_status().is_some(), enabled);
+    }
+    // 57kHz の副搬送波を表せないサンプルレートでは使えない
+    assert!(matches!(
+        FmRadioSimConfig::builder(44100, 64, 79_500_000.)
+            .composite_sample_rate(110_000)
+            .rds_decoder(true)
+            .build(),
+        Err(ConfigError::AboveNyquist { name: "rds", .. })
+    ));
+}
@@ -0,0 +1,91 @@
+use super::signals;
+use crate::{FmRadioSim, FmSimError};
+
+const AUDIO_SAMPLE_RATE: usize = 44100;
+const BUFFER_SIZE: usize = 64;
+const LEN: usize = BUFFER_SIZE * 6;
+
+fn run(sim: &mut FmRadioSim, input: &[f32], block_sizes: &[usize]) -> Vec<f32> {
+    let mut output = vec![0f32; input.len()];
+    let mut pos = 0;
+    for size in block_sizes.iter().cycle() {
+        if pos >= input.len() {
+            break;
+        }
+        let size = (*size).min(input.len() - pos);
+        let (dst_l, mut dst_r) = (&mut output[pos..pos + size], vec![0f32; size]);
+        sim.process_serial(
+            &input[pos..pos + size],
+            &input[pos..pos + size],
+            dst_l,
+            &mut dst_r,
+        )
+        .unwrap();
+        pos += size;
+    }
+    output
+}
+
+/// ホストのブロック長に関わらず、同じ入力には同じ出力を返す
+#[test]
+fn output_is_independent_of_host_block_size() {
+    let input = signals::tone(AUDIO_SAMPLE_RATE as f64, 440., 0.5, LEN)
+        .into_iter()
+        .map(|v| v as f32)
+        .collect::<Vec<_>>();
+    let mut reference = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, 79_500_000.).unwrap();
+    let expected = run(&mut reference, &input, &[BUFFER_SIZE]);
+    // 出力の先頭は再バッファリングの遅延分だけ無音
+    let latency = reference.buffering_latency();
+    assert!(expected[..latency].iter().all(|v| *v == 0.));
+    for block_sizes in [&[1usize, 17, 128, 5][..], &[128], &[300, 7], &[63, 65]] {
+        let mut sim = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, 79_500_000.).unwrap();
+        let actual = run(&mut sim, &input, block_sizes);
+        assert_eq!(actual, expected, "block sizes: {block_sizes:?}");
+    }
+}
+
+#[test]
+fn threaded_path_accepts_small_blocks() {
+    let mut sim = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, 79_500_000.).unwrap();
+    sim.init_thread();
+    let input = vec![0.25f32; 16];
+    let (mut dst_l, mut dst_r) = (vec![0f32; 16], vec![0f32; 16]);
+    for _ in 0..LEN / 16 {
+        sim.process(&input, &input, &mut dst_l, &mut dst_r).unwrap();
+    }
+    // 空のブロックも受け付ける
+    sim.process(&[], &[], &mut [], &mut []).unwrap();
+}
+
+/// ステージが失敗した後も、次の呼び出しは同じ遅延で処理を続けられる
+#[test]
+fn recovers_after_stage_error() {
+    let mut sim = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, 79_500_000.).unwrap();
+    let input = vec![0.25f32; BUFFER_SIZE * 2 + 10];
+    let (mut dst_l, mut dst_r) = (vec![0f32; input.len()], vec![0f32; input.len()]);
+    // ステージのバッファを持ったまま panic させて、ロックを poison にする
+    let stage = &sim.up_sampled_signal[0];
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let _guard = stage.lock().unwrap();
+            panic!("poison the modulate stage");
+        })
+        .join()
+        .unwrap_err();
+    });
+    assert!(matches!(
+        sim.process_serial(&input, &input, &mut dst_l, &mut dst_r),
+        Err(FmSimError::PoisonedStage("modulate"))
+    ));
+    sim.up_sampled_signal[0].clear_poison();
+    for _ in 0..3 {
+        sim.process_serial(&input, &input, &mut dst_l, &mut dst_r)
+            .unwrap();
+        // 出力の FIFO と入力の FIFO の和は常に再バッファリングの遅延に等しい
+        assert_eq!(
+            sim.output_fifo[0].len() + sim.input_fifo[0].len(),
+            sim.buffering_latency()
+        );
+    }
+}
@@ -0,0 +1,145 @@
+/**
+ * 各カーネルと同じ計算を素直な f64 のループで書いた参照モデル
+ * (SIMD のレーン分割・パイプライン化をしない)
+*/
+use crate::modulation_modules::filter::{Bpf, FilterInfo, Lpf};
+use std::f64::consts::TAU;
+
+/// 一般形の双二次フィルタ
+fn biquad(coeff: [f64; 5], x: f64, info: &mut FilterInfo) -> f64 {
+    let [c0, c1, c2, c3, c4] = coeff;
+    let [in1, in2, out1, out2] = *info;
+    let y = c0 * x + c1 * in1 + c2 * in2 - c3 * out1 - c4 * out2;
+    *info = [x, in1, y, out1];
+    y
+}
+fn bpf_coeff(bpf: &Bpf) -> [f64; 5] {
+    [bpf.c0, bpf.c1, bpf.c2, bpf.c3, bpf.c4]
+}
+
+/// y[n] = cos(2π fc n T + m T x[n])
+pub struct Modulator {
+    n: usize,
+    carrier_freq: f64,
+    sample_period: f64,
+    modulation_index: f64,
+}
+impl Modulator {
+    pub fn new(carrier_freq: f64, sample_rate: f64, modulation_index: f64) -> Self {
+        Self {
+            n: 0,
+            carrier_freq,
+            sample_period: 1. / sample_rate,
+            modulation_index,
+        }
+    }
+    pub fn process(&mut self, input: &[f64], dst: &mut [f64]) {
+        for (x, y) in input.iter().zip(dst.iter_mut()) {
+            let carrier = (TAU * self.carrier_freq * self.sample_period * self.n as f64) % TAU;
+            *y = (carrier + self.modulation_index * self.sample_period * x).cos();
+            self.n += 1;
+        }
+    }
+}
+
+/// y[n] = x[n] cos(2π (fc - fi) n T)
+pub struct CvtIntermediateFreq {
+    n: usize,
+    delta_angle: f64,
+}
+impl CvtIntermediateFreq {
+    pub fn new(fs: f64, fc: f64, fi: f64) -> Self {
+        Self {
+            n: 0,
+            delta_angle: TAU * (fc - fi) / fs,
+        }
+    }
+    pub fn process(&mut self, input: &[f64], dst: &mut [f64]) {
+        for (x, y) in input.iter().zip(dst.iter_mut()) {
+            *y = x * ((self.delta_angle * self.n as f64) % TAU).cos();
+            self.n += 1;
+        }
+    }
+}
+
+/// 2段縦続のBPF。2段目には4サンプル遅れた1段目の出力が入り、4サンプル毎に間引く。
+pub struct BandPassFilter {
+    coeff: [f64; 5],
+    info: [FilterInfo; 2],
+    delay: [f64; 4],
+    n: usize,
+}
+impl BandPassFilter {
+    pub const DECIMATION: usize = 4;
+    pub fn new(bpf: &Bpf) -> Self {
+        Self {
+            coeff: bpf_coeff(bpf),
+            info: [FilterInfo::default(); 2],
+            delay: [0.; 4],
+            n: 0,
+        }
+    }
+    fn next(&mut self, x: f64) -> f64 {
+        let stage1 = biquad(self.coeff, x, &mut self.info[0]);
+        let delayed = std::mem::replace(&mut self.delay[self.n % 4], stage1);
+        self.n += 1;
+        biquad(self.coeff, delayed, &mut self.info[1])
+    }
+    pub fn process(&mut self, input: &[f64], dst: &mut [f64]) {
+        for (block, y) in input.chunks_exact(Self::DECIMATION).zip(dst.iter_mut()) {
+            let mut out = 0.;
+            for x in block {
+                out = self.next(*x);
+            }
+            *y = 2. * out;
+        }
+    }
+    pub fn process_no_resample(&mut self, input: &[f64], dst: &mut [f64]) {
+        for (x, y) in input.iter().zip(dst.iter_mut()) {
+            *y = self.next(*x);
+        }
+    }
+}
+
+/// 直交検波 + 2段LPF (2段目は4サンプル遅れ) + 微分のたすき掛けによる復調
+pub struct DeModulator {
+    n: usize,
+    delta_angle: f64,
+    sample_rate: f64,
+    lpf: Lpf,
+    info: [FilterInfo; 4],
+    delay: [(f64, f64); 4],
+    prev_sin: f64,
+    prev_iq: (f64, f64),
+}
+impl DeModulator {
+    pub fn new(fc: f64, sample_rate: f64, cut_off: f64) -> Self {
+        Self {
+            n: 0,
+            delta_angle: TAU * fc / sample_rate,
+            sample_rate,
+            lpf: Lpf::new(sample_rate, cut_off, Lpf::Q),
+            info: [FilterInfo::default(); 4],
+            delay: [(0., 0.); 4],
+            prev_sin: 0.,
+            prev_iq: (0., 0.),
+        }
+    }
+    pub fn process(&mut self, input: &[f64], dst: &mut [f64]) {
+        for (x, y) in input.iter().zip(dst.iter_mut()) {
+            let sin = ((self.delta_angle * self.n as f64) % TAU).sin();
+            let cos = (sin - self.prev_sin) / self.delta_angle;
+            self.prev_sin = sin;
+            let i1 = self.lpf.process_without_buffer(-x * sin, &mut self.info[0]);
+            let q1 = self.lpf.process_without_buffer(x * cos, &mut self.info[1]);
+            let (di, dq) = std::mem::replace(&mut self.delay[self.n % 4], (i1, q1));
+            let i = self.lpf.process_without_buffer(di, &mut self.info[2]);
+            let q = self.lpf.process_without_buffer(dq, &mut self.info[3]);
+            let d_i = (i - self.prev_iq.0) * self.sample_rate;
+            let d_q = (q - self.prev_iq.1) * self.sample_rate;
+            self.prev_iq = (i, q);
+            *y = i * d_q - q * d_i;
+            self.n += 1;
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use super::signals;
+use crate::FmRadioSim;
+
+const AUDIO_SAMPLE_RATE: usize = 44100;
+const BUFFER_SIZE: usize = 64;
+const LEN: usize = BUFFER_SIZE * 6;
+
+/// ホストのブロック長を BUFFER_SIZE と揃えずに処理する
+fn run(sim: &mut FmRadioSim, input: &[f32], threaded: bool) -> Vec<f32> {
+    let mut output = vec![0f32; input.len()];
+    let mut dst_r = vec![0f32; input.len()];
+    for (pos, size) in (0..input.len())
+        .step_by(48)
+        .map(|p| (p, 48.min(input.len() - p)))
+    {
+        let range = pos..pos + size;
+        let (i, l, r) = (
+            &input[range.clone()],
+            &mut output[range.clone()],
+            &mut dst_r[range],
+        );
+        if threaded {
+            sim.process(i, i, l, r).unwrap();
+        } else {
+            sim.process_serial(i, i, l, r).unwrap();
+        }
+    }
+    output
+}
+
+/// reset 後の出力は、新しく作った FmRadioSim の出力と一致する
+#[test]
+fn reset_matches_fresh_instance() {
+    let [before, after]: [Vec<f32>; 2] = [1000., 440.].map(|freq| {
+        signals::tone(AUDIO_SAMPLE_RATE as f64, freq, 0.5, LEN)
+            .into_iter()
+            .map(|v| v as f32)
+            .collect()
+    });
+    for threaded in [false, true] {
+        let mut fresh = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, 79_500_000.).unwrap();
+        let mut sim = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, 79_500_000.).unwrap();
+        if threaded {
+            fresh.init_thread();
+            sim.init_thread();
+        }
+        let expected = run(&mut fresh, &after, threaded);
+        // ブロックの途中 (FIFO に端数が残った状態) で reset する
+        run(&mut sim, &before[..LEN - 20], threaded);
+        sim.reset().unwrap();
+        assert_eq!(sim.is_running(), threaded);
+        let actual = run(&mut sim, &after, threaded);
+        assert_eq!(actual, expected, "threaded: {threaded}");
+    }
+}
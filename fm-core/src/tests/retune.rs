@@ -0,0 +1,79 @@
+use super::{assert_within, backends, process_in_blocks, ErrorBudget};
+use crate::modulation_modules::{get_8x_sample_rate, modulator};
+use std::f64::consts::TAU;
+
+const CARRIER_FREQ: f64 = 79_500_000.;
+const RETUNED_FREQ: f64 = 80_100_000.;
+const INTERMEDIATE_FREQ: f64 = 10_700_000.;
+const DEVIATION: f64 = 75_000.;
+const LEN: usize = 8192;
+// 周波数を切り替えるサンプル位置 (4 レーンの途中にならないブロック境界)
+const SWITCH_AT: usize = 3072;
+fn fm_sample_rate() -> f64 {
+    get_8x_sample_rate(185_000_000, 192_000) as f64
+}
+
+/// SWITCH_AT で周波数が f1 から f2 に変わる、位相の連続した cos
+fn switched_cos(fs: f64, f1: f64, f2: f64) -> Vec<f64> {
+    let (d1, d2) = (TAU * f1 / fs, TAU * f2 / fs);
+    (0..LEN)
+        .map(|n| {
+            let phase = if n < SWITCH_AT {
+                d1 * n as f64
+            } else {
+                d1 * SWITCH_AT as f64 + d2 * (n - SWITCH_AT) as f64
+            };
+            (phase % TAU).cos()
+        })
+        .collect()
+}
+
+#[test]
+fn carrier_change_is_phase_continuous() {
+    let fs = fm_sample_rate();
+    // 入力 0 では変調器の出力は搬送波そのもの
+    let input = vec![0.; LEN];
+    let expected = switched_cos(fs, CARRIER_FREQ, RETUNED_FREQ);
+    for backend in backends() {
+        let mut dut = modulator::Modulator::from(CARRIER_FREQ, fs, DEVIATION);
+        dut.set_backend(backend);
+        let mut actual = process_in_blocks(&input[..SWITCH_AT], 1, |i, o| dut.process(i, o));
+        dut.set_carrier_freq(RETUNED_FREQ);
+        actual.extend(process_in_blocks(&input[SWITCH_AT..], 1, |i, o| {
+            dut.process(i, o)
+        }));
+        assert_within(
+            &format!("retune-modulate/{backend}"),
+            &actual,
+            &expected,
+            ErrorBudget::DOUBLE_PRECISION,
+        );
+    }
+}
+
+#[test]
+fn local_oscillator_change_is_phase_continuous() {
+    let fs = fm_sample_rate();
+    // 入力 1 では周波数変換の出力は局部発振そのもの
+    let input = vec![1.; LEN];
+    let expected = switched_cos(
+        fs,
+        CARRIER_FREQ - INTERMEDIATE_FREQ,
+        RETUNED_FREQ - INTERMEDIATE_FREQ,
+    );
+    for backend in backends() {
+        let mut dut = modulator::CvtIntermediateFreq::new(fs, CARRIER_FREQ, INTERMEDIATE_FREQ);
+        dut.set_backend(backend);
+        let mut actual = process_in_blocks(&input[..SWITCH_AT], 1, |i, o| dut.process(i, o));
+        dut.set_tuned_freq(RETUNED_FREQ);
+        actual.extend(process_in_blocks(&input[SWITCH_AT..], 1, |i, o| {
+            dut.process(i, o)
+        }));
+        assert_within(
+            &format!("retune-cvt-freq/{backend}"),
+            &actual,
+            &expected,
+            ErrorBudget::DOUBLE_PRECISION,
+        );
+    }
+}
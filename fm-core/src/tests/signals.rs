@@ -0,0 +1,80 @@
+/**
+ * テスト用の入力信号 (トーン、スイープ、ノイズ) と、出力のトーンの測定
+*/
+use crate::{FmRadioSim, FmRadioSimConfigBuilder};
+use std::f64::consts::TAU;
+
+pub fn tone(fs: f64, freq: f64, amplitude: f64, len: usize) -> Vec<f64> {
+    (0..len)
+        .map(|n| amplitude * (TAU * freq * n as f64 / fs).sin())
+        .collect()
+}
+
+/// f0 から f1 まで線形に周波数が変化するチャープ
+pub fn sweep(fs: f64, f0: f64, f1: f64, amplitude: f64, len: usize) -> Vec<f64> {
+    let duration = len as f64 / fs;
+    let rate = (f1 - f0) / duration;
+    (0..len)
+        .map(|n| {
+            let t = n as f64 / fs;
+            amplitude * (TAU * (f0 * t + 0.5 * rate * t * t)).sin()
+        })
+        .collect()
+}
+
+/// 一様分布の白色雑音 (シード固定)
+pub fn noise(seed: u64, amplitude: f64, len: usize) -> Vec<f64> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            // splitmix64
+            state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^= z >> 31;
+            amplitude * ((z >> 11) as f64 / (1u64 << 53) as f64 * 2. - 1.)
+        })
+        .collect()
+}
+
+/// 正弦波で周波数変調された搬送波
+pub fn fm_tone(fs: f64, carrier: f64, freq: f64, deviation: f64, len: usize) -> Vec<f64> {
+    (0..len)
+        .map(|n| {
+            let t = n as f64 / fs;
+            let beta = deviation / freq;
+            (TAU * carrier * t + beta * (TAU * freq * t).sin()).cos()
+        })
+        .collect()
+}
+
+/// signal に含まれる周波数 freq [Hz] の成分の振幅 (1 ビンの DFT)
+pub fn tone_amplitude(signal: &[f64], fs: f64, freq: f64) -> f64 {
+    let w = TAU * freq / fs;
+    let (re, im) = signal
+        .iter()
+        .enumerate()
+        .fold((0., 0.), |(re, im), (n, v)| {
+            let (sin, cos) = (w * n as f64).sin_cos();
+            (re + v * cos, im - v * sin)
+        });
+    2. * (re * re + im * im).sqrt() / signal.len() as f64
+}
+
+/// 振幅 0.3 の 1kHz のトーンを builder の設定のシミュレータに通した、出力の 1kHz の振幅
+pub fn audio_tone_level(builder: FmRadioSimConfigBuilder) -> f64 {
+    let config = builder.build().unwrap();
+    let (fs, len) = (config.audio_sample_rate() as f64, config.buffer_size() * 32);
+    let mut sim = FmRadioSim::with_config(config).unwrap();
+    let input: Vec<f32> = tone(fs, 1000., 0.3, len)
+        .into_iter()
+        .map(|v| v as f32)
+        .collect();
+    let (mut dst_l, mut dst_r) = (vec![0f32; len], vec![0f32; len]);
+    sim.process_serial(&input, &input, &mut dst_l, &mut dst_r)
+        .unwrap();
+    // 立ち上がりを除いた 441 サンプル (10 周期)
+    let tail: Vec<f64> = dst_l[len - 441..].iter().map(|v| *v as f64).collect();
+    tone_amplitude(&tail, fs, 1000.)
+}
@@ -0,0 +1,148 @@
+use super::{
+    assert_within, backends, process_in_blocks, reference, signals, ErrorBudget,
+};
+use crate::modulation_modules::{get_8x_sample_rate, modulator};
+use std::f64::consts::PI;
+
+const CARRIER_FREQ: f64 = 79_500_000.;
+const INTERMEDIATE_FREQ: f64 = 10_700_000.;
+const SIGNAL_MAX_FREQ: f64 = 106_000.;
+const DEVIATION: f64 = 75_000.;
+const LEN: usize = 16384;
+fn fm_sample_rate() -> f64 {
+    get_8x_sample_rate(185_000_000, 192_000) as f64
+}
+fn intermediate_sample_rate() -> f64 {
+    fm_sample_rate() / 4.
+}
+
+#[test]
+fn modulator_matches_reference() {
+    let fs = fm_sample_rate();
+    for backend in backends() {
+        let template = modulator::Modulator::from(CARRIER_FREQ, fs, DEVIATION);
+        // 位相偏移が ±π になるよう入力 (積分済み信号) をスケーリング
+        let scale = PI / (template.modulation_index * template.sample_period);
+        let inputs = [
+            ("tone", signals::tone(fs, 1_000_000., scale, LEN)),
+            ("sweep", signals::sweep(fs, 0., 5_000_000., scale, LEN)),
+            ("noise", signals::noise(1, scale, LEN)),
+        ];
+        for (name, input) in inputs {
+            let mut dut = modulator::Modulator::from(CARRIER_FREQ, fs, DEVIATION);
+            dut.set_backend(backend);
+            let mut model =
+                reference::Modulator::new(CARRIER_FREQ, fs, template.modulation_index);
+            let actual = process_in_blocks(&input, 1, |i, o| dut.process(i, o));
+            let expected = process_in_blocks(&input, 1, |i, o| model.process(i, o));
+            assert_within(
+                &format!("modulate/{backend}/{name}"),
+                &actual,
+                &expected,
+                ErrorBudget::DOUBLE_PRECISION,
+            );
+        }
+    }
+}
+
+#[test]
+fn freq_converter_matches_reference() {
+    let fs = fm_sample_rate();
+    for backend in backends() {
+        let inputs = [
+            ("tone", signals::tone(fs, CARRIER_FREQ, 1., LEN)),
+            (
+                "sweep",
+                signals::sweep(fs, CARRIER_FREQ - 1e6, CARRIER_FREQ + 1e6, 1., LEN),
+            ),
+            ("noise", signals::noise(2, 1., LEN)),
+        ];
+        for (name, input) in inputs {
+            let mut dut = modulator::CvtIntermediateFreq::new(fs, CARRIER_FREQ, INTERMEDIATE_FREQ);
+            dut.set_backend(backend);
+            let mut model =
+                reference::CvtIntermediateFreq::new(fs, CARRIER_FREQ, INTERMEDIATE_FREQ);
+            let actual = process_in_blocks(&input, 1, |i, o| dut.process(i, o));
+            let expected = process_in_blocks(&input, 1, |i, o| model.process(i, o));
+            assert_within(
+                &format!("cvt-freq/{backend}/{name}"),
+                &actual,
+                &expected,
+                ErrorBudget::DOUBLE_PRECISION,
+            );
+        }
+    }
+}
+
+#[test]
+fn bandpass_filter_matches_reference() {
+    let fs = fm_sample_rate();
+    let ratio = reference::BandPassFilter::DECIMATION;
+    for backend in backends() {
+        let inputs = [
+            ("tone", signals::tone(fs, INTERMEDIATE_FREQ, 1., LEN)),
+            (
+                "sweep",
+                signals::sweep(fs, INTERMEDIATE_FREQ - 2e6, INTERMEDIATE_FREQ + 2e6, 1., LEN),
+            ),
+            ("noise", signals::noise(3, 1., LEN)),
+        ];
+        for (name, input) in inputs {
+            let mut dut = modulator::BandPassFilter::new(fs, INTERMEDIATE_FREQ);
+            dut.set_backend(backend);
+            let mut model = reference::BandPassFilter::new(&dut.filter_coeff);
+            let actual = process_in_blocks(&input, ratio, |i, o| dut.process(i, o));
+            let expected = process_in_blocks(&input, ratio, |i, o| model.process(i, o));
+            assert_within(
+                &format!("bandpass-filter/{backend}/{name}"),
+                &actual,
+                &expected,
+                ErrorBudget::DOUBLE_PRECISION,
+            );
+            // 間引き無し
+            let mut dut = modulator::BandPassFilter::new(fs, INTERMEDIATE_FREQ);
+            dut.set_backend(backend);
+            let mut model = reference::BandPassFilter::new(&dut.filter_coeff);
+            let actual = process_in_blocks(&input, 1, |i, o| dut.process_no_resample(i, o));
+            let expected =
+                process_in_blocks(&input, 1, |i, o| model.process_no_resample(i, o));
+            assert_within(
+                &format!("bandpass-filter(no-resample)/{backend}/{name}"),
+                &actual,
+                &expected,
+                ErrorBudget::DOUBLE_PRECISION,
+            );
+        }
+    }
+}
+
+#[test]
+fn demodulator_matches_reference() {
+    let fs = intermediate_sample_rate();
+    for backend in backends() {
+        let inputs = [
+            (
+                "tone",
+                signals::fm_tone(fs, INTERMEDIATE_FREQ, 1_000., 75_000., LEN),
+            ),
+            (
+                "sweep",
+                signals::sweep(fs, INTERMEDIATE_FREQ - 1e5, INTERMEDIATE_FREQ + 1e5, 1., LEN),
+            ),
+            ("noise", signals::noise(4, 1., LEN)),
+        ];
+        for (name, input) in inputs {
+            let mut dut = modulator::DeModulator::from(INTERMEDIATE_FREQ, fs, SIGNAL_MAX_FREQ);
+            dut.set_backend(backend);
+            let mut model = reference::DeModulator::new(INTERMEDIATE_FREQ, fs, SIGNAL_MAX_FREQ);
+            let actual = process_in_blocks(&input, 1, |i, o| dut.process(i, o));
+            let expected = process_in_blocks(&input, 1, |i, o| model.process(i, o));
+            assert_within(
+                &format!("demodulate/{backend}/{name}"),
+                &actual,
+                &expected,
+                ErrorBudget::DOUBLE_PRECISION,
+            );
+        }
+    }
+}
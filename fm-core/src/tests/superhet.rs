@@ -0,0 +1,150 @@
+use super::signals;
+use crate::modulation_modules::modulator::CvtIntermediateFreq;
+use crate::modulation_modules::superhet::PreselectorFilter;
+use crate::{ConfigError, FmRadioSim, FmRadioSimConfig, Preselector, SecondIf, SuperhetConfig};
+use std::f64::consts::TAU;
+
+const SAMPLE_RATE: f64 = 8_000_000.;
+// 100Hz の整数倍の周波数は LEN サンプルでちょうど周期が揃う
+const LEN: usize = 80_000;
+const TUNED_FREQ: f64 = 3_000_000.;
+const INTERMEDIATE_FREQ: f64 = 1_000_000.;
+/// 局部発振 2MHz に対するイメージ周波数
+const IMAGE_FREQ: f64 = 1_000_000.;
+
+fn cos(freq: f64) -> Vec<f64> {
+    (0..LEN)
+        .map(|n| (TAU * freq * n as f64 / SAMPLE_RATE).cos())
+        .collect()
+}
+
+/// freq [Hz] の成分の振幅
+fn amplitude_at(signal: &[f64], freq: f64) -> f64 {
+    signals::tone_amplitude(signal, SAMPLE_RATE, freq)
+}
+
+/// 中間周波数に変換された成分の振幅
+fn converted(freq: f64, image_rejection: Option<f64>) -> f64 {
+    let mut dut = CvtIntermediateFreq::new(SAMPLE_RATE, TUNED_FREQ, INTERMEDIATE_FREQ);
+    dut.set_image_rejection(image_rejection);
+    let input = cos(freq);
+    let mut output = vec![0.; LEN];
+    for (src, dst) in input.chunks(4000).zip(output.chunks_mut(4000)) {
+        dut.process(src, dst);
+    }
+    amplitude_at(&output, INTERMEDIATE_FREQ)
+}
+
+#[test]
+fn image_reject_mixer_follows_rejection() {
+    // 通常のミキサーは希望波とイメージを同じ利得で通す
+    let desired = converted(TUNED_FREQ, None);
+    assert!((desired - 0.5).abs() < 1e-3, "desired: {desired}");
+    assert!((converted(IMAGE_FREQ, None) - desired).abs() < 1e-3);
+    for rejection in [20., 40.] {
+        let desired = converted(TUNED_FREQ, Some(rejection));
+        let image = converted(IMAGE_FREQ, Some(rejection));
+        let measured = 20. * (desired / image).log10();
+        assert!((desired - 0.5).abs() < 1e-3, "desired: {desired}");
+        assert!(
+            (measured - rejection).abs() < 0.5,
+            "rejection: {rejection}, measured: {measured}"
+        );
+    }
+}
+
+#[test]
+fn preselector_tracks_tuned_freq() {
+    let preselector = Preselector {
+        bandwidth: 200_000.,
+        sections: 2,
+    };
+    let gain = |tuned: f64, freq: f64| {
+        let mut dut = PreselectorFilter::new(SAMPLE_RATE, 2_000_000., Some(preselector));
+        dut.set_tuned_freq(tuned);
+        let mut signal = cos(freq);
+        dut.process(&mut signal);
+        // 過渡応答が収まった後半で測る
+        20. * amplitude_at(&signal[LEN / 2..], freq).log10()
+    };
+    assert!(gain(TUNED_FREQ, TUNED_FREQ).abs() < 0.05);
+    let edge = gain(TUNED_FREQ, TUNED_FREQ + 100_000.);
+    assert!((edge + 3.).abs() < 0.3, "edge: {edge}dB");
+    assert!(gain(TUNED_FREQ, IMAGE_FREQ) < -40.);
+}
+
+fn audio_tone_level(superhet: SuperhetConfig) -> f64 {
+    signals::audio_tone_level(FmRadioSimConfig::builder(44100, 64, 79_500_000.).superhet(superhet))
+}
+
+#[test]
+fn double_conversion_keeps_audio_level() {
+    let single = audio_tone_level(SuperhetConfig::default());
+    let double = audio_tone_level(SuperhetConfig {
+        preselector: Some(Preselector {
+            bandwidth: 4_000_000.,
+            sections: 1,
+        }),
+        image_rejection: Some(30.),
+        second_if: Some(SecondIf {
+            freq: SecondIf::DEFAULT_FREQ,
+            bandwidth: 400_000.,
+        }),
+    });
+    let diff = 20. * (double / single).log10();
+    assert!(single > 0.25, "single: {single}");
+    assert!(diff.abs() < 1., "single: {single}, double: {double}");
+}
+
+#[test]
+fn rejects_invalid_superhet() {
+    let build = |superhet: SuperhetConfig| {
+        FmRadioSimConfig::builder(44100, 64, 79_500_000.)
+            .superhet(superhet)
+            .build()
+    };
+    // 2 回目の中間周波数は 1 回目より低い必要がある
+    let second_if = SecondIf {
+        freq: 20_000_000.,
+        bandwidth: 200_000.,
+    };
+    let error = build(SuperhetConfig {
+        second_if: Some(second_if),
+        ..Default::default()
+    })
+    .unwrap_err();
+    assert_eq!(
+        error,
+        ConfigError::SecondIfAboveFirstIf {
+            second_if: 20_000_000.,
+            intermediate_freq: FmRadioSimConfig::DEFAULT_INTERMEDIATE_FREQ,
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "second if (20000000Hz) must be below the first intermediate freq (10700000Hz)"
+    );
+    assert!(matches!(
+        build(SuperhetConfig {
+            image_rejection: Some(-10.),
+            ..Default::default()
+        }),
+        Err(ConfigError::NotPositive {
+            name: "image_rejection",
+            ..
+        })
+    ));
+    // プリセレクタの通過帯域はナイキスト周波数より下
+    let config = build(SuperhetConfig {
+        preselector: Some(Preselector {
+            bandwidth: 10_000_000.,
+            sections: 3,
+        }),
+        ..Default::default()
+    })
+    .unwrap();
+    let mut sim = FmRadioSim::with_config(config).unwrap();
+    assert!(sim.set_tuned_freq(80_000_000.).is_ok());
+    assert!(sim.set_tuned_freq(90_000_000.).is_err());
+    assert_eq!(sim.config().tuned_freq(), 80_000_000.);
+}
@@ -130,7 +130,8 @@ impl MyChart {
             "Signal time per frame: {}ms",
             (BUFFER_SIZE as f64) / AUDIO_SAMPLE_RATE as f64 * 1000f64
         );
-        let mut fm_radio_sim = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, CARRIER_FREQ);
+        let mut fm_radio_sim = FmRadioSim::from(AUDIO_SAMPLE_RATE, BUFFER_SIZE, CARRIER_FREQ)
+            .expect("failed to create FmRadioSim");
         fm_radio_sim.init_thread();
         Self {
             render_times: 0,
@@ -176,20 +177,24 @@ impl MyChart {
             // up-sample
             println!("===============Start=================");
             let timer = Instant::now();
-            if ENABLE_PARALLEL {
+            let result = if ENABLE_PARALLEL {
                 self.fm_radio_sim.process(
                     &self.input_signal[0],
                     &self.input_signal[1],
                     &mut self.output_signal_l,
                     &mut self.output_signal_r,
-                );
+                )
             } else {
                 self.fm_radio_sim.process_serial(
                     &self.input_signal[0],
                     &self.input_signal[1],
                     &mut self.output_signal_l,
                     &mut self.output_signal_r,
-                );
+                )
+            };
+            if let Err(e) = result {
+                println!("simulation error: {e}");
+                self.continue_flag = false;
             }
 
             let end_time = timer.elapsed();
@@ -266,7 +271,7 @@ impl Chart<Message> for MyChart {
                         .copied()
                         .collect::<Vec<f64>>()
                         .as_slice(),
-                    FmRadioSim::COMPOSITE_SAMPLE_RATE,
+                    self.fm_radio_sim.config().composite_sample_rate(),
                 ),
                 3 => draw_chart(
                     builder,
@@ -278,7 +283,7 @@ impl Chart<Message> for MyChart {
                         .copied()
                         .collect::<Vec<f64>>()
                         .as_slice(),
-                    FmRadioSim::FM_MODULATION_SAMPLE_RATE,
+                    self.fm_radio_sim.config().rf_sample_rate(),
                 ),
                 4 => draw_chart(
                     builder,
@@ -290,8 +295,7 @@ impl Chart<Message> for MyChart {
                         .copied()
                         .collect::<Vec<f64>>()
                         .as_slice(),
-                    FmRadioSim::FM_MODULATION_SAMPLE_RATE
-                        / FmRadioSim::RATIO_FS_INTER_FS,
+                    self.fm_radio_sim.config().intermediate_sample_rate(),
                 ),
                 5 => draw_chart(
                     builder,
@@ -303,8 +307,7 @@ impl Chart<Message> for MyChart {
                         // .copied()
                         // .collect::<Vec<f64>>()
                         // .as_slice(),
-                    FmRadioSim::FM_MODULATION_SAMPLE_RATE
-                        / FmRadioSim::RATIO_FS_INTER_FS,
+                    self.fm_radio_sim.config().intermediate_sample_rate(),
                 ),
                 6 => draw_chart(
                     builder,
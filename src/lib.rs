@@ -1,9 +1,9 @@
 // use dasp_ring_buffer::Fixed as RingBuffer;
-use buffer::FixedLenBuffer;
 use fm_core::{sharable, FmRadioSim, Shareable};
 use nih_plug::prelude::*;
 // use parking_lot::Mutex;
 use std::{
+    collections::VecDeque,
     hint,
     sync::atomic::{AtomicBool, Ordering},
 };
@@ -22,10 +22,9 @@ struct FmSim {
     socket: Arc<Mutex<Option<UdpSocket>>>,
     params: Arc<FmParams>,
     // fmradio: Shareable<FmRadioSim>,
-    input_buffer: Shareable<[FixedLenBuffer; 2]>,
-    output_buffer: Shareable<[FixedLenBuffer; 2]>,
-    // input_buffer: Shareable<[VecDeque<f32>; 2]>,
-    // output_buffer: Shareable<[VecDeque<f32>; 2]>,
+    // ホストのブロック長は任意なので、可変長の FIFO でやり取りする
+    input_buffer: Shareable<[VecDeque<f32>; 2]>,
+    output_buffer: Shareable<[VecDeque<f32>; 2]>,
     // tmp_buffer_l: Vec<f32>,
     // tmp_buffer_r: Vec<f32>,
     // buf_l: VecDeque<f32>,
@@ -35,6 +34,8 @@ struct FmSim {
     //
     input_signal_wait: Arc<AtomicBool>,
     output_signal_wait: Arc<AtomicBool>,
+    // ホストからの reset 要求 (処理スレッドが次の入力を取り出す時に反映する)
+    reset_request: Arc<AtomicBool>,
     // start_barrier: Arc<Condvar>,
     //
     is_init: bool,
@@ -86,29 +87,13 @@ impl Default for FmSim {
             // tmp_buffer_r: vec![0.; Self::DEFAULT_BUFFER_SIZE],
             // buf_l: VecDeque::from([0f32; 4096]),
             // buf_r: VecDeque::from([0f32; 4096]),
-            // input_buffer: sharable!([
-            //     VecDeque::<f32>::with_capacity(
-            //         Self::DEFAULT_BUFFER_SIZE * Self::RING_BUFFER_SIZE
-            //     ),
-            //     VecDeque::<f32>::with_capacity(
-            //         Self::DEFAULT_BUFFER_SIZE * Self::RING_BUFFER_SIZE
-            //     )
-            // ]),
-            // output_buffer: sharable!([
-            //     VecDeque::<f32>::with_capacity(
-            //         Self::DEFAULT_BUFFER_SIZE * Self::RING_BUFFER_SIZE
-            //     ),
-            //     VecDeque::<f32>::with_capacity(
-            //         Self::DEFAULT_BUFFER_SIZE * Self::RING_BUFFER_SIZE
-            //     )
-            // ]),
             input_buffer: sharable!([
-                FixedLenBuffer::new(Self::DEFAULT_BUFFER_SIZE, Self::RING_BUFFER_SIZE).unwrap(),
-                FixedLenBuffer::new(Self::DEFAULT_BUFFER_SIZE, Self::RING_BUFFER_SIZE).unwrap()
+                VecDeque::<f32>::with_capacity(Self::DEFAULT_BUFFER_SIZE * Self::RING_BUFFER_SIZE),
+                VecDeque::<f32>::with_capacity(Self::DEFAULT_BUFFER_SIZE * Self::RING_BUFFER_SIZE)
             ]),
             output_buffer: sharable!([
-                FixedLenBuffer::new(Self::DEFAULT_BUFFER_SIZE, Self::RING_BUFFER_SIZE).unwrap(),
-                FixedLenBuffer::new(Self::DEFAULT_BUFFER_SIZE, Self::RING_BUFFER_SIZE).unwrap()
+                VecDeque::<f32>::with_capacity(Self::DEFAULT_BUFFER_SIZE * Self::RING_BUFFER_SIZE),
+                VecDeque::<f32>::with_capacity(Self::DEFAULT_BUFFER_SIZE * Self::RING_BUFFER_SIZE)
             ]),
             // fmradio: sharable!(FmRadioSim::from(
             //     44100,
@@ -120,6 +105,7 @@ impl Default for FmSim {
             // output_signal: Arc::new((Mutex::new(false), Condvar::new())),
             input_signal_wait: Arc::new(AtomicBool::new(false)),
             output_signal_wait: Arc::new(AtomicBool::new(false)),
+            reset_request: Arc::new(AtomicBool::new(false)),
             // start_barrier: Arc::new(Condvar::new()),
             is_init: false,
             msg_sender: None,
@@ -131,6 +117,14 @@ impl Default for FmSim {
 impl FmSim {
     const DEFAULT_BUFFER_SIZE: usize = 700;
     const RING_BUFFER_SIZE: usize = 8;
+    // 処理スレッドが間に合うように、出力側に先行して詰めておくブロック数
+    const PREFILL_BLOCKS: usize = 4;
+    fn prefill_output(buffer: &mut [VecDeque<f32>; 2]) {
+        for ch in buffer.iter_mut() {
+            ch.clear();
+            ch.resize(Self::DEFAULT_BUFFER_SIZE * Self::PREFILL_BLOCKS, 0.);
+        }
+    }
     pub fn add_socket(&mut self, ip: impl AsRef<str>) {
         if self.socket.lock().unwrap().is_none() {
             let socket = UdpSocket::bind("127.0.0.1:12345").unwrap();
@@ -216,17 +210,18 @@ impl Plugin for FmSim {
             // self.info(String::from("wait @ main"));
             // self.start_barrier.wait();
         }
+        let len = buffer.samples();
         let samples = buffer.as_slice();
         // let is_input_empty = self.input_buffer.lock().unwrap()[0].is_empty();
         // self.info(String::from("process init end"));
         // let input_is_empty = self.input_buffer.lock().unwrap()[1].is_empty();
-        // 入力バッファへデータを追加
-        samples
-            .iter()
-            .zip(self.input_buffer.lock().unwrap().iter_mut())
-            .for_each(|(samples, buffer)| {
-                buffer.enqueue(samples);
+        // 入力バッファへデータを追加 (モノラルの場合は L/R に同じ信号を入れる)
+        if let Some(last) = samples.len().checked_sub(1) {
+            let mut inputs = self.input_buffer.lock().unwrap();
+            inputs.iter_mut().enumerate().for_each(|(ch, buffer)| {
+                buffer.extend(samples[ch.min(last)].iter());
             });
+        }
         self.input_signal_wait.store(true, Ordering::Release);
         // if input_is_empty {
         //     self.info(String::from("sync input buffer @ main"));
@@ -235,7 +230,7 @@ impl Plugin for FmSim {
         //     self.info(String::from("After input sync @ main"));
         // }
 
-        if self.output_buffer.lock().unwrap()[1].is_empty() {
+        while self.output_buffer.lock().unwrap()[1].len() < len {
             // self.info(String::from("output buffer is empty"));
             while !self.output_signal_wait.load(Ordering::Acquire) {
                 hint::spin_loop();
@@ -243,20 +238,22 @@ impl Plugin for FmSim {
             self.output_signal_wait.store(false, Ordering::Release);
             // self.info(String::from(??"After output sync @ main"));
         }
-        // 出力バッファからデータを取り出す
-        samples
-            .iter_mut()
-            .zip(self.output_buffer.lock().unwrap().iter_mut())
-            .for_each(|(samples, buffer)| {
-                buffer.dequeue(samples);
-            });
+        // 出力バッファからデータを取り出す (モノラルでも L/R の FIFO は同じ長さだけ進める)
+        let mut outputs = self.output_buffer.lock().unwrap();
+        outputs.iter_mut().enumerate().for_each(|(ch, buffer)| {
+            let drained = buffer.drain(..len);
+            match samples.get_mut(ch) {
+                Some(samples) => samples.iter_mut().zip(drained).for_each(|(s, d)| *s = d),
+                None => drop(drained),
+            }
+        });
         ProcessStatus::Normal
     }
     fn initialize(
         &mut self,
         _audio_io_layout: &AudioIOLayout,
         buffer_config: &BufferConfig,
-        _context: &mut impl InitContext<Self>,
+        context: &mut impl InitContext<Self>,
     ) -> bool {
         /* Add input/output Buffer */
         // self.add_socket("127.0.0.1:54635");
@@ -275,11 +272,30 @@ impl Plugin for FmSim {
         let (tx, rx) = mpsc::channel::<usize>();
         self.msg_sender = Some(tx);
         if let Ok(buf) = &mut self.output_buffer.lock() {
-            buf[0].set_len(4);
-            buf[1].set_len(4);
+            Self::prefill_output(buf);
+        }
+        self.reset_request.store(false, Ordering::Release);
+        if let Ok(buf) = &mut self.input_buffer.lock() {
+            buf.iter_mut().for_each(VecDeque::clear);
         }
         // self.re_init(buffer_config.sample_rate as f64, FmRadio::DEFAULT_BUF_SIZE);
         // self.info(format!("Initialized: fs: {}", buffer_config.sample_rate));
+        // 作成・処理に失敗した場合は入力をそのまま出力する (出力を止めるとホスト側が待ち続ける)
+        let mut fmradio = match FmRadioSim::from(
+            self.sample_rate as usize,
+            Self::DEFAULT_BUFFER_SIZE,
+            79_500_000f64,
+        ) {
+            Ok(fmradio) => Some(fmradio),
+            Err(e) => {
+                nih_error!("failed to create FmRadioSim: {e}");
+                None
+            }
+        };
+        // 出力側に先行して詰めた無音もホストから見た遅延になる
+        let latency = Self::DEFAULT_BUFFER_SIZE * Self::PREFILL_BLOCKS
+            + fmradio.as_ref().map_or(0, FmRadioSim::latency_samples);
+        context.set_latency_samples(latency as u32);
         {
             let input_buffer = Arc::clone(&self.input_buffer);
             let output_buffer = Arc::clone(&self.output_buffer);
@@ -287,26 +303,26 @@ impl Plugin for FmSim {
             // let _start_barrier = Arc::clone(&self.start_barrier);
             let wait_input = Arc::clone(&self.input_signal_wait);
             let wait_output = Arc::clone(&self.output_signal_wait);
+            let reset_request = Arc::clone(&self.reset_request);
             //
-            let buffer_size = self.buffer_size;
-            let sample_rate = self.sample_rate as usize;
+            let buffer_size = self.buffer_size * Self::RING_BUFFER_SIZE;
             let handle = std::thread::spawn(move || {
                 // let send_msg = |msg: &[u8]| {
                 //     socket.lock().unwrap().as_ref().unwrap().send(msg).unwrap();
                 // };
-                let mut l_buffer = vec![0.; buffer_size];
-                let mut r_buffer = vec![0.; buffer_size];
-                let mut l_dst_buffer = vec![0.; buffer_size];
-                let mut r_dst_buffer = vec![0.; buffer_size];
+                let mut l_buffer = Vec::with_capacity(buffer_size);
+                let mut r_buffer = Vec::with_capacity(buffer_size);
+                let mut l_dst_buffer = Vec::with_capacity(buffer_size);
+                let mut r_dst_buffer = Vec::with_capacity(buffer_size);
                 // let (input_flag, input_sig) = &*input_signal;
                 // let (output_flag, output_sig) = &*output_signal;
 
                 if rx.recv().unwrap() == 0 {
                     return 0;
                 }
-                let mut fmradio =
-                    FmRadioSim::from(sample_rate, Self::DEFAULT_BUFFER_SIZE, 79_500_000f64);
-                fmradio.init_thread();
+                if let Some(fmradio) = fmradio.as_mut() {
+                    fmradio.init_thread();
+                }
                 // send_msg(b"start processing thread");
                 loop {
                     // while  {}
@@ -318,9 +334,22 @@ impl Plugin for FmSim {
                         let mut inputs = input_buffer.lock().unwrap();
                         // send_msg(format!("input buffer len: {:?}", inputs[1].get_len()).as_bytes());
 
-                        inputs[0].dequeue(&mut l_buffer);
-                        inputs[1].dequeue(&mut r_buffer);
+                        // 溜まっている分をまとめて処理する (長さは FmRadioSim 側で吸収される)
+                        l_buffer.clear();
+                        r_buffer.clear();
+                        l_buffer.extend(inputs[0].drain(..));
+                        r_buffer.extend(inputs[1].drain(..));
+                        // reset 前の入力は reset 時に捨てられているので、ここで取り出したのは reset 後の入力のみ
+                        if reset_request.swap(false, Ordering::AcqRel) {
+                            if let Some(Err(e)) = fmradio.as_mut().map(FmRadioSim::reset) {
+                                nih_error!("failed to reset FmRadioSim: {e}");
+                                fmradio = None;
+                            }
+                            Self::prefill_output(&mut output_buffer.lock().unwrap());
+                        }
                     }
+                    l_dst_buffer.resize(l_buffer.len(), 0.);
+                    r_dst_buffer.resize(r_buffer.len(), 0.);
                     // Note: [TEST] gain code
                     // [&l_buffer,&r_buffer].iter().zip([
                     //   &mut l_dst_buffer,&mut r_dst_buffer
@@ -333,7 +362,22 @@ impl Plugin for FmSim {
                     // );
                     // Note: FM SIM CODE
                     // let start = Instant::now();
-                    fmradio.process(&l_buffer, &r_buffer, &mut l_dst_buffer, &mut r_dst_buffer);
+                    let result = fmradio.as_mut().map(|fmradio| {
+                        fmradio.process(&l_buffer, &r_buffer, &mut l_dst_buffer, &mut r_dst_buffer)
+                    });
+                    match result {
+                        Some(Ok(())) => {}
+                        Some(Err(e)) => {
+                            nih_error!("FmRadioSim stopped: {e}");
+                            fmradio = None;
+                            l_dst_buffer.copy_from_slice(&l_buffer);
+                            r_dst_buffer.copy_from_slice(&r_buffer);
+                        }
+                        None => {
+                            l_dst_buffer.copy_from_slice(&l_buffer);
+                            r_dst_buffer.copy_from_slice(&r_buffer);
+                        }
+                    }
                     // let end = start.elapsed();
                     {
                         let mut buffer = output_buffer.lock().unwrap();
@@ -341,10 +385,8 @@ impl Plugin for FmSim {
                         //     format!("output buffer len: {:?}", buffer[1].get_len()).as_bytes(),
                         // );
                         // let is_empty = buffer[1].is_empty();
-                        buffer[0].enqueue(&l_dst_buffer);
-                        // if !buffer[1].enqueue(&r_dst_buffer) {
-                        //     send_msg(b"output buffer is full");
-                        // };
+                        buffer[0].extend(l_dst_buffer.iter());
+                        buffer[1].extend(r_dst_buffer.iter());
                         wait_output.store(true, Ordering::Release);
                         // if is_empty {
                         //     send_msg(b"sync out-buffer @ processing thread");
@@ -370,6 +412,12 @@ impl Plugin for FmSim {
         // self.info("Initialized end".to_string());
         true
     }
+    // トランスポートの停止等で呼ばれる。シミュレーションの状態を作成直後に戻す
+    fn reset(&mut self) {
+        let mut inputs = self.input_buffer.lock().unwrap();
+        inputs.iter_mut().for_each(VecDeque::clear);
+        self.reset_request.store(true, Ordering::Release);
+    }
     // This can be used for cleaning up special resources like socket connections whenever the
     // plugin is deactivated. Most plugins won't need to do anything here.
     fn deactivate(&mut self) {
@@ -1,6 +1,8 @@
 use clap::Parser;
-use fm_core::FmRadioSim;
+use fm_core::{FmRadioSim, Interferer, InterfererSource};
 use hound;
+use std::error::Error;
+use std::sync::Arc;
 
 #[derive(Parser, Debug)]
 #[command(long_about = None)]
@@ -8,55 +10,77 @@ struct Args {
     fname: String,
     #[arg(short, long)]
     out: Option<String>,
+    /// 妨害局 `周波数差[Hz],電力比[dB],音源` (音源: WAVファイル, tone:周波数, noise)。複数指定可
+    #[arg(long, allow_hyphen_values = true)]
+    interferer: Vec<String>,
 }
 
 fn main() {
-    let args = Args::parse();
+    if let Err(e) = run(Args::parse()) {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
     let output_file = if args.out.is_some() {
         args.out.unwrap().clone()
     } else {
         String::from("out.wav")
     };
-    let mut reader = hound::WavReader::open(args.fname).unwrap();
+    let reader = hound::WavReader::open(args.fname)?;
     if reader.spec().channels != 2 {
-        panic!("Only stereo supported");
+        return Err("Only stereo supported".into());
     }
     if reader.spec().sample_rate != 44100 {
-        panic!("Only 44100 supported");
+        return Err("Only 44100 supported".into());
     }
     const CHUNK_SIZE: usize = 700;
+    // 振幅 1 が最大周波数偏移になるため、-1..1 に正規化して渡す
+    const I16_SCALE: f32 = 32768.;
 
     let samples = reader
         .into_samples::<i16>()
-        .map(|s| s.unwrap())
-        .collect::<Vec<i16>>();
+        .collect::<Result<Vec<i16>, _>>()?;
     let [mut l_samples, mut r_samples] = samples.chunks(2).fold(
         [Vec::<f32>::new(), Vec::<f32>::new()],
         |mut acc, samples| {
-            acc[0].push(samples[0] as f32);
-            acc[1].push(samples[1] as f32);
+            acc[0].push(samples[0] as f32 / I16_SCALE);
+            acc[1].push(samples[1] as f32 / I16_SCALE);
             acc
         },
     );
-    // let len = l_samples.len();
-    while l_samples.len() % CHUNK_SIZE != 0 {
-        l_samples.push(0f32);
-        r_samples.push(0f32);
-    }
-    let mut fm_sim = FmRadioSim::from(44100, CHUNK_SIZE, 79_500_000f64);
+    let len = l_samples.len();
+    let mut fm_sim = FmRadioSim::from(44100, CHUNK_SIZE, 79_500_000f64)?;
+    println!("kernel backend: {}", fm_sim.kernel_backends());
+    let interferers = args
+        .interferer
+        .iter()
+        .map(|spec| parse_interferer(spec))
+        .collect::<Result<Vec<_>, _>>()?;
+    fm_sim.set_interferers(&interferers)?;
     fm_sim.init_thread();
-    let mut dst_buffer: Vec<i16> = Vec::new();
-    let mut l_buffer = vec![0.; CHUNK_SIZE];
-    let mut r_buffer = vec![0.; CHUNK_SIZE];
-    l_samples
+    // 遅延分の無音を足して入力の最後まで出力させ、先頭の遅延分を捨てて入力と揃える
+    let latency = fm_sim.latency_samples();
+    l_samples.resize(len + latency, 0f32);
+    r_samples.resize(len + latency, 0f32);
+    let mut l_buffer = vec![0f32; l_samples.len()];
+    let mut r_buffer = vec![0f32; r_samples.len()];
+    for (((l, r), l_dst), r_dst) in l_samples
         .chunks(CHUNK_SIZE)
         .zip(r_samples.chunks(CHUNK_SIZE))
+        .zip(l_buffer.chunks_mut(CHUNK_SIZE))
+        .zip(r_buffer.chunks_mut(CHUNK_SIZE))
+    {
+        fm_sim.process(l, r, l_dst, r_dst)?;
+    }
+    let mut dst_buffer: Vec<i16> = Vec::with_capacity(len * 2);
+    l_buffer[latency..]
+        .iter()
+        .zip(r_buffer[latency..].iter())
         .for_each(|(l, r)| {
-            fm_sim.process(l, r, &mut l_buffer, &mut r_buffer);
-            l_buffer.iter().zip(r_buffer.iter()).for_each(|(l, r)| {
-                dst_buffer.push(*l as i16);
-                dst_buffer.push(*r as i16);
-            })
+            dst_buffer.push((*l * I16_SCALE) as i16);
+            dst_buffer.push((*r * I16_SCALE) as i16);
         });
     // output
     let spec = hound::WavSpec {
@@ -65,9 +89,54 @@ fn main() {
         bits_per_sample: 16,
         sample_format: hound::SampleFormat::Int,
     };
-    let mut writer = hound::WavWriter::create(output_file, spec).unwrap();
-    dst_buffer.into_iter().for_each(|s| {
-        writer.write_sample(s).unwrap();
-    });
-    writer.finalize().unwrap();
+    let mut writer = hound::WavWriter::create(output_file, spec)?;
+    for s in dst_buffer {
+        writer.write_sample(s)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+fn parse_interferer(spec: &str) -> Result<Interferer, Box<dyn Error>> {
+    let mut fields = spec.splitn(3, ',');
+    let (Some(offset), Some(level), Some(source)) = (fields.next(), fields.next(), fields.next())
+    else {
+        return Err(format!("invalid interferer `{spec}` (expected offset,level,source)").into());
+    };
+    let source = if source == "noise" {
+        InterfererSource::Noise { amplitude: 0.3 }
+    } else if let Some(freq) = source.strip_prefix("tone:") {
+        InterfererSource::Tone {
+            freq: freq.parse()?,
+            amplitude: 0.5,
+        }
+    } else {
+        read_interferer_wav(source)?
+    };
+    Ok(Interferer::new(source, offset.parse()?, level.parse()?))
+}
+
+/// 妨害局の音源。サンプルレートは任意で、モノラルは左右に同じ音を使う
+fn read_interferer_wav(fname: &str) -> Result<InterfererSource, Box<dyn Error>> {
+    const I16_SCALE: f32 = 32768.;
+    let reader = hound::WavReader::open(fname)?;
+    let spec = reader.spec();
+    let channels = spec.channels as usize;
+    if channels > 2 || spec.bits_per_sample != 16 {
+        return Err(format!("{fname}: only 16bit mono/stereo supported").into());
+    }
+    let samples = reader
+        .into_samples::<i16>()
+        .collect::<Result<Vec<i16>, _>>()?;
+    let channel = |c: usize| -> Arc<[f32]> {
+        samples
+            .chunks(channels)
+            .map(|s| s[c.min(channels - 1)] as f32 / I16_SCALE)
+            .collect()
+    };
+    Ok(InterfererSource::Samples {
+        sample_rate: spec.sample_rate as f64,
+        left: channel(0),
+        right: channel(1),
+    })
 }